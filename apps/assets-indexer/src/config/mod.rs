@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::path::PathBuf;
 
 use clap::Parser;
-use common::types::{ChainConfig, RedisConfig};
-use once_cell::sync::Lazy;
+use common::{
+    chains::{ChainRegistry, ChainRegistryError},
+    logging::LoggingArgs,
+    types::{ChainConfig, RedisConfig},
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -17,7 +20,12 @@ pub struct AssetsIndexerArgs {
         help = "Chain ID number to synchronize with.",
         default_value_t = 1
     )]
-    pub chain_id: usize,
+    pub chain_id: u32,
+    #[arg(
+        long,
+        help = "JSON file with additional chain definitions. Entries override bundled chains with the same id. [optional]"
+    )]
+    pub chains_file: Option<PathBuf>,
     #[arg(long, help = "Redis connection URL.")]
     pub redis_url: String,
     #[arg(long, help = "Redis stream key")]
@@ -26,49 +34,25 @@ pub struct AssetsIndexerArgs {
     pub redis_group_name: String,
     #[arg(long, help = "Database connection URL.")]
     pub db_url: String,
-    #[arg(
-        long,
-        help = "Enables debug logging. Useful for troubleshooting and development. [optional]",
-        default_value_t = false
-    )]
-    pub debug: bool,
+    #[command(flatten)]
+    pub logging: LoggingArgs,
 }
 
-static CHAIN_CONFIGS: Lazy<HashMap<usize, ChainConfig>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    m.insert(
-        1,
-        ChainConfig {
-            id: 1,
-            name: "Ethereum".to_string(),
-        },
-    );
-    m
-});
-
 #[derive(Debug, Clone)]
 pub struct Config {
     pub indexer_name: String,
     pub chain: ChainConfig,
     pub db_url: String,
     pub redis_config: RedisConfig,
-    pub debug: bool,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self::new()
-    }
+    pub logging: LoggingArgs,
 }
 
 impl Config {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, ChainRegistryError> {
         let args = AssetsIndexerArgs::parse();
-        let chain = CHAIN_CONFIGS
-            .get(&args.chain_id)
-            .expect("Default chain error.")
-            .clone();
-        Self {
+        let chain =
+            ChainRegistry::load(args.chains_file.as_deref())?.get(args.chain_id)?;
+        Ok(Self {
             indexer_name: args.indexer_name,
             chain,
             db_url: args.db_url,
@@ -77,7 +61,7 @@ impl Config {
                 stream_key: args.redis_stream_key,
                 group_name: args.redis_group_name,
             },
-            debug: args.debug,
-        }
+            logging: args.logging,
+        })
     }
 }
@@ -3,7 +3,13 @@ pub mod services;
 
 use std::sync::Arc;
 
-use common::{redis::redis_client_factory, types::SummaryLog};
+use common::{
+    codec::{Encoding, ENCODING_FIELD},
+    logging,
+    redis::redis_client_factory,
+    telemetry,
+    types::StreamPayload,
+};
 use config::Config;
 use redis::{
     streams::{StreamReadOptions, StreamReadReply},
@@ -22,8 +28,7 @@ use services::{
     },
 };
 use sqlx::postgres::PgPoolOptions;
-use tracing_appender::rolling;
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing::Instrument;
 
 async fn ensure_stream_and_group_exist(
     conn: &mut redis::aio::Connection,
@@ -58,21 +63,9 @@ async fn ensure_stream_and_group_exist(
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config = Config::new();
-
-    let file_appender = rolling::daily("./logs", "assets-indexer.log");
-    let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-
-    let debug_level = if config.debug { "debug" } else { "info" };
-    let console_layer = fmt::layer().with_writer(std::io::stdout);
-    let file_layer = fmt::layer().with_writer(non_blocking);
-    let filter_layer = EnvFilter::new(debug_level);
+    let config = Config::new()?;
 
-    tracing_subscriber::registry()
-        .with(console_layer)
-        .with(file_layer)
-        .with(filter_layer)
-        .init();
+    let _logging = logging::init(&config.logging, "assets-indexer")?;
 
     let redis_config = config.redis_config;
 
@@ -107,8 +100,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         StreamReadOptions::default().group(&group, consumer_name);
 
     loop {
+        // Debug only: the stream is polled without blocking, so most reads
+        // return nothing.
         let results: RedisResult<StreamReadReply> = redis_conn
             .xread_options(&[&stream_key], &[">"], &opts)
+            .instrument(tracing::debug_span!("stream_read"))
             .await;
 
         match results {
@@ -116,34 +112,77 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 for stream in reply.keys {
                     for message in stream.ids {
                         if let Some(Value::Data(bytes)) = message.map.get("message") {
-                            let json_data =
-                                String::from_utf8(bytes.clone()).unwrap_or_default();
-
-                            let logs: Vec<SummaryLog> = serde_json::from_str(&json_data)?;
-                            for log in logs {
-                                let address = log.address;
-                                let data = log.data;
-                                let topics = log.topics;
-                                processor
-                                    .process_and_store_if_apply(&EventProcessorRequest {
-                                        tx_hash: log.transaction_hash.unwrap_or_default(),
-                                        tx_index: log
-                                            .transaction_index
-                                            .unwrap_or_default(),
-                                        address,
-                                        data,
-                                        topic0: topics
-                                            .first()
-                                            .cloned()
-                                            .unwrap_or_default(),
-                                        block_number: log.block_number,
-                                        chain_id: config.chain.clone().id,
-                                        topic1: topics.get(1).cloned(),
-                                        topic2: topics.get(2).cloned(),
-                                        topic3: topics.get(3).cloned(),
-                                    })
-                                    .await;
+                            let encoding = match message.map.get(ENCODING_FIELD) {
+                                Some(Value::Data(name)) => {
+                                    match String::from_utf8_lossy(name)
+                                        .parse::<Encoding>()
+                                    {
+                                        Ok(encoding) => encoding,
+                                        Err(e) => {
+                                            tracing::error!(
+                                                "Skipping message {}: {}",
+                                                message.id,
+                                                e
+                                            );
+                                            continue;
+                                        }
+                                    }
+                                }
+                                _ => Encoding::default(),
+                            };
+
+                            let decode_span =
+                                tracing::info_span!("decode", message_id = %message.id);
+                            let envelope = match decode_span
+                                .in_scope(|| encoding.decode(bytes, config.chain.id))
+                            {
+                                Ok(envelope) => envelope,
+                                Err(e) => {
+                                    tracing::error!(
+                                        "Skipping message {}: {}",
+                                        message.id,
+                                        e
+                                    );
+                                    continue;
+                                }
+                            };
+
+                            // Continues the trace of the block the message was
+                            // published for.
+                            let trace_context = envelope.trace_context.as_deref();
+                            telemetry::set_parent(&decode_span, trace_context);
+                            let index_span = tracing::info_span!(
+                                "index",
+                                message_id = %message.id,
+                                block_number = envelope.block_number
+                            );
+                            telemetry::set_parent(&index_span, trace_context);
+
+                            let logs = match envelope.payload {
+                                StreamPayload::Logs(logs) => logs,
+                                payload => {
+                                    tracing::debug!(
+                                        "Ignoring {} message {}",
+                                        payload.kind(),
+                                        message.id
+                                    );
+                                    continue;
+                                }
+                            };
+                            async {
+                                for log in logs {
+                                    processor
+                                        .process_and_store_if_apply(
+                                            &EventProcessorRequest::from_log(
+                                                log,
+                                                config.chain.id,
+                                            ),
+                                        )
+                                        .await;
+                                }
                             }
+                            .instrument(index_span)
+                            .await;
                         }
                     }
                 }
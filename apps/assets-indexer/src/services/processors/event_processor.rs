@@ -1,6 +1,8 @@
 use std::fmt;
 
 use async_trait::async_trait;
+use common::types::SummaryLog;
+use ethers::utils::hex;
 
 #[derive(Debug)]
 pub enum ProcessorError {
@@ -56,6 +58,31 @@ pub struct EventProcessorRequest {
     pub topic3: Option<String>,
 }
 
+impl EventProcessorRequest {
+    /// Renders a stream log as the hex strings the processors decode.
+    pub fn from_log(log: SummaryLog, chain_id: u32) -> Self {
+        let mut topics = log
+            .topics
+            .into_iter()
+            .map(|topic| format!("0x{}", hex::encode(topic)));
+        Self {
+            tx_hash: log
+                .transaction_hash
+                .map(|hash| format!("0x{}", hex::encode(hash)))
+                .unwrap_or_default(),
+            tx_index: log.transaction_index.unwrap_or_default(),
+            address: format!("0x{}", hex::encode(log.address)),
+            data: format!("0x{}", hex::encode(&log.data)),
+            chain_id,
+            block_number: log.block_number.unwrap_or_default(),
+            topic0: topics.next().unwrap_or_default(),
+            topic1: topics.next(),
+            topic2: topics.next(),
+            topic3: topics.next(),
+        }
+    }
+}
+
 pub struct EventProcessorService {
     processors: Vec<Box<dyn EventProcessor>>,
 }
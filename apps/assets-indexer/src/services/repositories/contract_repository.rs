@@ -26,6 +26,7 @@ impl ContractRepository {
 
 #[async_trait]
 impl ContractRepositoryTrait for ContractRepository {
+    #[tracing::instrument(skip(self))]
     async fn get_or_create_contract(
         &self,
         address: &str,
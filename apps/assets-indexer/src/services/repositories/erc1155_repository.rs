@@ -37,6 +37,7 @@ impl Erc1155Repository {
 
 #[async_trait]
 impl Erc1155TransferTrait for Erc1155Repository {
+    #[tracing::instrument(skip_all, fields(table = "erc1155_transfer"))]
     async fn insert_transfer(
         &self,
         transfer: Erc1155TransferData,
@@ -36,6 +36,7 @@ impl Erc721Repository {
 
 #[async_trait]
 impl Erc721TransferTrait for Erc721Repository {
+    #[tracing::instrument(skip_all, fields(table = "erc721_transfer"))]
     async fn insert_transfer(
         &self,
         transfer: Erc721TransferData,
@@ -1,10 +1,20 @@
-use std::sync::Arc;
+use std::{
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+};
 
 use async_trait::async_trait;
 use ethers::{
     providers::{Http, Middleware, Provider, ProviderError},
-    types::{Block, Transaction, TransactionReceipt, H256},
+    types::{
+        Block, BlockNumber, Bytes, CallFrame, Transaction, TransactionReceipt, H160, H256,
+    },
 };
+use serde::{Deserialize, Serialize};
 
 #[async_trait]
 pub trait BlockchainClientTrait: Clone + Send + Sync + 'static {
@@ -17,11 +27,286 @@ pub trait BlockchainClientTrait: Clone + Send + Sync + 'static {
         tx_hash: H256,
     ) -> Result<Option<TransactionReceipt>, ProviderError>;
     async fn get_block_number(&self) -> Result<u64, ProviderError>;
+    async fn get_chain_id(&self) -> Result<u64, ProviderError>;
+    async fn get_block_hash(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<H256>, ProviderError>;
+    /// The block's timestamp, in seconds since the Unix epoch.
+    async fn get_block_timestamp(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<u64>, ProviderError>;
+    /// The call tree of each of the block's transactions, in order.
+    async fn trace_block(
+        &self,
+        block_number: u64,
+    ) -> Result<Vec<TransactionTrace>, ProviderError>;
+    async fn get_code(
+        &self,
+        address: H160,
+        block_number: u64,
+    ) -> Result<Bytes, ProviderError>;
+    /// Fails over from the active provider, e.g. after it served inconsistent
+    /// data. Returns whether another provider took over.
+    async fn switch_provider(&self) -> bool;
+}
+
+/// One transaction's entry in a `debug_traceBlockByNumber` response with
+/// geth's `callTracer`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionTrace {
+    /// Missing from nodes older than geth 1.11.
+    #[serde(rename = "txHash", default, skip_serializing_if = "Option::is_none")]
+    pub transaction_hash: Option<H256>,
+    pub result: CallFrame,
+}
+
+#[derive(Debug)]
+pub enum ChainVerificationError {
+    ChainIdMismatch(u64, u64, String),
+    GenesisMismatch(H256, H256),
+    MissingGenesis(String),
+    NoVerifiedProvider(u64),
+}
+
+impl fmt::Display for ChainVerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainVerificationError::ChainIdMismatch(expected, actual, url) => write!(
+                f,
+                "Chain Id Mismatch: expected {}, RPC {} serves {}",
+                expected, url, actual
+            ),
+            ChainVerificationError::GenesisMismatch(expected, actual) => write!(
+                f,
+                "Genesis Mismatch: expected {:?}, RPC serves {:?}",
+                expected, actual
+            ),
+            ChainVerificationError::MissingGenesis(url) => {
+                write!(f, "Missing Genesis: RPC {} returned no block 0", url)
+            }
+            ChainVerificationError::NoVerifiedProvider(chain_id) => write!(
+                f,
+                "No Verified Provider: no reachable RPC serves chain {}",
+                chain_id
+            ),
+        }
+    }
 }
 
+impl std::error::Error for ChainVerificationError {}
+
+struct RpcEndpoint {
+    url: String,
+    provider: Arc<Provider<Http>>,
+    rejected: AtomicBool,
+}
+
+/// JSON-RPC client for one chain, backed by one or more providers.
+///
+/// Requests go to the active provider. When it fails, the client fails over
+/// to the next provider, but only after checking with `eth_chainId` (and the
+/// genesis hash, once pinned) that it serves the expected chain. Providers
+/// that serve another chain are rejected for the rest of the process.
 #[derive(Clone)]
 pub struct BlockchainClient {
-    pub provider: Arc<Provider<Http>>,
+    endpoints: Arc<Vec<RpcEndpoint>>,
+    active: Arc<AtomicUsize>,
+    chain_id: u64,
+    genesis_hash: Arc<OnceLock<H256>>,
+}
+
+impl BlockchainClient {
+    /// Builds a client and activates the first reachable provider that serves
+    /// `chain_id`.
+    pub async fn connect(
+        urls: &[String],
+        chain_id: u64,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let endpoints = urls
+            .iter()
+            .map(|url| {
+                Ok(RpcEndpoint {
+                    url: url.clone(),
+                    provider: Arc::new(Provider::<Http>::try_from(url.as_str())?),
+                    rejected: AtomicBool::new(false),
+                })
+            })
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error + Send + Sync>>>()?;
+
+        let client = Self {
+            endpoints: Arc::new(endpoints),
+            active: Arc::new(AtomicUsize::new(0)),
+            chain_id,
+            genesis_hash: Arc::new(OnceLock::new()),
+        };
+
+        let mut last_error = None;
+        for index in 0..client.endpoints.len() {
+            match client.verify_endpoint(index).await {
+                Ok(()) => {
+                    client.active.store(index, Ordering::SeqCst);
+                    return Ok(client);
+                }
+                Err(error) => {
+                    tracing::error!(
+                        "RPC provider {} failed verification: {}",
+                        client.endpoints[index].url,
+                        error
+                    );
+                    // A provider serving another chain is a configuration
+                    // error and takes precedence over unreachable providers.
+                    if error.is::<ChainVerificationError>() || last_error.is_none() {
+                        last_error = Some(error);
+                    }
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            Box::new(ChainVerificationError::NoVerifiedProvider(chain_id))
+        }))
+    }
+
+    /// Pins the genesis hash every provider must serve from now on.
+    pub fn pin_genesis_hash(&self, genesis_hash: H256) {
+        let _ = self.genesis_hash.set(genesis_hash);
+    }
+
+    pub fn active_url(&self) -> &str {
+        &self.endpoints[self.active.load(Ordering::SeqCst)].url
+    }
+
+    async fn verify_endpoint(
+        &self,
+        index: usize,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let endpoint = &self.endpoints[index];
+
+        let chain_id = endpoint.provider.get_chainid().await?.as_u64();
+        if chain_id != self.chain_id {
+            endpoint.rejected.store(true, Ordering::SeqCst);
+            return Err(Box::new(ChainVerificationError::ChainIdMismatch(
+                self.chain_id,
+                chain_id,
+                endpoint.url.clone(),
+            )));
+        }
+
+        if let Some(expected) = self.genesis_hash.get() {
+            let genesis_hash = endpoint
+                .provider
+                .get_block(BlockNumber::Number(0.into()))
+                .await?
+                .and_then(|block| block.hash)
+                .ok_or_else(|| {
+                    ChainVerificationError::MissingGenesis(endpoint.url.clone())
+                })?;
+            if genesis_hash != *expected {
+                endpoint.rejected.store(true, Ordering::SeqCst);
+                return Err(Box::new(ChainVerificationError::GenesisMismatch(
+                    *expected,
+                    genesis_hash,
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves to the next provider that passes verification. When none does,
+    /// checks the failed provider again, so that one now serving another
+    /// chain is rejected instead of retried.
+    async fn failover(&self, failed: usize) {
+        let total = self.endpoints.len();
+        for offset in 1..total {
+            if self.active.load(Ordering::SeqCst) != failed {
+                // Another request already moved to a healthy provider.
+                return;
+            }
+
+            let candidate = (failed + offset) % total;
+            if self.endpoints[candidate].rejected.load(Ordering::SeqCst) {
+                continue;
+            }
+
+            match self.verify_endpoint(candidate).await {
+                Ok(()) => {
+                    if self
+                        .active
+                        .compare_exchange(
+                            failed,
+                            candidate,
+                            Ordering::SeqCst,
+                            Ordering::SeqCst,
+                        )
+                        .is_ok()
+                    {
+                        tracing::warn!(
+                            "Failing over from RPC provider {} to {}",
+                            self.endpoints[failed].url,
+                            self.endpoints[candidate].url
+                        );
+                    }
+                    return;
+                }
+                Err(error) => {
+                    tracing::error!(
+                        "RPC provider {} failed verification: {}",
+                        self.endpoints[candidate].url,
+                        error
+                    );
+                }
+            }
+        }
+
+        if self.active.load(Ordering::SeqCst) == failed
+            && !self.endpoints[failed].rejected.load(Ordering::SeqCst)
+        {
+            if let Err(error) = self.verify_endpoint(failed).await {
+                tracing::error!(
+                    "RPC provider {} failed verification: {}",
+                    self.endpoints[failed].url,
+                    error
+                );
+            }
+        }
+    }
+
+    async fn request<T, F, Fut>(&self, call: F) -> Result<T, ProviderError>
+    where
+        F: Fn(Arc<Provider<Http>>) -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+    {
+        let mut attempts = 0;
+        loop {
+            let index = self.active.load(Ordering::SeqCst);
+            let endpoint = &self.endpoints[index];
+            if endpoint.rejected.load(Ordering::SeqCst) {
+                self.failover(index).await;
+                if self.active.load(Ordering::SeqCst) == index {
+                    return Err(ProviderError::CustomError(
+                        ChainVerificationError::NoVerifiedProvider(self.chain_id)
+                            .to_string(),
+                    ));
+                }
+                continue;
+            }
+
+            match call(endpoint.provider.clone()).await {
+                Ok(result) => return Ok(result),
+                Err(error) => {
+                    attempts += 1;
+                    if attempts >= self.endpoints.len() {
+                        return Err(error);
+                    }
+                    tracing::debug!("RPC provider {} failed: {}", endpoint.url, error);
+                    self.failover(index).await;
+                }
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -30,18 +315,86 @@ impl BlockchainClientTrait for BlockchainClient {
         &self,
         block_number: u64,
     ) -> Result<Option<Block<Transaction>>, ProviderError> {
-        self.provider.clone().get_block_with_txs(block_number).await
+        self.request(
+            |provider| async move { provider.get_block_with_txs(block_number).await },
+        )
+        .await
     }
 
     async fn get_transaction_receipt(
         &self,
         tx_hash: H256,
     ) -> Result<Option<TransactionReceipt>, ProviderError> {
-        self.provider.clone().get_transaction_receipt(tx_hash).await
+        self.request(
+            |provider| async move { provider.get_transaction_receipt(tx_hash).await },
+        )
+        .await
     }
 
     async fn get_block_number(&self) -> Result<u64, ProviderError> {
-        let result = self.provider.clone().get_block_number().await?;
+        let result = self
+            .request(|provider| async move { provider.get_block_number().await })
+            .await?;
+        Ok(result.as_u64())
+    }
+
+    async fn get_chain_id(&self) -> Result<u64, ProviderError> {
+        let result = self
+            .request(|provider| async move { provider.get_chainid().await })
+            .await?;
         Ok(result.as_u64())
     }
+
+    async fn get_block_hash(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<H256>, ProviderError> {
+        let block = self
+            .request(|provider| async move { provider.get_block(block_number).await })
+            .await?;
+        Ok(block.and_then(|block| block.hash))
+    }
+
+    async fn get_block_timestamp(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<u64>, ProviderError> {
+        let block = self
+            .request(|provider| async move { provider.get_block(block_number).await })
+            .await?;
+        Ok(block.map(|block| block.timestamp.low_u64()))
+    }
+
+    async fn trace_block(
+        &self,
+        block_number: u64,
+    ) -> Result<Vec<TransactionTrace>, ProviderError> {
+        let block_number = BlockNumber::Number(block_number.into());
+        self.request(|provider| async move {
+            provider
+                .request(
+                    "debug_traceBlockByNumber",
+                    (block_number, serde_json::json!({ "tracer": "callTracer" })),
+                )
+                .await
+        })
+        .await
+    }
+
+    async fn get_code(
+        &self,
+        address: H160,
+        block_number: u64,
+    ) -> Result<Bytes, ProviderError> {
+        self.request(|provider| async move {
+            provider.get_code(address, Some(block_number.into())).await
+        })
+        .await
+    }
+
+    async fn switch_provider(&self) -> bool {
+        let active = self.active.load(Ordering::SeqCst);
+        self.failover(active).await;
+        self.active.load(Ordering::SeqCst) != active
+    }
 }
@@ -1,2 +1,3 @@
 pub mod blockchain_client;
+pub mod recording_client;
 pub mod redis_client;
@@ -0,0 +1,188 @@
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use async_trait::async_trait;
+use ethers::{
+    providers::ProviderError,
+    types::{Block, Bytes, Transaction, TransactionReceipt, H160, H256},
+    utils::hex,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::fs;
+
+use super::blockchain_client::{BlockchainClientTrait, TransactionTrace};
+
+/// Tells apart the temporary files of concurrent writes.
+static WRITE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Records JSON-RPC responses to a directory, or replays them without a node.
+///
+/// Each response is stored in its own file, named after the method and its
+/// parameters, e.g. `eth_getTransactionReceipt-0xab...json`. A later call
+/// overwrites the file, so a replayed `eth_blockNumber` returns the last head
+/// seen while recording. Failed requests are not recorded. Replays are
+/// strict: a request without a recording fails.
+#[derive(Clone)]
+pub struct RecordingClient<C: BlockchainClientTrait> {
+    /// Absent when replaying.
+    inner: Option<C>,
+    dir: Arc<PathBuf>,
+}
+
+impl<C: BlockchainClientTrait> RecordingClient<C> {
+    pub async fn record(inner: C, dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).await?;
+        Ok(Self {
+            inner: Some(inner),
+            dir: Arc::new(dir),
+        })
+    }
+
+    pub fn replay(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            inner: None,
+            dir: Arc::new(dir.into()),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    async fn call<T, F, Fut>(&self, key: String, request: F) -> Result<T, ProviderError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(C) -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+    {
+        let path = self.dir.join(format!("{}.json", key));
+        let Some(inner) = &self.inner else {
+            let content = fs::read(&path).await.map_err(|_| {
+                ProviderError::CustomError(format!(
+                    "Replay Error: no recorded response for {} in {}",
+                    key,
+                    self.dir.display()
+                ))
+            })?;
+            return Ok(serde_json::from_slice(&content)?);
+        };
+
+        let response = request(inner.clone()).await?;
+        let content = serde_json::to_vec_pretty(&response)?;
+        let temporary = path.with_extension(format!(
+            "json.{}.tmp",
+            WRITE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = match fs::write(&temporary, content).await {
+            Ok(()) => fs::rename(&temporary, &path).await,
+            Err(error) => Err(error),
+        };
+        written.map_err(|e| {
+            ProviderError::CustomError(format!(
+                "Record Error: {}. Path: {}",
+                e,
+                path.display()
+            ))
+        })?;
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl<C: BlockchainClientTrait> BlockchainClientTrait for RecordingClient<C> {
+    async fn get_block_with_txs(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<Block<Transaction>>, ProviderError> {
+        self.call(
+            format!("eth_getBlockByNumber-{}-full", block_number),
+            |inner| async move { inner.get_block_with_txs(block_number).await },
+        )
+        .await
+    }
+
+    async fn get_transaction_receipt(
+        &self,
+        tx_hash: H256,
+    ) -> Result<Option<TransactionReceipt>, ProviderError> {
+        self.call(
+            format!("eth_getTransactionReceipt-0x{}", hex::encode(tx_hash)),
+            |inner| async move { inner.get_transaction_receipt(tx_hash).await },
+        )
+        .await
+    }
+
+    async fn get_block_number(&self) -> Result<u64, ProviderError> {
+        self.call("eth_blockNumber".to_string(), |inner| async move {
+            inner.get_block_number().await
+        })
+        .await
+    }
+
+    async fn get_chain_id(&self) -> Result<u64, ProviderError> {
+        self.call("eth_chainId".to_string(), |inner| async move {
+            inner.get_chain_id().await
+        })
+        .await
+    }
+
+    async fn get_block_hash(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<H256>, ProviderError> {
+        self.call(
+            format!("eth_getBlockByNumber-{}", block_number),
+            |inner| async move { inner.get_block_hash(block_number).await },
+        )
+        .await
+    }
+
+    async fn get_block_timestamp(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<u64>, ProviderError> {
+        self.call(
+            format!("eth_getBlockByNumber-{}-timestamp", block_number),
+            |inner| async move { inner.get_block_timestamp(block_number).await },
+        )
+        .await
+    }
+
+    async fn trace_block(
+        &self,
+        block_number: u64,
+    ) -> Result<Vec<TransactionTrace>, ProviderError> {
+        self.call(
+            format!("debug_traceBlockByNumber-{}", block_number),
+            |inner| async move { inner.trace_block(block_number).await },
+        )
+        .await
+    }
+
+    async fn get_code(
+        &self,
+        address: H160,
+        block_number: u64,
+    ) -> Result<Bytes, ProviderError> {
+        self.call(
+            format!("eth_getCode-0x{}-{}", hex::encode(address), block_number),
+            |inner| async move { inner.get_code(address, block_number).await },
+        )
+        .await
+    }
+
+    /// Replays have a single source of responses.
+    async fn switch_provider(&self) -> bool {
+        match &self.inner {
+            Some(inner) => inner.switch_provider().await,
+            None => false,
+        }
+    }
+}
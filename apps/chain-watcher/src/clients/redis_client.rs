@@ -1,44 +1,331 @@
-use std::sync::Arc;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use async_trait::async_trait;
 use bb8::Pool;
 use bb8_redis::redis::AsyncCommands;
 use bb8_redis::RedisConnectionManager;
-use common::types::SummaryLog;
-use ethers::types::Log;
-use redis::RedisError;
+use clap::ValueEnum;
+use common::{
+    codec::{CodecError, Encoding, ENCODING_FIELD},
+    types::{StreamEnvelope, StreamPayload},
+};
+use ethers::utils::{hex, keccak256};
+use redis::{aio::ConnectionLike, streams::StreamRangeReply, RedisError};
+
+/// Adds the entry only if its dedup key was not already set, so both happen
+/// atomically. KEYS: stream, dedup key. ARGV: ttl, then field/value pairs.
+const DEDUP_XADD_SCRIPT: &str = r#"
+if redis.call('SET', KEYS[2], '1', 'NX', 'EX', ARGV[1]) then
+    return redis.call('XADD', KEYS[1], '*', unpack(ARGV, 2))
+end
+return false
+"#;
+
+/// Adds the entry unless a leader with a greater fencing token has written to
+/// the stream, optionally with the dedup check above. KEYS: stream, fence key,
+/// then an optional dedup key. ARGV: token, entry id, ttl, then field/value
+/// pairs.
+const FENCED_XADD_SCRIPT: &str = r#"
+local fence = tonumber(redis.call('GET', KEYS[2]) or '0')
+local token = tonumber(ARGV[1])
+if token < fence then
+    return redis.error_reply('FENCED fencing token ' .. ARGV[1] .. ' is below ' .. fence)
+end
+if token > fence then
+    redis.call('SET', KEYS[2], ARGV[1])
+end
+if KEYS[3] and not redis.call('SET', KEYS[3], '1', 'NX', 'EX', ARGV[3]) then
+    return false
+end
+return redis.call('XADD', KEYS[1], ARGV[2], unpack(ARGV, 4))
+"#;
+
+/// Error code of [`FENCED_XADD_SCRIPT`]'s rejection.
+const FENCED_ERROR_CODE: &str = "FENCED";
+
+/// How stream entry ids are chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StreamIdMode {
+    /// Redis assigns the id (`XADD key *`).
+    Auto,
+    /// The id is `<block number>-<transaction index>`. A block published
+    /// again is not duplicated, but one that was never published cannot be
+    /// added below the last entry, so blocks can only be published in order.
+    BlockTx,
+    /// Each message claims a key derived from a hash of its content, for
+    /// `dedup_ttl` seconds, and is skipped if the key already exists.
+    ContentHash,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishOutcome {
+    Added,
+    /// The message was already in the stream and was not added again.
+    Duplicate,
+}
+
+#[derive(Debug)]
+pub enum PublishError {
+    PoolError(String),
+    RedisError(RedisError),
+    EncodingError(CodecError),
+    /// Another instance became leader since this one's fencing token was
+    /// issued.
+    Fenced(String),
+    /// A `block-tx` entry id below the stream's last entry that is not in
+    /// the stream.
+    OutOfOrder(String),
+}
+
+impl fmt::Display for PublishError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublishError::PoolError(error) => write!(f, "Pool Error: {}", error),
+            PublishError::RedisError(error) => write!(f, "Redis Error: {}", error),
+            PublishError::EncodingError(error) => write!(f, "{}", error),
+            PublishError::Fenced(error) => write!(f, "Fenced: {}", error),
+            PublishError::OutOfOrder(error) => write!(f, "Out of order: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for PublishError {}
+
+impl From<RedisError> for PublishError {
+    fn from(error: RedisError) -> Self {
+        if error.code() == Some(FENCED_ERROR_CODE) {
+            return PublishError::Fenced(error.detail().unwrap_or_default().to_string());
+        }
+        PublishError::RedisError(error)
+    }
+}
+
+impl From<CodecError> for PublishError {
+    fn from(error: CodecError) -> Self {
+        PublishError::EncodingError(error)
+    }
+}
+
+impl PublishError {
+    /// Whether sending the same messages again may succeed.
+    pub fn is_transient(&self) -> bool {
+        !matches!(
+            self,
+            PublishError::EncodingError(_)
+                | PublishError::Fenced(_)
+                | PublishError::OutOfOrder(_)
+        )
+    }
+}
 
 #[async_trait]
 pub trait RedisClientTrait: Clone + Send + Sync + 'static {
-    async fn send_logs(
+    /// Adds `envelopes` to the stream in order, returning one outcome per
+    /// envelope.
+    async fn send_messages(
         &self,
-        key_stream: String,
-        logs: Vec<Log>,
-    ) -> Result<(), RedisError>;
+        key_stream: &str,
+        envelopes: &[StreamEnvelope],
+    ) -> Result<Vec<PublishOutcome>, PublishError>;
 }
 
 #[derive(Clone)]
 pub struct RedisClient {
     pub pool: Arc<Pool<RedisConnectionManager>>,
+    pub encoding: Encoding,
+    pub id_mode: StreamIdMode,
+    pub dedup_ttl: u64,
+    /// Messages sent per `MULTI`/`EXEC` round trip.
+    pub batch_size: usize,
+    /// Token of the leader lease this instance holds, checked against the
+    /// stream's fence key on every write. Zero while leader election is off.
+    pub fencing_token: Arc<AtomicU64>,
+}
+
+impl RedisClient {
+    /// The explicit entry id for `envelope` in `block-tx` mode. A block's
+    /// contract creations take the last sequence number of the block, after
+    /// its transactions. Messages without a transaction index, such as
+    /// headers, reverts and pending messages, keep Redis-assigned ids.
+    fn entry_id(envelope: &StreamEnvelope) -> Option<(u64, u64)> {
+        let transaction_index = match &envelope.payload {
+            StreamPayload::Logs(logs) => logs.first()?.transaction_index?,
+            StreamPayload::Transaction(transaction) => transaction.transaction_index?,
+            StreamPayload::ContractCreations(_) => u64::MAX,
+            StreamPayload::Revert
+            | StreamPayload::Header(_)
+            | StreamPayload::PendingTransfer(_)
+            | StreamPayload::PendingResolution(_) => return None,
+        };
+        Some((envelope.block_number, transaction_index))
+    }
+
+    /// Covers what identifies the message, but not the producer or the
+    /// finality status, which may differ when a block is published again.
+    fn dedup_key(
+        key_stream: &str,
+        envelope: &StreamEnvelope,
+    ) -> Result<String, PublishError> {
+        let content = serde_json::to_vec(&(
+            envelope.chain_id,
+            envelope.block_number,
+            envelope.block_hash,
+            &envelope.payload,
+        ))
+        .map_err(|e| {
+            PublishError::EncodingError(CodecError::EncodeError(e.to_string()))
+        })?;
+        Ok(format!(
+            "{}:dedup:{}",
+            key_stream,
+            hex::encode(keccak256(content))
+        ))
+    }
+
+    fn fence_key(key_stream: &str) -> String {
+        format!("{}:fence", key_stream)
+    }
+
+    /// The id of the stream's last entry, if any.
+    async fn last_entry_id(
+        conn: &mut (impl ConnectionLike + Send),
+        key_stream: &str,
+    ) -> Result<Option<(u64, u64)>, PublishError> {
+        let reply: StreamRangeReply =
+            conn.xrevrange_count(key_stream, "+", "-", 1).await?;
+        Ok(reply.ids.first().and_then(|entry| {
+            let (milliseconds, sequence) = entry.id.split_once('-')?;
+            Some((milliseconds.parse().ok()?, sequence.parse().ok()?))
+        }))
+    }
+
+    /// Whether the stream has an entry with `id`.
+    async fn has_entry(
+        conn: &mut (impl ConnectionLike + Send),
+        key_stream: &str,
+        id: &str,
+    ) -> Result<bool, PublishError> {
+        let reply: StreamRangeReply = conn.xrange_count(key_stream, id, id, 1).await?;
+        Ok(!reply.ids.is_empty())
+    }
 }
 
 #[async_trait]
 impl RedisClientTrait for RedisClient {
-    async fn send_logs(
+    async fn send_messages(
         &self,
-        key_stream: String,
-        logs: Vec<Log>,
-    ) -> Result<(), RedisError> {
-        if logs.is_empty() {
-            return Ok(());
+        key_stream: &str,
+        envelopes: &[StreamEnvelope],
+    ) -> Result<Vec<PublishOutcome>, PublishError> {
+        if envelopes.is_empty() {
+            return Ok(Vec::new());
         }
-        let pool_cloned = self.pool.clone();
-        let mut conn = pool_cloned.get().await.expect("Pool connection Error");
 
-        let summary_logs: Vec<SummaryLog> =
-            logs.iter().map(|log| log.clone().into()).collect();
-        let message: String = serde_json::to_string(&summary_logs).unwrap();
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|e| PublishError::PoolError(e.to_string()))?;
+
+        // Entries at or below the last one are checked up front, so a retried
+        // block does not fail the whole MULTI.
+        let last_entry_id = match self.id_mode {
+            StreamIdMode::BlockTx => Self::last_entry_id(&mut *conn, key_stream).await?,
+            _ => None,
+        };
+
+        let encoding = self.encoding.to_string();
+        let fencing_token = self.fencing_token.load(Ordering::SeqCst);
+        let mut outcomes = Vec::with_capacity(envelopes.len());
+        for chunk in envelopes.chunks(self.batch_size) {
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            let mut chunk_outcomes = Vec::with_capacity(chunk.len());
+
+            for envelope in chunk {
+                let message = self.encoding.encode(envelope)?;
+
+                let mut entry_id = "*".to_string();
+                let mut dedup_key = None;
+                match self.id_mode {
+                    StreamIdMode::Auto => {}
+                    StreamIdMode::BlockTx => {
+                        if let Some(id) = Self::entry_id(envelope) {
+                            entry_id = format!("{}-{}", id.0, id.1);
+                            if Some(id) <= last_entry_id {
+                                if !Self::has_entry(&mut *conn, key_stream, &entry_id)
+                                    .await?
+                                {
+                                    return Err(PublishError::OutOfOrder(format!(
+                                        "entry {} is below the last entry of {}",
+                                        entry_id, key_stream
+                                    )));
+                                }
+                                chunk_outcomes.push(Some(PublishOutcome::Duplicate));
+                                continue;
+                            }
+                        }
+                    }
+                    StreamIdMode::ContentHash => {
+                        dedup_key = Some(Self::dedup_key(key_stream, envelope)?);
+                    }
+                }
+
+                let mut cmd;
+                if fencing_token > 0 {
+                    cmd = redis::cmd("EVAL");
+                    cmd.arg(FENCED_XADD_SCRIPT)
+                        .arg(2 + dedup_key.is_some() as usize)
+                        .arg(key_stream)
+                        .arg(Self::fence_key(key_stream))
+                        // Writes nothing without a dedup key.
+                        .arg(dedup_key.as_deref())
+                        .arg(fencing_token)
+                        .arg(&entry_id)
+                        .arg(self.dedup_ttl);
+                } else if let Some(dedup_key) = &dedup_key {
+                    cmd = redis::cmd("EVAL");
+                    cmd.arg(DEDUP_XADD_SCRIPT)
+                        .arg(2)
+                        .arg(key_stream)
+                        .arg(dedup_key)
+                        .arg(self.dedup_ttl);
+                } else {
+                    cmd = redis::cmd("XADD");
+                    cmd.arg(key_stream).arg(&entry_id);
+                }
+                cmd.arg("message")
+                    .arg(message.as_slice())
+                    .arg(ENCODING_FIELD)
+                    .arg(&encoding);
+
+                if fencing_token == 0 && dedup_key.is_none() {
+                    pipe.add_command(cmd).ignore();
+                    chunk_outcomes.push(Some(PublishOutcome::Added));
+                } else {
+                    pipe.add_command(cmd);
+                    // Known once the script has run.
+                    chunk_outcomes.push(None);
+                }
+            }
+
+            let script_results: Vec<Option<String>> =
+                pipe.query_async(&mut *conn).await?;
+            let mut script_results = script_results.into_iter();
+            outcomes.extend(chunk_outcomes.into_iter().map(|outcome| {
+                outcome.unwrap_or_else(|| match script_results.next().flatten() {
+                    Some(_) => PublishOutcome::Added,
+                    None => PublishOutcome::Duplicate,
+                })
+            }));
+        }
 
-        conn.xadd(key_stream, "*", &[("message", &message)]).await
+        Ok(outcomes)
     }
 }
@@ -1,7 +1,21 @@
-use clap::Parser;
-use common::types::{ChainConfig, RedisConfig};
-use hashbrown::HashMap;
-use once_cell::sync::Lazy;
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDate, NaiveTime};
+use clap::{Parser, Subcommand};
+use common::{
+    chains::{ChainRegistry, ChainRegistryError},
+    codec::Encoding,
+    logging::LoggingArgs,
+    types::{ChainConfig, RedisConfig},
+};
+use serde::{Deserialize, Deserializer};
+
+use crate::clients::redis_client::StreamIdMode;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -15,89 +29,605 @@ pub struct ChainWatcherArgs {
         default_value_t = false
     )]
     pub reset: bool,
+    #[command(flatten)]
+    pub logging: LoggingArgs,
     #[arg(
         long,
-        help = "Enables debug logging. Useful for troubleshooting and development. [optional]",
-        default_value_t = false
+        help = "Chain ID number to synchronize with.",
+        default_value_t = 1
     )]
-    pub debug: bool,
+    pub chain_id: u32,
     #[arg(
         long,
-        help = "Chain ID number to synchronize with.",
-        default_value_t = 1
+        help = "JSON file with additional chain definitions. Entries override bundled chains with the same id. [optional]"
+    )]
+    pub chains_file: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "JSON file listing the chains to watch, each with its own RPC, stream key and block range. Replaces --chain-id, --rpc, --redis-stream-key, --start-block and --end-block. [optional]"
     )]
-    pub chain_id: usize,
-    #[arg(long, help = "RPC URL to use for fetching blocks.")]
-    pub rpc: String,
+    pub watch_file: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "RPC URL to use for fetching blocks. Several comma-separated URLs enable failover between providers.",
+        required_unless_present_any = ["watch_file", "rpc_replay"]
+    )]
+    pub rpc: Option<String>,
     #[arg(long, help = "Block number to start syncing from. [optional]")]
     pub start_block: Option<u64>,
     #[arg(long, help = "Block number to end syncing at. [optional]")]
     pub end_block: Option<u64>,
+    #[arg(
+        long,
+        help = "Starts syncing from the first block mined at or after this time: a Unix timestamp, a date (2024-03-03, midnight UTC) or an RFC 3339 date and time. [optional]",
+        value_parser = parse_timestamp,
+        conflicts_with = "start_block"
+    )]
+    pub start_time: Option<u64>,
+    #[arg(
+        long,
+        help = "Ends syncing at the last block mined at or before this time, in the formats of --start-time. [optional]",
+        value_parser = parse_timestamp,
+        conflicts_with = "end_block"
+    )]
+    pub end_time: Option<u64>,
     #[arg(long, help = "Redis connection URL.")]
     pub redis_url: String,
-    #[arg(long, help = "Redis stream key")]
-    pub redis_stream_key: String,
+    #[arg(long, help = "Redis stream key. Required without --watch-file.")]
+    pub redis_stream_key: Option<String>,
     #[arg(long, help = "Redis group name.")]
     pub redis_group_name: String,
     #[arg(long, help = "Database connection URL.")]
     pub db_url: String,
+    #[arg(
+        long,
+        help = "Stores every transaction, joined with its receipt, in the transaction table. [optional]",
+        default_value_t = false
+    )]
+    pub store_transactions: bool,
+    #[arg(
+        long,
+        help = "Archives every published log in the log table. [optional]",
+        default_value_t = false
+    )]
+    pub archive_logs: bool,
+    #[arg(
+        long,
+        help = "Producer id written in every stream message, to tell instances apart. [optional]",
+        default_value = "chain-watcher"
+    )]
+    pub producer_id: String,
+    #[arg(
+        long,
+        help = "Encoding of stream messages: json, msgpack, cbor or protobuf, optionally followed by +zstd. [optional]",
+        default_value = "json"
+    )]
+    pub stream_encoding: Encoding,
+    #[arg(
+        long,
+        help = "How stream entry ids are chosen: auto lets Redis assign them, block-tx derives them from the block number and transaction index, content-hash skips messages whose content was already published. [optional]",
+        value_enum,
+        default_value_t = StreamIdMode::Auto
+    )]
+    pub stream_id_mode: StreamIdMode,
+    #[arg(
+        long,
+        help = "Seconds a content-hash dedup key is kept. [optional]",
+        default_value_t = 86_400,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub dedup_ttl: u64,
+    #[arg(
+        long,
+        help = "Maximum number of stream messages sent in one MULTI/EXEC round trip. [optional]",
+        default_value_t = 500,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub publish_batch_size: u64,
+    #[arg(
+        long,
+        help = "Directory for the on-disk spool that buffers stream messages while Redis is unavailable. Each chain uses a subdirectory named after its id. [optional]"
+    )]
+    pub spool_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Maximum size of each chain's spool, in bytes. [optional]",
+        default_value_t = 1_073_741_824,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub spool_max_bytes: u64,
+    #[arg(
+        long,
+        help = "Splits the backfill between --start-block and --end-block into ranges leased from Postgres, so several instances can index it together. [optional]",
+        default_value_t = false
+    )]
+    pub shard_backfill: bool,
+    #[arg(
+        long,
+        help = "Number of blocks in each leased range. All instances sharing a backfill must use the same value. [optional]",
+        default_value_t = 10_000,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub range_size: u64,
+    #[arg(
+        long,
+        help = "Name this instance holds range leases and leadership under. Defaults to the producer id followed by the process id. [optional]"
+    )]
+    pub instance_id: Option<String>,
+    #[arg(
+        long,
+        help = "Seconds a range lease lasts without a heartbeat before another instance may reclaim it. [optional]",
+        default_value_t = 300,
+        value_parser = clap::value_parser!(u64).range(3..)
+    )]
+    pub lease_ttl: u64,
+    #[arg(
+        long,
+        help = "Runs this instance as one of several replicas of each chain, of which only the leader elected through Postgres syncs and publishes. [optional]",
+        default_value_t = false
+    )]
+    pub leader_election: bool,
+    #[arg(
+        long,
+        help = "Seconds leadership lasts without a heartbeat before a standby takes over. [optional]",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u64).range(3..)
+    )]
+    pub leader_ttl: u64,
+    #[arg(
+        long,
+        help = "Blocks fetched at once by the live lane, which follows the chain head. Defaults to the number of CPUs. [optional]",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub live_workers: Option<u64>,
+    #[arg(
+        long,
+        help = "Blocks fetched at once by the backfill lane, which indexes historical blocks and gaps. Defaults to the number of CPUs. [optional]",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub backfill_workers: Option<u64>,
+    #[arg(
+        long,
+        help = "Maximum number of blocks per second indexed by the backfill lane. Unlimited by default. [optional]",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub backfill_rate: Option<u64>,
+    #[arg(
+        long,
+        help = "Directory where every JSON-RPC response is recorded, in a subdirectory per chain id, for later replay. [optional]",
+        conflicts_with = "rpc_replay"
+    )]
+    pub rpc_record: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Directory of JSON-RPC responses recorded with --rpc-record to replay instead of calling a node. Requests that were not recorded fail. [optional]"
+    )]
+    pub rpc_replay: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "WebSocket RPC URL to watch the mempool on. Pending NFT transfers are published to the stream key followed by :pending. [optional]"
+    )]
+    pub mempool_ws: Option<String>,
+    #[arg(
+        long,
+        help = "Blocks a pending transaction may stay out of a block before the node is asked whether it was dropped. [optional]",
+        default_value_t = 25,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub pending_timeout_blocks: u64,
+    #[arg(
+        long,
+        help = "Publishes a contract_creations message for every block that deploys contracts. [optional]",
+        default_value_t = false
+    )]
+    pub detect_contracts: bool,
+    #[arg(
+        long,
+        help = "Also finds contracts deployed by factories, by tracing every block with debug_traceBlockByNumber. [optional]",
+        default_value_t = false,
+        requires = "detect_contracts"
+    )]
+    pub trace_contracts: bool,
+    #[arg(
+        long,
+        help = "Adds the keccak256 hash of each new contract's bytecode, fetched with eth_getCode. [optional]",
+        default_value_t = false,
+        requires = "detect_contracts"
+    )]
+    pub contract_code_hash: bool,
+    #[arg(
+        long,
+        help = "Checks each block's receipts against its receipts root and logs bloom, and refetches a block that fails from another provider. [optional]",
+        default_value_t = false
+    )]
+    pub verify_receipts: bool,
+    #[arg(
+        long,
+        help = "Address the admin API listens on, e.g. 127.0.0.1:9090. Disabled by default. [optional]",
+        requires = "admin_token"
+    )]
+    pub admin_listen: Option<SocketAddr>,
+    #[arg(
+        long,
+        env = "CHAIN_WATCHER_ADMIN_TOKEN",
+        hide_env_values = true,
+        help = "Bearer token required by the admin API. [optional]"
+    )]
+    pub admin_token: Option<String>,
+    #[arg(
+        long,
+        help = "Address the Prometheus metrics are served on at /metrics, e.g. 127.0.0.1:9100. Disabled by default. [optional]"
+    )]
+    pub metrics_listen: Option<SocketAddr>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// What the watcher does with each configured chain.
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Follows the chain head and backfills until stopped. The default.
+    Sync,
+    /// Indexes a fixed block range, then exits.
+    Backfill {
+        #[arg(long, help = "First block to index.")]
+        from: u64,
+        #[arg(long, help = "Last block to index.")]
+        to: u64,
+    },
+    /// Prints the indexed range, gaps, lane checkpoints and lag behind the
+    /// chain head.
+    Status,
+    /// Checks that every block in a range is indexed with its canonical hash.
+    Verify {
+        #[arg(long, help = "First block to check.")]
+        from: u64,
+        #[arg(long, help = "Last block to check.")]
+        to: u64,
+    },
+    /// Deletes the indexed blocks above a block and publishes a revert
+    /// message for each.
+    Rewind {
+        #[arg(long, help = "Last block to keep.")]
+        to: u64,
+    },
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    RegistryError(ChainRegistryError),
+    ReadError(String, String),
+    ParseError(String, String),
+    DuplicateChain(u32),
+    MissingEndBlock(u32),
+    MissingOption(String),
+    IncompatibleOptions(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::RegistryError(error) => write!(f, "{}", error),
+            ConfigError::ReadError(error, path) => {
+                write!(f, "Read Error: {}. Path: {}", error, path)
+            }
+            ConfigError::ParseError(error, path) => {
+                write!(f, "Parse Error: {}. Path: {}", error, path)
+            }
+            ConfigError::DuplicateChain(id) => {
+                write!(f, "Chain id {} is listed more than once.", id)
+            }
+            ConfigError::MissingEndBlock(id) => {
+                write!(
+                    f,
+                    "Chain id {} needs an end block to shard its backfill.",
+                    id
+                )
+            }
+            ConfigError::MissingOption(error) => write!(f, "Missing Option: {}", error),
+            ConfigError::IncompatibleOptions(error) => {
+                write!(f, "Incompatible Options: {}", error)
+            }
+        }
+    }
 }
 
-static CHAIN_CONFIGS: Lazy<HashMap<usize, ChainConfig>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    m.insert(
-        1,
-        ChainConfig {
-            id: 1,
-            name: "Ethereum".to_string(),
-        },
-    );
-    m
-});
+impl std::error::Error for ConfigError {}
+
+impl From<ChainRegistryError> for ConfigError {
+    fn from(error: ChainRegistryError) -> Self {
+        ConfigError::RegistryError(error)
+    }
+}
+
+/// One entry of the `--watch-file` list.
+#[derive(Debug, Deserialize)]
+struct WatchedChain {
+    chain_id: u32,
+    rpc: String,
+    redis_stream_key: String,
+    start_block: Option<u64>,
+    end_block: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_timestamp")]
+    start_time: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_timestamp")]
+    end_time: Option<u64>,
+    mempool_ws: Option<String>,
+}
 
+/// Settings for a single chain's synchronizer.
 #[derive(Debug, Clone)]
-pub struct Config {
+pub struct ChainSyncConfig {
     pub chain: ChainConfig,
-    pub db_url: String,
     pub redis_config: RedisConfig,
     pub start_block: Option<u64>,
     pub end_block: Option<u64>,
-    pub rpc: String,
+    /// Resolved to `start_block` when the chain starts.
+    pub start_time: Option<u64>,
+    /// Resolved to `end_block` when the chain starts.
+    pub end_time: Option<u64>,
+    pub rpc: Vec<String>,
+    pub rpc_record: Option<PathBuf>,
+    pub rpc_replay: Option<PathBuf>,
+    /// Concurrency of the live lane.
     pub num_workers: usize,
-    pub reset: bool,
-    pub debug: bool,
+    pub backfill_workers: usize,
+    pub backfill_rate: Option<u64>,
+    pub store_transactions: bool,
+    pub archive_logs: bool,
+    pub producer_id: String,
+    pub stream_encoding: Encoding,
+    pub stream_id_mode: StreamIdMode,
+    pub dedup_ttl: u64,
+    pub publish_batch_size: usize,
+    pub spool_dir: Option<PathBuf>,
+    pub spool_max_bytes: u64,
+    pub shard_backfill: bool,
+    pub range_size: u64,
+    pub instance_id: String,
+    pub lease_ttl: u64,
+    pub leader_election: bool,
+    pub leader_ttl: u64,
+    pub mempool_ws: Option<String>,
+    pub pending_timeout_blocks: u64,
+    pub detect_contracts: bool,
+    pub trace_contracts: bool,
+    pub contract_code_hash: bool,
+    pub verify_receipts: bool,
+    /// What to run against the chain.
+    pub command: Command,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self::new()
+impl ChainSyncConfig {
+    /// The stream pending transfers and their resolutions are published to.
+    pub fn pending_stream_key(&self) -> String {
+        format!("{}:pending", self.redis_config.stream_key)
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub command: Command,
+    pub chains: Vec<ChainSyncConfig>,
+    pub db_url: String,
+    pub redis_url: String,
+    pub reset: bool,
+    pub logging: LoggingArgs,
+    pub admin_listen: Option<SocketAddr>,
+    pub admin_token: Option<String>,
+    pub metrics_listen: Option<SocketAddr>,
+}
+
 impl Config {
-    pub fn new() -> Self {
-        let args = ChainWatcherArgs::parse();
+    pub fn new() -> Result<Self, ConfigError> {
+        Self::from_args(ChainWatcherArgs::parse())
+    }
+
+    /// Builds the configuration from parsed command line arguments, checking
+    /// what clap cannot.
+    pub fn from_args(args: ChainWatcherArgs) -> Result<Self, ConfigError> {
+        let registry = ChainRegistry::load(args.chains_file.as_deref())?;
+        let watched_chains = match &args.watch_file {
+            Some(path) => Self::read_watch_file(path)?,
+            None => vec![WatchedChain {
+                chain_id: args.chain_id,
+                // Not required when replaying recorded responses.
+                rpc: args.rpc.clone().unwrap_or_default(),
+                redis_stream_key: args.redis_stream_key.ok_or_else(|| {
+                    ConfigError::MissingOption(
+                        "--redis-stream-key is required without --watch-file."
+                            .to_string(),
+                    )
+                })?,
+                start_block: args.start_block,
+                end_block: args.end_block,
+                start_time: args.start_time,
+                end_time: args.end_time,
+                mempool_ws: args.mempool_ws.clone(),
+            }],
+        };
+
+        let mut seen = HashSet::new();
+        if let Some(watched) = watched_chains.iter().find(|w| !seen.insert(w.chain_id)) {
+            return Err(ConfigError::DuplicateChain(watched.chain_id));
+        }
 
-        let chain = CHAIN_CONFIGS
-            .get(&args.chain_id)
-            .expect("Default chain error.")
-            .clone();
-        let rpc: String = args.rpc;
+        if args.shard_backfill {
+            if let Some(watched) = watched_chains
+                .iter()
+                .find(|w| w.end_block.is_none() && w.end_time.is_none())
+            {
+                return Err(ConfigError::MissingEndBlock(watched.chain_id));
+            }
+            // Ranges finish out of order, and block-tx ids must increase.
+            if args.stream_id_mode == StreamIdMode::BlockTx {
+                return Err(ConfigError::IncompatibleOptions(
+                    "--shard-backfill cannot be used with --stream-id-mode block-tx."
+                        .to_string(),
+                ));
+            }
+            if args.leader_election {
+                return Err(ConfigError::IncompatibleOptions(
+                    "--shard-backfill cannot be used with --leader-election.".to_string(),
+                ));
+            }
+        }
+        if watched_chains.iter().any(|w| w.mempool_ws.is_some()) {
+            if args.shard_backfill {
+                return Err(ConfigError::IncompatibleOptions(
+                    "--mempool-ws cannot be used with --shard-backfill.".to_string(),
+                ));
+            }
+            if args.rpc_replay.is_some() {
+                return Err(ConfigError::IncompatibleOptions(
+                    "--mempool-ws cannot be used with --rpc-replay.".to_string(),
+                ));
+            }
+        }
+        let instance_id = args
+            .instance_id
+            .clone()
+            .unwrap_or_else(|| format!("{}-{}", args.producer_id, std::process::id()));
 
-        Self {
-            chain,
+        let command = args.command.clone().unwrap_or(Command::Sync);
+        // Blocks published again would land below the stream's last entry.
+        if args.stream_id_mode == StreamIdMode::BlockTx {
+            match command {
+                Command::Backfill { .. } | Command::Rewind { .. } => {
+                    return Err(ConfigError::IncompatibleOptions(
+                        "backfill and rewind cannot be used with --stream-id-mode block-tx."
+                            .to_string(),
+                    ));
+                }
+                Command::Sync | Command::Status | Command::Verify { .. } => {}
+            }
+        }
+        let num_workers = num_cpus::get();
+        let chains = watched_chains
+            .into_iter()
+            .map(|watched| {
+                Ok(ChainSyncConfig {
+                    chain: registry.get(watched.chain_id)?,
+                    redis_config: RedisConfig {
+                        url: args.redis_url.clone(),
+                        stream_key: watched.redis_stream_key,
+                        group_name: args.redis_group_name.clone(),
+                    },
+                    start_block: watched.start_block,
+                    end_block: watched.end_block,
+                    start_time: watched.start_time,
+                    end_time: watched.end_time,
+                    rpc: watched
+                        .rpc
+                        .split(',')
+                        .map(|url| url.trim().to_string())
+                        .filter(|url| !url.is_empty())
+                        .collect(),
+                    num_workers: args.live_workers.map_or(num_workers, |n| n as usize),
+                    backfill_workers: args
+                        .backfill_workers
+                        .map_or(num_workers, |n| n as usize),
+                    backfill_rate: args.backfill_rate,
+                    rpc_record: args.rpc_record.clone(),
+                    rpc_replay: args.rpc_replay.clone(),
+                    store_transactions: args.store_transactions,
+                    archive_logs: args.archive_logs,
+                    producer_id: args.producer_id.clone(),
+                    stream_encoding: args.stream_encoding,
+                    stream_id_mode: args.stream_id_mode,
+                    dedup_ttl: args.dedup_ttl,
+                    publish_batch_size: args.publish_batch_size as usize,
+                    spool_dir: args.spool_dir.clone(),
+                    spool_max_bytes: args.spool_max_bytes,
+                    shard_backfill: args.shard_backfill,
+                    range_size: args.range_size,
+                    instance_id: instance_id.clone(),
+                    lease_ttl: args.lease_ttl,
+                    leader_election: args.leader_election,
+                    leader_ttl: args.leader_ttl,
+                    mempool_ws: watched.mempool_ws,
+                    pending_timeout_blocks: args.pending_timeout_blocks,
+                    detect_contracts: args.detect_contracts,
+                    trace_contracts: args.trace_contracts,
+                    contract_code_hash: args.contract_code_hash,
+                    verify_receipts: args.verify_receipts,
+                    command: command.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, ConfigError>>()?;
+
+        Ok(Self {
+            command,
+            chains,
             db_url: args.db_url,
-            redis_config: RedisConfig {
-                url: args.redis_url,
-                stream_key: args.redis_stream_key,
-                group_name: args.redis_group_name,
-            },
-            start_block: args.start_block,
-            end_block: args.end_block,
-            rpc,
-            num_workers: num_cpus::get(),
+            redis_url: args.redis_url,
             reset: args.reset,
-            debug: args.debug,
+            logging: args.logging,
+            admin_listen: args.admin_listen,
+            admin_token: args.admin_token,
+            metrics_listen: args.metrics_listen,
+        })
+    }
+
+    fn read_watch_file(path: &Path) -> Result<Vec<WatchedChain>, ConfigError> {
+        let source = path.display().to_string();
+        let content = fs::read_to_string(path)
+            .map_err(|e| ConfigError::ReadError(e.to_string(), source.clone()))?;
+        let watched_chains: Vec<WatchedChain> = serde_json::from_str(&content)
+            .map_err(|e| ConfigError::ParseError(e.to_string(), source.clone()))?;
+        if let Some(watched) = watched_chains.iter().find(|w| {
+            (w.start_block.is_some() && w.start_time.is_some())
+                || (w.end_block.is_some() && w.end_time.is_some())
+        }) {
+            return Err(ConfigError::ParseError(
+                format!(
+                    "chain id {} sets both a block and a time to start or end at",
+                    watched.chain_id
+                ),
+                source,
+            ));
         }
+        Ok(watched_chains)
+    }
+}
+
+/// Parses a Unix timestamp in seconds, a date, taken as midnight UTC, or an
+/// RFC 3339 date and time.
+pub fn parse_timestamp(value: &str) -> Result<u64, String> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(seconds);
+    }
+    let seconds = match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => date.and_time(NaiveTime::MIN).and_utc().timestamp(),
+        Err(_) => DateTime::parse_from_rfc3339(value)
+            .map_err(|_| {
+                format!(
+                    "{} is not a Unix timestamp, a date or an RFC 3339 date and time",
+                    value
+                )
+            })?
+            .timestamp(),
+    };
+    u64::try_from(seconds).map_err(|_| format!("{} is before the Unix epoch", value))
+}
+
+/// Reads a watch-file time, given as a number or in any format of
+/// `parse_timestamp`.
+fn deserialize_timestamp<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Timestamp {
+        Seconds(u64),
+        Text(String),
+    }
+
+    match Option::<Timestamp>::deserialize(deserializer)? {
+        Some(Timestamp::Seconds(seconds)) => Ok(Some(seconds)),
+        Some(Timestamp::Text(text)) => parse_timestamp(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        None => Ok(None),
     }
 }
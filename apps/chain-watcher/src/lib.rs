@@ -0,0 +1,3 @@
+pub mod clients;
+pub mod config;
+pub mod services;
@@ -1,76 +1,77 @@
-pub mod clients;
-pub mod config;
-pub mod services;
-
-use crate::services::sync::ChainSynchronizer;
-use clients::{blockchain_client::BlockchainClient, redis_client::RedisClient};
-use common::redis::redis_pool_factory;
-use config::Config;
-use ethers::providers::{Http, Provider};
-use services::repositories::block::{BlockRepository, BlockRepositoryTrait};
+use chain_watcher::{
+    config::{Command, Config},
+    services::{
+        admin_api::AdminApi,
+        chain_runner::{run_chain_command, supervise_chain},
+        control::ChainControl,
+        metrics::MetricsExporter,
+    },
+};
+use common::{logging, redis::redis_pool_factory};
 use sqlx::postgres::PgPoolOptions;
-use std::sync::Arc;
-use tracing_appender::rolling;
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use std::{collections::HashMap, sync::Arc};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config: Config = Config::new();
-
-    let file_appender = rolling::daily("./logs", "chain-watcher.log");
-    let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-
-    let debug_level = if config.debug { "debug" } else { "info" };
-    let console_layer = fmt::layer().with_writer(std::io::stdout);
-    let file_layer = fmt::layer().with_writer(non_blocking);
-    let filter_layer = EnvFilter::new(debug_level);
+    let config: Config = Config::new()?;
 
-    tracing_subscriber::registry()
-        .with(console_layer)
-        .with(file_layer)
-        .with(filter_layer)
-        .init();
+    let _logging = logging::init(&config.logging, "chain-watcher")?;
 
-    let redis_config = config.clone().redis_config;
-    let redis_pool = redis_pool_factory(redis_config.url)
+    let redis_pool = redis_pool_factory(config.redis_url.clone())
         .await
         .expect("Error on acquiring redis connection.");
+    let redis_pool = Arc::new(redis_pool);
 
-    let http_provider = Provider::<Http>::try_from(&config.rpc)
-        .expect("Error on provider http creation.");
-
-    let database_pool = PgPoolOptions::new().connect(&config.db_url).await?;
-    let block_repository =
-        BlockRepository::new(Arc::new(database_pool.clone()), config.chain.clone());
-
-    let synchronizer = ChainSynchronizer::new(
-        BlockchainClient {
-            provider: Arc::new(http_provider),
-        },
-        RedisClient {
-            pool: Arc::new(redis_pool),
-        },
-        block_repository,
-        config.clone(),
-    );
+    let database_pool = Arc::new(PgPoolOptions::new().connect(&config.db_url).await?);
 
     if config.reset {
         // TODO: implement
     }
 
-    let mut start_block = synchronizer.start_block();
-
-    let missing_blocks = synchronizer.missing_blocks().await?;
-    synchronizer.sync_missing_blocks(missing_blocks).await;
-
-    loop {
-        let end_block = synchronizer.end_block().await?;
-
-        tracing::info!("Indexing from block {} to block {}", start_block, end_block);
+    if config.command != Command::Sync {
+        for chain_config in config.chains {
+            run_chain_command(chain_config, redis_pool.clone(), database_pool.clone())
+                .await
+                .map_err(|error| error as Box<dyn std::error::Error>)?;
+        }
+        return Ok(());
+    }
 
-        synchronizer.sync(start_block, end_block).await;
+    let controls: HashMap<u32, Arc<ChainControl>> = config
+        .chains
+        .iter()
+        .map(|chain_config| {
+            let control =
+                ChainControl::default().with_stream_id_mode(chain_config.stream_id_mode);
+            (chain_config.chain.id, Arc::new(control))
+        })
+        .collect();
+    if let (Some(addr), Some(token)) = (config.admin_listen, &config.admin_token) {
+        AdminApi::new(controls.clone(), token).serve(addr).await?;
+    }
+    if let Some(addr) = config.metrics_listen {
+        let metrics = controls
+            .iter()
+            .map(|(chain_id, control)| (*chain_id, control.metrics.clone()))
+            .collect();
+        MetricsExporter::new(metrics).serve(addr).await?;
+    }
 
-        start_block = end_block + 1;
-        tracing::info!("Updating start block {}", start_block);
+    let watchers = config.chains.into_iter().map(|chain_config| {
+        let control = controls[&chain_config.chain.id].clone();
+        tokio::spawn(supervise_chain(
+            chain_config,
+            redis_pool.clone(),
+            database_pool.clone(),
+            control,
+        ))
+    });
+
+    for watcher in futures::future::join_all(watchers).await {
+        if let Err(error) = watcher {
+            tracing::error!("Chain watcher task failed: {}", error);
+        }
     }
+
+    Ok(())
 }
@@ -0,0 +1,217 @@
+use std::fmt;
+
+use common::types::SyncLane;
+
+use crate::{
+    clients::{blockchain_client::BlockchainClientTrait, redis_client::RedisClientTrait},
+    config::{ChainSyncConfig, Command},
+};
+
+use super::{
+    repositories::{
+        block::{BlockRepositoryTrait, IndexedRange},
+        checkpoint::{CheckpointRepositoryTrait, SyncCheckpoint},
+        log::LogRepositoryTrait,
+        transaction::TransactionRepositoryTrait,
+    },
+    sync::{ChainSynchronizer, SyncError},
+};
+
+/// Blocks indexed between two progress reports of `backfill`.
+const BACKFILL_CHUNK_SIZE: u64 = 1000;
+
+/// Gaps listed by `status`.
+const STATUS_GAP_LIMIT: u64 = 10;
+
+/// Where a chain's index stands, as printed by `status`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainStatus {
+    pub chain_id: u32,
+    pub chain_name: String,
+    pub chain_head: u64,
+    pub indexed: Option<IndexedRange>,
+    /// The first gaps in the indexed range.
+    pub gaps: Vec<(u64, u64)>,
+    pub live: Option<SyncCheckpoint>,
+    pub backfill: Option<SyncCheckpoint>,
+}
+
+impl ChainStatus {
+    /// Blocks between the last indexed block and the chain head.
+    pub fn lag(&self) -> Option<u64> {
+        self.indexed
+            .map(|indexed| self.chain_head.saturating_sub(indexed.last_block))
+    }
+
+    /// Blocks missing between the first and last indexed blocks.
+    pub fn missing_blocks(&self) -> u64 {
+        self.indexed.map_or(0, |indexed| {
+            indexed.last_block - indexed.first_block + 1 - indexed.block_count
+        })
+    }
+}
+
+impl fmt::Display for ChainStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Chain {} ({})", self.chain_id, self.chain_name)?;
+        writeln!(f, "  Chain head: {}", self.chain_head)?;
+        match (self.indexed, self.lag()) {
+            (Some(indexed), Some(lag)) => {
+                writeln!(
+                    f,
+                    "  Indexed: {} blocks from {} to {}",
+                    indexed.block_count, indexed.first_block, indexed.last_block
+                )?;
+                writeln!(f, "  Lag: {} blocks", lag)?;
+            }
+            _ => writeln!(f, "  Indexed: none")?,
+        }
+        writeln!(f, "  Missing: {} blocks", self.missing_blocks())?;
+        for (first, last) in &self.gaps {
+            writeln!(f, "    {} to {}", first, last)?;
+        }
+        for (lane, checkpoint) in [
+            (SyncLane::Live, self.live),
+            (SyncLane::Backfill, self.backfill),
+        ] {
+            match checkpoint {
+                Some(SyncCheckpoint {
+                    start_block,
+                    last_block: Some(last_block),
+                }) => writeln!(
+                    f,
+                    "  Lane {}: from {}, indexed up to {}",
+                    lane, start_block, last_block
+                )?,
+                Some(SyncCheckpoint { start_block, .. }) => {
+                    writeln!(f, "  Lane {}: from {}, not started", lane, start_block)?
+                }
+                None => writeln!(f, "  Lane {}: no checkpoint", lane)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs a one-off command against a chain. `Command::Sync` is not one of
+/// them and does nothing here.
+pub async fn run_command<B, R, E, T, L, C>(
+    command: &Command,
+    synchronizer: &ChainSynchronizer<B, R, E, T, L>,
+    backfill_synchronizer: &ChainSynchronizer<B, R, E, T, L>,
+    block_repository: &E,
+    checkpoint_repository: &C,
+    config: &ChainSyncConfig,
+) -> Result<(), SyncError>
+where
+    B: BlockchainClientTrait,
+    R: RedisClientTrait,
+    E: BlockRepositoryTrait,
+    T: TransactionRepositoryTrait,
+    L: LogRepositoryTrait,
+    C: CheckpointRepositoryTrait,
+{
+    match *command {
+        Command::Sync => Ok(()),
+        Command::Backfill { from, to } => backfill(backfill_synchronizer, from, to).await,
+        Command::Status => {
+            let status = status(
+                synchronizer,
+                block_repository,
+                checkpoint_repository,
+                config,
+            )
+            .await?;
+            print!("{}", status);
+            Ok(())
+        }
+        Command::Verify { from, to } => {
+            let discrepancies = synchronizer.verify(from, to).await?;
+            for discrepancy in &discrepancies {
+                println!("{}", discrepancy);
+            }
+            if !discrepancies.is_empty() {
+                return Err(format!(
+                    "{} of the blocks from {} to {} failed verification",
+                    discrepancies.len(),
+                    from,
+                    to
+                )
+                .into());
+            }
+            println!(
+                "Blocks {} to {} are indexed with their canonical hashes.",
+                from, to
+            );
+            Ok(())
+        }
+        Command::Rewind { to } => {
+            let reverted = synchronizer.rewind(to).await?;
+            checkpoint_repository.rewind_checkpoints(to).await?;
+            println!("Reverted {} blocks above block {}.", reverted, to);
+            Ok(())
+        }
+    }
+}
+
+/// Indexes the blocks from `from` to `to`, whether or not they already are.
+pub async fn backfill<B, R, E, T, L>(
+    synchronizer: &ChainSynchronizer<B, R, E, T, L>,
+    from: u64,
+    to: u64,
+) -> Result<(), SyncError>
+where
+    B: BlockchainClientTrait,
+    R: RedisClientTrait,
+    E: BlockRepositoryTrait,
+    T: TransactionRepositoryTrait,
+    L: LogRepositoryTrait,
+{
+    let mut start_block = from;
+    while start_block <= to {
+        let end_block = to.min(start_block.saturating_add(BACKFILL_CHUNK_SIZE - 1));
+        tracing::info!(
+            "Backfilling from block {} to block {}",
+            start_block,
+            end_block
+        );
+        // Refreshes the chain head, so finality is judged against it.
+        synchronizer.chain_head().await?;
+        synchronizer.sync(start_block, end_block).await?;
+
+        match end_block.checked_add(1) {
+            Some(next) => start_block = next,
+            None => break,
+        }
+    }
+
+    tracing::info!("Backfill from block {} to block {} finished.", from, to);
+    Ok(())
+}
+
+pub async fn status<B, R, E, T, L, C>(
+    synchronizer: &ChainSynchronizer<B, R, E, T, L>,
+    block_repository: &E,
+    checkpoint_repository: &C,
+    config: &ChainSyncConfig,
+) -> Result<ChainStatus, SyncError>
+where
+    B: BlockchainClientTrait,
+    R: RedisClientTrait,
+    E: BlockRepositoryTrait,
+    T: TransactionRepositoryTrait,
+    L: LogRepositoryTrait,
+    C: CheckpointRepositoryTrait,
+{
+    Ok(ChainStatus {
+        chain_id: config.chain.id,
+        chain_name: config.chain.name.clone(),
+        chain_head: synchronizer.chain_head().await?,
+        indexed: block_repository.get_indexed_range().await?,
+        gaps: block_repository.get_gaps(STATUS_GAP_LIMIT).await?,
+        live: checkpoint_repository.get_checkpoint(SyncLane::Live).await?,
+        backfill: checkpoint_repository
+            .get_checkpoint(SyncLane::Backfill)
+            .await?,
+    })
+}
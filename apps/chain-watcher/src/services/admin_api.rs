@@ -0,0 +1,311 @@
+use std::{collections::HashMap, fmt, net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{Path, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::clients::redis_client::StreamIdMode;
+
+use super::{
+    control::{ChainControl, ChainRequest, InFlightBlock},
+    metrics::{ChainMetrics, MetricsSnapshot},
+};
+
+#[derive(Debug)]
+pub enum AdminApiError {
+    Unauthorized,
+    UnknownChain(u32),
+    InvalidRequest(String),
+    /// The request cannot be carried out with the chain's configuration.
+    Conflict(String),
+}
+
+impl fmt::Display for AdminApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminApiError::Unauthorized => {
+                write!(f, "Admin API Error: missing or invalid bearer token")
+            }
+            AdminApiError::UnknownChain(chain_id) => {
+                write!(f, "Admin API Error: chain {} is not watched", chain_id)
+            }
+            AdminApiError::InvalidRequest(message) | AdminApiError::Conflict(message) => {
+                write!(f, "Admin API Error: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AdminApiError {}
+
+impl IntoResponse for AdminApiError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            AdminApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AdminApiError::UnknownChain(_) => StatusCode::NOT_FOUND,
+            AdminApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            AdminApiError::Conflict(_) => StatusCode::CONFLICT,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+/// What `GET /chains/{chain_id}` returns.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainState {
+    pub chain_id: u32,
+    pub paused: bool,
+    /// Workers set through the API in place of the configured ones.
+    pub worker_limit: Option<usize>,
+    pub in_flight: Vec<InFlightBlock>,
+    pub metrics: MetricsSnapshot,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkersRequest {
+    workers: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BackfillRequest {
+    from: u64,
+    to: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct RewindRequest {
+    to: u64,
+}
+
+/// An HTTP API to pause, resume, backfill and rewind the watched chains,
+/// authenticated by a bearer token.
+#[derive(Clone)]
+pub struct AdminApi {
+    controls: Arc<HashMap<u32, Arc<ChainControl>>>,
+    token: Arc<str>,
+}
+
+impl AdminApi {
+    pub fn new(controls: HashMap<u32, Arc<ChainControl>>, token: &str) -> Self {
+        Self {
+            controls: Arc::new(controls),
+            token: token.into(),
+        }
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/chains", get(list_chains))
+            .route("/chains/:chain_id", get(get_chain))
+            .route("/chains/:chain_id/in-flight", get(get_in_flight))
+            .route("/chains/:chain_id/pause", post(pause))
+            .route("/chains/:chain_id/resume", post(resume))
+            .route("/chains/:chain_id/workers", put(set_workers))
+            .route("/chains/:chain_id/backfill", post(backfill))
+            .route("/chains/:chain_id/rewind", post(rewind))
+            .layer(middleware::from_fn_with_state(self.clone(), authorize))
+            .with_state(self.clone())
+    }
+
+    /// Binds `addr` and serves in the background.
+    pub async fn serve(&self, addr: SocketAddr) -> std::io::Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        tracing::info!("Admin API listening on {}", listener.local_addr()?);
+        let router = self.router();
+        tokio::spawn(async move {
+            if let Err(error) = axum::serve(listener, router).await {
+                tracing::error!("Admin API stopped: {}", error);
+            }
+        });
+        Ok(())
+    }
+
+    fn control(&self, chain_id: u32) -> Result<&Arc<ChainControl>, AdminApiError> {
+        self.controls
+            .get(&chain_id)
+            .ok_or(AdminApiError::UnknownChain(chain_id))
+    }
+
+    /// Refuses requests that publish below the stream's last entry.
+    fn republishing_control(
+        &self,
+        chain_id: u32,
+        operation: &str,
+    ) -> Result<&Arc<ChainControl>, AdminApiError> {
+        let control = self.control(chain_id)?;
+        if control.stream_id_mode() == StreamIdMode::BlockTx {
+            return Err(AdminApiError::Conflict(format!(
+                "{} cannot be used with --stream-id-mode block-tx",
+                operation
+            )));
+        }
+        Ok(control)
+    }
+
+    fn state(&self, chain_id: u32) -> Result<ChainState, AdminApiError> {
+        let control = self.control(chain_id)?;
+        Ok(ChainState {
+            chain_id,
+            paused: control.is_paused(),
+            worker_limit: control.worker_limit(),
+            in_flight: control.in_flight(),
+            metrics: control.metrics.snapshot(),
+        })
+    }
+}
+
+async fn authorize(
+    State(api): State<AdminApi>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AdminApiError> {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if constant_time_eq(token.as_bytes(), api.token.as_bytes()) => {
+            Ok(next.run(request).await)
+        }
+        _ => {
+            tracing::warn!(
+                "Rejected unauthorized admin request {} {}",
+                request.method(),
+                request.uri().path()
+            );
+            Err(AdminApiError::Unauthorized)
+        }
+    }
+}
+
+/// Compares without returning early, so the time taken does not tell how
+/// much of the token was guessed.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+async fn list_chains(State(api): State<AdminApi>) -> Json<Vec<ChainState>> {
+    let mut chain_ids: Vec<u32> = api.controls.keys().copied().collect();
+    chain_ids.sort_unstable();
+    Json(
+        chain_ids
+            .into_iter()
+            .filter_map(|chain_id| api.state(chain_id).ok())
+            .collect(),
+    )
+}
+
+async fn get_chain(
+    State(api): State<AdminApi>,
+    Path(chain_id): Path<u32>,
+) -> Result<Json<ChainState>, AdminApiError> {
+    api.state(chain_id).map(Json)
+}
+
+async fn get_in_flight(
+    State(api): State<AdminApi>,
+    Path(chain_id): Path<u32>,
+) -> Result<Json<Vec<InFlightBlock>>, AdminApiError> {
+    Ok(Json(api.control(chain_id)?.in_flight()))
+}
+
+async fn pause(
+    State(api): State<AdminApi>,
+    Path(chain_id): Path<u32>,
+) -> Result<Json<ChainState>, AdminApiError> {
+    let control = api.control(chain_id)?;
+    ChainMetrics::add(&control.metrics.admin_operations, 1);
+    if control.pause() {
+        tracing::warn!(chain_id, "Syncing paused through the admin API");
+    }
+    api.state(chain_id).map(Json)
+}
+
+async fn resume(
+    State(api): State<AdminApi>,
+    Path(chain_id): Path<u32>,
+) -> Result<Json<ChainState>, AdminApiError> {
+    let control = api.control(chain_id)?;
+    ChainMetrics::add(&control.metrics.admin_operations, 1);
+    if control.resume() {
+        tracing::warn!(chain_id, "Syncing resumed through the admin API");
+    }
+    api.state(chain_id).map(Json)
+}
+
+async fn set_workers(
+    State(api): State<AdminApi>,
+    Path(chain_id): Path<u32>,
+    Json(request): Json<WorkersRequest>,
+) -> Result<Json<ChainState>, AdminApiError> {
+    let control = api.control(chain_id)?;
+    if request.workers == Some(0) {
+        return Err(AdminApiError::InvalidRequest(
+            "workers must be at least 1".to_string(),
+        ));
+    }
+    ChainMetrics::add(&control.metrics.admin_operations, 1);
+    control.set_worker_limit(request.workers);
+    match request.workers {
+        Some(workers) => {
+            tracing::warn!(
+                chain_id,
+                "Worker limit set to {} through the admin API",
+                workers
+            )
+        }
+        None => tracing::warn!(chain_id, "Worker limit reset through the admin API"),
+    }
+    api.state(chain_id).map(Json)
+}
+
+async fn backfill(
+    State(api): State<AdminApi>,
+    Path(chain_id): Path<u32>,
+    Json(request): Json<BackfillRequest>,
+) -> Result<StatusCode, AdminApiError> {
+    let control = api.republishing_control(chain_id, "backfill")?;
+    if request.from > request.to {
+        return Err(AdminApiError::InvalidRequest(format!(
+            "from {} is after to {}",
+            request.from, request.to
+        )));
+    }
+    ChainMetrics::add(&control.metrics.admin_operations, 1);
+    control.request(ChainRequest::Backfill {
+        from: request.from,
+        to: request.to,
+    });
+    tracing::warn!(
+        chain_id,
+        "Backfill of blocks {} to {} requested through the admin API",
+        request.from,
+        request.to
+    );
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn rewind(
+    State(api): State<AdminApi>,
+    Path(chain_id): Path<u32>,
+    Json(request): Json<RewindRequest>,
+) -> Result<StatusCode, AdminApiError> {
+    let control = api.republishing_control(chain_id, "rewind")?;
+    ChainMetrics::add(&control.metrics.admin_operations, 1);
+    control.request(ChainRequest::Rewind { to: request.to });
+    tracing::warn!(
+        chain_id,
+        "Rewind to block {} requested through the admin API",
+        request.to
+    );
+    Ok(StatusCode::ACCEPTED)
+}
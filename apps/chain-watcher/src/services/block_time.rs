@@ -0,0 +1,142 @@
+use std::fmt;
+
+use crate::{clients::blockchain_client::BlockchainClientTrait, config::ChainSyncConfig};
+
+use super::{repositories::block_time::BlockTimeRepositoryTrait, sync::SyncError};
+
+#[derive(Debug)]
+pub enum BlockTimeError {
+    /// The timestamp, and that of the chain head.
+    AfterChainHead(u64, u64),
+    BeforeGenesis(u64),
+    MissingBlock(u64),
+}
+
+impl fmt::Display for BlockTimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockTimeError::AfterChainHead(timestamp, head_timestamp) => write!(
+                f,
+                "Block Time Error: no block is mined at or after {} yet, the chain head was mined at {}",
+                timestamp, head_timestamp
+            ),
+            BlockTimeError::BeforeGenesis(timestamp) => {
+                write!(f, "Block Time Error: {} is before the genesis block", timestamp)
+            }
+            BlockTimeError::MissingBlock(block_number) => {
+                write!(f, "Block Time Error: the RPC has no block {}", block_number)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BlockTimeError {}
+
+/// Sets the start and end blocks of a chain configured with `--start-time`
+/// or `--end-time`: the first block mined at or after the start time, and the
+/// last one mined at or before the end time, or the chain head if none is
+/// mined after it yet.
+pub async fn resolve_block_range<B, T>(
+    blockchain_client: &B,
+    block_time_repository: &T,
+    config: &mut ChainSyncConfig,
+) -> Result<(), SyncError>
+where
+    B: BlockchainClientTrait,
+    T: BlockTimeRepositoryTrait,
+{
+    let finality_depth = config.chain.finality_depth;
+    if let Some(start_time) = config.start_time {
+        let start_block = first_block_at(
+            blockchain_client,
+            block_time_repository,
+            start_time,
+            finality_depth,
+        )
+        .await?;
+        tracing::info!(
+            "Start time {} resolved to block {}",
+            start_time,
+            start_block
+        );
+        config.start_block = Some(start_block);
+    }
+    if let Some(end_time) = config.end_time {
+        let chain_head = blockchain_client.get_block_number().await?;
+        let head_timestamp = block_timestamp(blockchain_client, chain_head).await?;
+        // No block mined after the end time exists yet to search for.
+        let end_block = if end_time >= head_timestamp {
+            chain_head
+        } else {
+            let next_block = first_block_at(
+                blockchain_client,
+                block_time_repository,
+                end_time.saturating_add(1),
+                finality_depth,
+            )
+            .await?;
+            next_block
+                .checked_sub(1)
+                .ok_or(BlockTimeError::BeforeGenesis(end_time))?
+        };
+        tracing::info!("End time {} resolved to block {}", end_time, end_block);
+        config.end_block = Some(end_block);
+    }
+    Ok(())
+}
+
+/// Binary searches the chain for the first block mined at or after
+/// `timestamp`. Answers at least `finality_depth` blocks below the head are
+/// cached, since a reorg cannot move them.
+pub async fn first_block_at<B, T>(
+    blockchain_client: &B,
+    block_time_repository: &T,
+    timestamp: u64,
+    finality_depth: u64,
+) -> Result<u64, SyncError>
+where
+    B: BlockchainClientTrait,
+    T: BlockTimeRepositoryTrait,
+{
+    if let Some(block_number) =
+        block_time_repository.get_first_block_at(timestamp).await?
+    {
+        return Ok(block_number);
+    }
+
+    let chain_head = blockchain_client.get_block_number().await?;
+    let head_timestamp = block_timestamp(blockchain_client, chain_head).await?;
+    if timestamp > head_timestamp {
+        return Err(Box::new(BlockTimeError::AfterChainHead(
+            timestamp,
+            head_timestamp,
+        )));
+    }
+
+    let (mut low, mut high) = (0, chain_head);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if block_timestamp(blockchain_client, middle).await? >= timestamp {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+
+    if chain_head - low >= finality_depth {
+        block_time_repository
+            .save_first_block_at(timestamp, low)
+            .await?;
+    }
+    Ok(low)
+}
+
+async fn block_timestamp<B: BlockchainClientTrait>(
+    blockchain_client: &B,
+    block_number: u64,
+) -> Result<u64, SyncError> {
+    blockchain_client
+        .get_block_timestamp(block_number)
+        .await?
+        .ok_or_else(|| BlockTimeError::MissingBlock(block_number).into())
+}
@@ -0,0 +1,497 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bb8::Pool;
+use bb8_redis::RedisConnectionManager;
+use common::types::SyncLane;
+use ethers::{types::H256, utils::hex};
+use sqlx::PgPool;
+use tokio::task::JoinSet;
+use tracing::Instrument;
+
+use crate::{
+    clients::{
+        blockchain_client::{
+            BlockchainClient, BlockchainClientTrait, ChainVerificationError,
+        },
+        recording_client::RecordingClient,
+        redis_client::{RedisClient, RedisClientTrait},
+    },
+    config::{ChainSyncConfig, Command},
+};
+
+use super::{
+    admin,
+    block_time::resolve_block_range,
+    control::{ChainControl, ChainRequest},
+    lanes::run_lanes,
+    leadership::{acquire_leadership, hold_leadership},
+    mempool::watch_mempool,
+    repositories::{
+        block::{BlockRepository, BlockRepositoryTrait},
+        block_time::{BlockTimeRepository, BlockTimeRepositoryTrait},
+        chain::{ChainRepository, ChainRepositoryTrait},
+        checkpoint::{CheckpointRepository, CheckpointRepositoryTrait},
+        leader::{LeaderRepository, LeaderRepositoryTrait},
+        lease::{LeaseRepository, LeaseRepositoryTrait},
+        log::{LogRepository, LogRepositoryTrait},
+        transaction::{TransactionRepository, TransactionRepositoryTrait},
+    },
+    sharding::run_sharded_backfill,
+    spool::{replay_spool, Spool},
+    sync::ChainSynchronizer,
+};
+
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+const SPOOL_DRAIN_DELAY: Duration = Duration::from_secs(5);
+
+pub type ChainRunnerError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Keeps one chain's synchronizer running until the process exits.
+///
+/// The synchronizer runs in its own task, so an error or a panic while
+/// syncing one chain only restarts that chain and never reaches the others.
+/// A chain whose RPC serves a different network is not restarted.
+pub async fn supervise_chain(
+    config: ChainSyncConfig,
+    redis_pool: Arc<Pool<RedisConnectionManager>>,
+    database_pool: Arc<PgPool>,
+    control: Arc<ChainControl>,
+) {
+    let span =
+        tracing::info_span!("chain", id = config.chain.id, name = %config.chain.name);
+
+    async move {
+        let fencing_token = Arc::new(AtomicU64::new(0));
+        let spool = match &config.spool_dir {
+            Some(dir) => {
+                let dir = dir.join(config.chain.id.to_string());
+                match Spool::open(dir, config.spool_max_bytes, control.metrics.clone())
+                    .await
+                {
+                    Ok(spool) => Some(Arc::new(spool)),
+                    Err(error) => {
+                        tracing::error!("Refusing to sync chain: {}", error);
+                        return;
+                    }
+                }
+            }
+            None => None,
+        };
+        let replay = spool.clone().map(|spool| {
+            tokio::spawn(
+                replay_spool(
+                    spool,
+                    redis_client(&config, redis_pool.clone(), fencing_token.clone()),
+                    config.redis_config.stream_key.clone(),
+                    config.publish_batch_size,
+                    fencing_token.clone(),
+                    config.leader_election,
+                    control.clone(),
+                )
+                .in_current_span(),
+            )
+        });
+
+        loop {
+            let run = tokio::spawn(
+                run_chain(
+                    config.clone(),
+                    redis_pool.clone(),
+                    database_pool.clone(),
+                    spool.clone(),
+                    control.clone(),
+                    fencing_token.clone(),
+                )
+                .in_current_span(),
+            );
+
+            match run.await {
+                Ok(Ok(())) => {
+                    if let (Some(spool), Some(replay)) = (&spool, replay) {
+                        while !spool.is_empty().await {
+                            tracing::info!("Waiting for the spool to drain.");
+                            tokio::time::sleep(SPOOL_DRAIN_DELAY).await;
+                        }
+                        replay.abort();
+                    }
+                    tracing::info!("Chain synchronization finished.");
+                    return;
+                }
+                Ok(Err(error)) if error.is::<ChainVerificationError>() => {
+                    tracing::error!("Refusing to sync chain: {}", error);
+                    return;
+                }
+                Ok(Err(error)) => {
+                    tracing::error!("Chain synchronization failed: {}", error)
+                }
+                Err(error) => {
+                    tracing::error!("Chain synchronization task aborted: {}", error)
+                }
+            }
+
+            tracing::info!("Restarting chain synchronization in {:?}.", RESTART_DELAY);
+            tokio::time::sleep(RESTART_DELAY).await;
+        }
+    }
+    .instrument(span)
+    .await
+}
+
+/// Runs the chain's one-off command, without restarting it on failure.
+pub async fn run_chain_command(
+    config: ChainSyncConfig,
+    redis_pool: Arc<Pool<RedisConnectionManager>>,
+    database_pool: Arc<PgPool>,
+) -> Result<(), ChainRunnerError> {
+    let span =
+        tracing::info_span!("chain", id = config.chain.id, name = %config.chain.name);
+
+    run_chain(
+        config,
+        redis_pool,
+        database_pool,
+        None,
+        Arc::new(ChainControl::default()),
+        Arc::new(AtomicU64::new(0)),
+    )
+    .instrument(span)
+    .await
+}
+
+fn redis_client(
+    config: &ChainSyncConfig,
+    redis_pool: Arc<Pool<RedisConnectionManager>>,
+    fencing_token: Arc<AtomicU64>,
+) -> RedisClient {
+    RedisClient {
+        pool: redis_pool,
+        encoding: config.stream_encoding,
+        id_mode: config.stream_id_mode,
+        dedup_ttl: config.dedup_ttl,
+        batch_size: config.publish_batch_size,
+        fencing_token,
+    }
+}
+
+async fn run_chain(
+    config: ChainSyncConfig,
+    redis_pool: Arc<Pool<RedisConnectionManager>>,
+    database_pool: Arc<PgPool>,
+    spool: Option<Arc<Spool>>,
+    control: Arc<ChainControl>,
+    fencing_token: Arc<AtomicU64>,
+) -> Result<(), ChainRunnerError> {
+    let chain_repository =
+        ChainRepository::new(database_pool.clone(), config.chain.clone());
+
+    if let Some(dir) = &config.rpc_replay {
+        let dir = dir.join(config.chain.id.to_string());
+        let blockchain_client = RecordingClient::<BlockchainClient>::replay(&dir);
+        let chain_id = blockchain_client.get_chain_id().await?;
+        if chain_id != config.chain.id as u64 {
+            return Err(Box::new(ChainVerificationError::ChainIdMismatch(
+                config.chain.id as u64,
+                chain_id,
+                dir.display().to_string(),
+            )));
+        }
+        verify_genesis(
+            &blockchain_client,
+            &dir.display().to_string(),
+            &chain_repository,
+        )
+        .await?;
+        tracing::info!("Replaying RPC responses from {}", dir.display());
+        return sync_chain(
+            blockchain_client,
+            config,
+            redis_pool,
+            database_pool,
+            spool,
+            control,
+            fencing_token,
+        )
+        .await;
+    }
+
+    let blockchain_client =
+        BlockchainClient::connect(&config.rpc, config.chain.id as u64).await?;
+    let genesis_hash = verify_genesis(
+        &blockchain_client,
+        blockchain_client.active_url(),
+        &chain_repository,
+    )
+    .await?;
+    blockchain_client.pin_genesis_hash(genesis_hash);
+    tracing::info!(
+        "Connected to RPC provider {}",
+        blockchain_client.active_url()
+    );
+
+    let Some(dir) = &config.rpc_record else {
+        return sync_chain(
+            blockchain_client,
+            config,
+            redis_pool,
+            database_pool,
+            spool,
+            control,
+            fencing_token,
+        )
+        .await;
+    };
+    let dir = dir.join(config.chain.id.to_string());
+    let blockchain_client = RecordingClient::record(blockchain_client, &dir).await?;
+    // Recorded so a replay can run the same checks.
+    blockchain_client.get_chain_id().await?;
+    blockchain_client.get_block_hash(0).await?;
+    tracing::info!("Recording RPC responses to {}", dir.display());
+    sync_chain(
+        blockchain_client,
+        config,
+        redis_pool,
+        database_pool,
+        spool,
+        control,
+        fencing_token,
+    )
+    .await
+}
+
+async fn sync_chain<B: BlockchainClientTrait>(
+    blockchain_client: B,
+    mut config: ChainSyncConfig,
+    redis_pool: Arc<Pool<RedisConnectionManager>>,
+    database_pool: Arc<PgPool>,
+    spool: Option<Arc<Spool>>,
+    control: Arc<ChainControl>,
+    fencing_token: Arc<AtomicU64>,
+) -> Result<(), ChainRunnerError> {
+    let block_time_repository =
+        BlockTimeRepository::new(database_pool.clone(), config.chain.clone());
+    resolve_block_range(&blockchain_client, &block_time_repository, &mut config).await?;
+
+    let block_repository =
+        BlockRepository::new(database_pool.clone(), config.chain.clone());
+    let transaction_repository =
+        TransactionRepository::new(database_pool.clone(), config.chain.clone());
+    let log_repository = LogRepository::new(database_pool.clone(), config.chain.clone());
+
+    let mempool_redis_client =
+        redis_client(&config, redis_pool.clone(), fencing_token.clone());
+    let mut synchronizer = ChainSynchronizer::new(
+        blockchain_client,
+        redis_client(&config, redis_pool, fencing_token.clone()),
+        block_repository.clone(),
+        transaction_repository,
+        log_repository,
+        config.clone(),
+    )
+    .with_control(control.clone());
+    if let Some(spool) = spool {
+        synchronizer = synchronizer.with_spool(spool);
+    }
+
+    let backfill_synchronizer = synchronizer
+        .clone()
+        .with_lane(SyncLane::Backfill)
+        .with_workers(config.backfill_workers);
+    let checkpoint_repository =
+        CheckpointRepository::new(database_pool.clone(), config.chain.clone());
+
+    if config.command != Command::Sync {
+        return admin::run_command(
+            &config.command,
+            &synchronizer,
+            &backfill_synchronizer,
+            &block_repository,
+            &checkpoint_repository,
+            &config,
+        )
+        .await;
+    }
+
+    if let (true, Some(end_block)) = (config.shard_backfill, config.end_block) {
+        let lease_repository = LeaseRepository::new(
+            database_pool,
+            config.chain.clone(),
+            config.instance_id.clone(),
+            config.lease_ttl,
+        );
+        let start_block = synchronizer.start_block();
+        tracing::info!(
+            "Sharding backfill from block {} to block {} as {}",
+            start_block,
+            end_block,
+            config.instance_id
+        );
+        return run_sharded_backfill(
+            &backfill_synchronizer,
+            &lease_repository,
+            start_block,
+            end_block,
+            config.range_size,
+        )
+        .await;
+    }
+
+    // A rewind stops the lanes, which then resume from the rewound
+    // checkpoints.
+    let lanes = async {
+        loop {
+            let to = tokio::select! {
+                result = run_lanes(
+                    &synchronizer,
+                    &backfill_synchronizer,
+                    &checkpoint_repository,
+                    &block_repository,
+                    &config,
+                ) => return result,
+                to = serve_requests(&control, &backfill_synchronizer) => to,
+            };
+            tracing::warn!("Rewinding to block {} on request", to);
+            match rewind(&synchronizer, &checkpoint_repository, to).await {
+                Ok(reverted) => {
+                    tracing::info!("Reverted {} blocks above block {}.", reverted, to)
+                }
+                Err(error) => tracing::error!("Rewind to block {} failed: {}", to, error),
+            }
+        }
+    };
+    // The mempool is watched for as long as the lanes run, and a node serving
+    // another chain stops the chain like an RPC would.
+    let lanes = async {
+        let Some(ws_url) = &config.mempool_ws else {
+            return lanes.await;
+        };
+        tokio::select! {
+            result = lanes => result,
+            result = watch_mempool(ws_url, &mempool_redis_client, &config, &control) => {
+                result.map_err(ChainRunnerError::from)
+            }
+        }
+    };
+
+    if !config.leader_election {
+        return lanes.await;
+    }
+
+    let leader_repository = LeaderRepository::new(
+        database_pool,
+        config.chain.clone(),
+        config.instance_id.clone(),
+        config.leader_ttl,
+    );
+    tracing::info!("Standing by for leadership as {}", config.instance_id);
+    let token = acquire_leadership(&leader_repository).await?;
+    fencing_token.store(token, Ordering::SeqCst);
+    tracing::info!("Became leader with fencing token {}", token);
+
+    // The lanes resume from the checkpoints the previous leader saved.
+    let result = tokio::select! {
+        result = lanes => result,
+        _ = hold_leadership(&leader_repository, token) => {
+            Err("Lost leadership to another instance".into())
+        }
+    };
+    if let Err(error) = leader_repository.release(token).await {
+        tracing::warn!("Error releasing leadership: {}", error);
+    }
+    result
+}
+
+/// Starts the backfills requested through `control`, until a rewind is
+/// requested. Returning aborts the backfills still running.
+async fn serve_requests<B, R, E, T, L>(
+    control: &ChainControl,
+    backfill_synchronizer: &ChainSynchronizer<B, R, E, T, L>,
+) -> u64
+where
+    B: BlockchainClientTrait,
+    R: RedisClientTrait,
+    E: BlockRepositoryTrait,
+    T: TransactionRepositoryTrait,
+    L: LogRepositoryTrait,
+{
+    let mut backfills = JoinSet::new();
+    loop {
+        match control.next_request().await {
+            ChainRequest::Backfill { from, to } => {
+                tracing::info!("Backfilling blocks {} to {} on request", from, to);
+                let synchronizer = backfill_synchronizer.clone();
+                backfills.spawn(
+                    async move {
+                        if let Err(error) = admin::backfill(&synchronizer, from, to).await
+                        {
+                            tracing::error!(
+                                "Backfill from block {} to block {} failed: {}",
+                                from,
+                                to,
+                                error
+                            );
+                        }
+                    }
+                    .in_current_span(),
+                );
+            }
+            ChainRequest::Rewind { to } => return to,
+        }
+    }
+}
+
+async fn rewind<B, R, E, T, L, C>(
+    synchronizer: &ChainSynchronizer<B, R, E, T, L>,
+    checkpoint_repository: &C,
+    to: u64,
+) -> Result<u64, ChainRunnerError>
+where
+    B: BlockchainClientTrait,
+    R: RedisClientTrait,
+    E: BlockRepositoryTrait,
+    T: TransactionRepositoryTrait,
+    L: LogRepositoryTrait,
+    C: CheckpointRepositoryTrait,
+{
+    let reverted = synchronizer.rewind(to).await?;
+    checkpoint_repository.rewind_checkpoints(to).await?;
+    Ok(reverted)
+}
+
+/// Compares the genesis hash served by `source` with the one recorded in
+/// Postgres the first time the chain was synced, and returns it so it can be
+/// pinned on the client for later failovers.
+async fn verify_genesis<B: BlockchainClientTrait>(
+    blockchain_client: &B,
+    source: &str,
+    chain_repository: &ChainRepository,
+) -> Result<H256, ChainRunnerError> {
+    let genesis_hash = blockchain_client
+        .get_block_hash(0)
+        .await?
+        .ok_or_else(|| ChainVerificationError::MissingGenesis(source.to_string()))?;
+
+    let recorded = chain_repository
+        .record_genesis_hash(&format!("0x{}", hex::encode(genesis_hash)))
+        .await?;
+    let recorded_hash = recorded.parse().map_err(|_| {
+        format!(
+            "Invalid genesis hash {} recorded in the chain table",
+            recorded
+        )
+    })?;
+    if recorded_hash != genesis_hash {
+        return Err(Box::new(ChainVerificationError::GenesisMismatch(
+            recorded_hash,
+            genesis_hash,
+        )));
+    }
+
+    Ok(genesis_hash)
+}
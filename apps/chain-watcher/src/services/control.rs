@@ -0,0 +1,186 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use common::types::SyncLane;
+use serde::Serialize;
+use tokio::sync::{mpsc, watch};
+
+use crate::clients::redis_client::StreamIdMode;
+
+use super::metrics::ChainMetrics;
+
+/// An operation the chain runner carries out between the lanes' blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainRequest {
+    /// Indexes the blocks from `from` to `to` alongside the lanes.
+    Backfill { from: u64, to: u64 },
+    /// Stops the lanes, reverts the blocks above `to`, and restarts the
+    /// lanes from their rewound checkpoints.
+    Rewind { to: u64 },
+}
+
+/// A block being fetched or published.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InFlightBlock {
+    pub block_number: u64,
+    pub lane: SyncLane,
+    pub elapsed_ms: u64,
+}
+
+/// Runtime controls of one chain, shared by its synchronizers and the admin
+/// API. They outlive restarts of the chain.
+pub struct ChainControl {
+    pub metrics: Arc<ChainMetrics>,
+    /// Backfills and rewinds publish below the stream's last entry, which
+    /// `block-tx` ids refuse.
+    stream_id_mode: StreamIdMode,
+    paused: watch::Sender<bool>,
+    /// Overrides the configured workers of both lanes when not 0.
+    worker_limit: AtomicUsize,
+    next_id: AtomicU64,
+    in_flight: Mutex<HashMap<u64, (u64, SyncLane, Instant)>>,
+    requests: mpsc::UnboundedSender<ChainRequest>,
+    pending_requests: tokio::sync::Mutex<mpsc::UnboundedReceiver<ChainRequest>>,
+}
+
+impl Default for ChainControl {
+    fn default() -> Self {
+        Self::new(Arc::new(ChainMetrics::default()))
+    }
+}
+
+impl ChainControl {
+    pub fn new(metrics: Arc<ChainMetrics>) -> Self {
+        let (requests, pending_requests) = mpsc::unbounded_channel();
+        Self {
+            metrics,
+            stream_id_mode: StreamIdMode::Auto,
+            paused: watch::Sender::new(false),
+            worker_limit: AtomicUsize::new(0),
+            next_id: AtomicU64::new(0),
+            in_flight: Mutex::new(HashMap::new()),
+            requests,
+            pending_requests: tokio::sync::Mutex::new(pending_requests),
+        }
+    }
+
+    pub fn with_stream_id_mode(mut self, stream_id_mode: StreamIdMode) -> Self {
+        self.stream_id_mode = stream_id_mode;
+        self
+    }
+
+    pub fn stream_id_mode(&self) -> StreamIdMode {
+        self.stream_id_mode
+    }
+
+    /// Stops the synchronizers before their next block is fetched or
+    /// published. Returns false if syncing was already paused.
+    pub fn pause(&self) -> bool {
+        let changed = !self.paused.send_replace(true);
+        ChainMetrics::set(&self.metrics.paused, 1);
+        changed
+    }
+
+    /// Returns false if syncing was not paused.
+    pub fn resume(&self) -> bool {
+        let changed = self.paused.send_replace(false);
+        ChainMetrics::set(&self.metrics.paused, 0);
+        changed
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    pub async fn wait_while_paused(&self) {
+        // The sender lives as long as `self`, so this cannot fail.
+        let _ = self.paused.subscribe().wait_for(|paused| !paused).await;
+    }
+
+    pub async fn wait_until_paused(&self) {
+        let _ = self.paused.subscribe().wait_for(|paused| *paused).await;
+    }
+
+    /// Blocks fetched at once by each lane in place of its configured
+    /// workers, or the configured workers again with `None`.
+    pub fn set_worker_limit(&self, workers: Option<usize>) {
+        let workers = workers.unwrap_or(0);
+        self.worker_limit.store(workers, Ordering::Relaxed);
+        ChainMetrics::set(&self.metrics.worker_limit, workers as u64);
+    }
+
+    pub fn worker_limit(&self) -> Option<usize> {
+        match self.worker_limit.load(Ordering::Relaxed) {
+            0 => None,
+            workers => Some(workers),
+        }
+    }
+
+    /// Lists `block_number` among the blocks in flight until the returned
+    /// guard is dropped.
+    pub fn track(self: &Arc<Self>, block_number: u64, lane: SyncLane) -> InFlightGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut in_flight = self.in_flight.lock().unwrap();
+        in_flight.insert(id, (block_number, lane, Instant::now()));
+        ChainMetrics::set(&self.metrics.blocks_in_flight, in_flight.len() as u64);
+        InFlightGuard {
+            control: self.clone(),
+            id,
+        }
+    }
+
+    /// The blocks in flight, in block order.
+    pub fn in_flight(&self) -> Vec<InFlightBlock> {
+        let mut blocks: Vec<InFlightBlock> = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(block_number, lane, started)| InFlightBlock {
+                block_number: *block_number,
+                lane: *lane,
+                elapsed_ms: started.elapsed().as_millis() as u64,
+            })
+            .collect();
+        blocks.sort_by_key(|block| block.block_number);
+        blocks
+    }
+
+    /// Queues `request` for the chain runner.
+    pub fn request(&self, request: ChainRequest) {
+        // The receiver lives as long as `self`, so this cannot fail.
+        let _ = self.requests.send(request);
+    }
+
+    /// Waits for the next queued request.
+    pub async fn next_request(&self) -> ChainRequest {
+        let mut pending_requests = self.pending_requests.lock().await;
+        match pending_requests.recv().await {
+            Some(request) => request,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// Removes its block from the blocks in flight when dropped.
+pub struct InFlightGuard {
+    control: Arc<ChainControl>,
+    id: u64,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut in_flight = self.control.in_flight.lock().unwrap();
+        in_flight.remove(&self.id);
+        ChainMetrics::set(
+            &self.control.metrics.blocks_in_flight,
+            in_flight.len() as u64,
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+
+use common::trie::{logs_bloom, receipts_root};
+use ethers::types::{Block, Log, Transaction, TransactionReceipt, H256};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityError {
+    /// The block, and how many receipts were expected and fetched.
+    ReceiptCount(u64, usize, usize),
+    /// The block, and the receipts root of its header and of its receipts.
+    ReceiptsRoot(u64, H256, H256),
+    LogsBloom(u64),
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityError::ReceiptCount(block_number, expected, actual) => write!(
+                f,
+                "Integrity Error: block {} has {} transactions, {} receipts were fetched",
+                block_number, expected, actual
+            ),
+            IntegrityError::ReceiptsRoot(block_number, expected, actual) => write!(
+                f,
+                "Integrity Error: block {} has receipts root {:?}, its receipts hash to {:?}",
+                block_number, expected, actual
+            ),
+            IntegrityError::LogsBloom(block_number) => write!(
+                f,
+                "Integrity Error: the logs bloom of block {} does not match its logs",
+                block_number
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+/// Checks that `receipts`, in transaction order, are the complete receipts of
+/// `block`: they must hash to the header's receipts root, and their logs must
+/// match the header's logs bloom.
+pub fn verify_receipts(
+    block: &Block<Transaction>,
+    receipts: &[TransactionReceipt],
+) -> Result<(), IntegrityError> {
+    let block_number = block.number.unwrap_or_default().as_u64();
+    if receipts.len() != block.transactions.len() {
+        return Err(IntegrityError::ReceiptCount(
+            block_number,
+            block.transactions.len(),
+            receipts.len(),
+        ));
+    }
+
+    let receipts_root = receipts_root(receipts);
+    if receipts_root != block.receipts_root {
+        return Err(IntegrityError::ReceiptsRoot(
+            block_number,
+            block.receipts_root,
+            receipts_root,
+        ));
+    }
+
+    let logs: Vec<Log> = receipts
+        .iter()
+        .flat_map(|receipt| receipt.logs.iter().cloned())
+        .collect();
+    if Some(logs_bloom(&logs)) != block.logs_bloom {
+        return Err(IntegrityError::LogsBloom(block_number));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,294 @@
+use std::time::{Duration, Instant};
+
+use common::types::SyncLane;
+
+use crate::{
+    clients::{
+        blockchain_client::BlockchainClientTrait,
+        redis_client::{RedisClientTrait, StreamIdMode},
+    },
+    config::ChainSyncConfig,
+};
+
+use super::{
+    repositories::{
+        block::BlockRepositoryTrait,
+        checkpoint::{CheckpointRepositoryTrait, SyncCheckpoint},
+        log::LogRepositoryTrait,
+        transaction::TransactionRepositoryTrait,
+    },
+    sync::{ChainSynchronizer, SyncError},
+};
+
+/// Blocks synced between two checkpoint saves.
+const CHECKPOINT_INTERVAL: u64 = 1000;
+
+/// Wait before polling the RPC again once the live lane reached the head.
+const HEAD_POLL_DELAY: Duration = Duration::from_secs(1);
+
+/// Follows the head in the live lane while the backfill lane indexes the
+/// blocks before it, each resuming from its checkpoint.
+///
+/// The live lane starts after the head seen on the first run, and retries
+/// the blocks it skipped. The backfill lane covers the configured start block
+/// up to there, then fills any gaps up to the live lane's checkpoint, and
+/// finishes.
+pub async fn run_lanes<B, R, E, T, L, C>(
+    live: &ChainSynchronizer<B, R, E, T, L>,
+    backfill: &ChainSynchronizer<B, R, E, T, L>,
+    checkpoint_repository: &C,
+    block_repository: &E,
+    config: &ChainSyncConfig,
+) -> Result<(), SyncError>
+where
+    B: BlockchainClientTrait,
+    R: RedisClientTrait,
+    E: BlockRepositoryTrait,
+    T: TransactionRepositoryTrait,
+    L: LogRepositoryTrait,
+    C: CheckpointRepositoryTrait,
+{
+    let live_checkpoint =
+        match checkpoint_repository.get_checkpoint(SyncLane::Live).await? {
+            Some(checkpoint) => checkpoint,
+            None => {
+                let chain_head = live.chain_head().await?;
+                let last_backfilled = config
+                    .end_block
+                    .map_or(chain_head, |end| end.min(chain_head));
+                let checkpoint = SyncCheckpoint {
+                    start_block: (last_backfilled + 1).max(live.start_block()),
+                    last_block: None,
+                };
+                checkpoint_repository
+                    .save_checkpoint(SyncLane::Live, checkpoint)
+                    .await?;
+                checkpoint
+            }
+        };
+    tracing::info!("Live lane starts at block {}", live_checkpoint.start_block);
+
+    let live_lane = run_live_lane(live, checkpoint_repository, live_checkpoint, config);
+    let backfill_lane = run_backfill_lane(
+        backfill,
+        checkpoint_repository,
+        block_repository,
+        live_checkpoint.start_block,
+        config,
+    );
+
+    // Block-tx ids must increase, so the backfill has to be published first.
+    if config.stream_id_mode == StreamIdMode::BlockTx {
+        backfill_lane.await?;
+        return live_lane.await;
+    }
+    tokio::try_join!(live_lane, backfill_lane)?;
+    Ok(())
+}
+
+async fn run_live_lane<B, R, E, T, L, C>(
+    synchronizer: &ChainSynchronizer<B, R, E, T, L>,
+    checkpoint_repository: &C,
+    checkpoint: SyncCheckpoint,
+    config: &ChainSyncConfig,
+) -> Result<(), SyncError>
+where
+    B: BlockchainClientTrait,
+    R: RedisClientTrait,
+    E: BlockRepositoryTrait,
+    T: TransactionRepositoryTrait,
+    L: LogRepositoryTrait,
+    C: CheckpointRepositoryTrait,
+{
+    let mut start_block = checkpoint
+        .last_block
+        .map_or(checkpoint.start_block, |last| last + 1);
+    // Blocks the lane skipped. They are retried before every chunk, and the
+    // checkpoint stays below the first of them, so a restart syncs it again.
+    let mut gaps: Vec<u64> = Vec::new();
+
+    loop {
+        if !gaps.is_empty() {
+            synchronizer.sync_missing_blocks(gaps.clone()).await?;
+            gaps = synchronizer
+                .missing_blocks(gaps[0], start_block - 1)
+                .await?;
+            save_live_checkpoint(
+                checkpoint_repository,
+                checkpoint.start_block,
+                &gaps,
+                start_block - 1,
+            )
+            .await?;
+        }
+
+        if config.end_block.is_some_and(|last| start_block > last) {
+            if gaps.is_empty() {
+                return Ok(());
+            }
+            tokio::time::sleep(HEAD_POLL_DELAY).await;
+            continue;
+        }
+
+        let chain_head = synchronizer.chain_head().await?;
+        let end_block = config
+            .end_block
+            .map_or(chain_head, |end| end.min(chain_head));
+        if start_block > end_block {
+            tokio::time::sleep(HEAD_POLL_DELAY).await;
+            continue;
+        }
+        let end_block = end_block.min(start_block + CHECKPOINT_INTERVAL - 1);
+
+        tracing::info!("Indexing from block {} to block {}", start_block, end_block);
+        synchronizer.sync(start_block, end_block).await?;
+        gaps.extend(synchronizer.missing_blocks(start_block, end_block).await?);
+        if !gaps.is_empty() {
+            tracing::warn!(
+                "{} blocks are missing, from block {}. Retrying them.",
+                gaps.len(),
+                gaps[0]
+            );
+        }
+        save_live_checkpoint(
+            checkpoint_repository,
+            checkpoint.start_block,
+            &gaps,
+            end_block,
+        )
+        .await?;
+
+        start_block = end_block + 1;
+    }
+}
+
+/// Saves the live lane's progress up to `synced_to`, or up to the block
+/// before the first of `gaps`.
+async fn save_live_checkpoint<C: CheckpointRepositoryTrait>(
+    checkpoint_repository: &C,
+    start_block: u64,
+    gaps: &[u64],
+    synced_to: u64,
+) -> Result<(), SyncError> {
+    let last_block = match gaps.first() {
+        Some(first) => first.checked_sub(1).filter(|last| *last >= start_block),
+        None => Some(synced_to),
+    };
+    checkpoint_repository
+        .save_checkpoint(
+            SyncLane::Live,
+            SyncCheckpoint {
+                start_block,
+                last_block,
+            },
+        )
+        .await?;
+    Ok(())
+}
+
+async fn run_backfill_lane<B, R, E, T, L, C>(
+    synchronizer: &ChainSynchronizer<B, R, E, T, L>,
+    checkpoint_repository: &C,
+    block_repository: &E,
+    live_start: u64,
+    config: &ChainSyncConfig,
+) -> Result<(), SyncError>
+where
+    B: BlockchainClientTrait,
+    R: RedisClientTrait,
+    E: BlockRepositoryTrait,
+    T: TransactionRepositoryTrait,
+    L: LogRepositoryTrait,
+    C: CheckpointRepositoryTrait,
+{
+    let first_block = synchronizer.start_block();
+    // At least 1, since the live lane starts after the head of the first run.
+    let last_block = live_start - 1;
+
+    // Without a checkpoint for this start block, blocks indexed by earlier
+    // versions count as done; the gap fill below catches any holes.
+    let progress = match checkpoint_repository
+        .get_checkpoint(SyncLane::Backfill)
+        .await?
+    {
+        Some(checkpoint) if checkpoint.start_block == first_block => {
+            checkpoint.last_block
+        }
+        _ => block_repository
+            .get_last_indexed_block()
+            .await?
+            .filter(|block| *block >= first_block && *block < live_start),
+    };
+    let chunk_size = config.backfill_rate.map_or(CHECKPOINT_INTERVAL, |rate| {
+        rate.clamp(1, CHECKPOINT_INTERVAL)
+    });
+
+    let mut start_block = progress.map_or(first_block, |last| last + 1);
+    while start_block <= last_block {
+        let end_block = last_block.min(start_block + chunk_size - 1);
+        let started = Instant::now();
+
+        tracing::info!(
+            "Backfilling from block {} to block {}",
+            start_block,
+            end_block
+        );
+        // Refreshes the chain head, so finality is judged against it.
+        synchronizer.chain_head().await?;
+        synchronizer.sync(start_block, end_block).await?;
+        checkpoint_repository
+            .save_checkpoint(
+                SyncLane::Backfill,
+                SyncCheckpoint {
+                    start_block: first_block,
+                    last_block: Some(end_block),
+                },
+            )
+            .await?;
+
+        throttle(end_block - start_block + 1, started, config.backfill_rate).await;
+        start_block = end_block + 1;
+    }
+
+    // Up to the live lane's checkpoint, so blocks it skipped are filled too.
+    let live_last_block = checkpoint_repository
+        .get_checkpoint(SyncLane::Live)
+        .await?
+        .and_then(|checkpoint| checkpoint.last_block)
+        .map_or(last_block, |last| last.max(last_block));
+    let gaps = synchronizer
+        .missing_blocks(synchronizer.start_block(), live_last_block)
+        .await?;
+    if !gaps.is_empty() && config.stream_id_mode == StreamIdMode::BlockTx {
+        // They would be published below the stream's last entry.
+        tracing::error!(
+            "{} blocks are missing, from block {}, and cannot be filled with --stream-id-mode block-tx",
+            gaps.len(),
+            gaps[0]
+        );
+        return Ok(());
+    }
+    if !gaps.is_empty() {
+        tracing::info!("Filling {} missing blocks", gaps.len());
+    }
+    for chunk in gaps.chunks(chunk_size as usize) {
+        let started = Instant::now();
+        synchronizer.sync_missing_blocks(chunk.to_vec()).await?;
+        throttle(chunk.len() as u64, started, config.backfill_rate).await;
+    }
+
+    tracing::info!("Backfill finished.");
+    Ok(())
+}
+
+/// Sleeps for whatever is left of the time `blocks` may take at `rate` blocks
+/// per second.
+async fn throttle(blocks: u64, started: Instant, rate: Option<u64>) {
+    let Some(rate) = rate else {
+        return;
+    };
+    let budget = Duration::from_secs_f64(blocks as f64 / rate as f64);
+    if let Some(remaining) = budget.checked_sub(started.elapsed()) {
+        tokio::time::sleep(remaining).await;
+    }
+}
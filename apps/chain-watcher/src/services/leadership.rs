@@ -0,0 +1,44 @@
+use std::time::{Duration, Instant};
+
+use super::repositories::leader::LeaderRepositoryTrait;
+
+/// Interval between a standby's attempts to take over and between a leader's
+/// renewals, short enough that a few missed renewals do not cost leadership.
+fn renew_interval<S: LeaderRepositoryTrait>(leader_repository: &S) -> Duration {
+    Duration::from_secs(leader_repository.lease_ttl()).div_f64(3.0)
+}
+
+/// Waits until this instance becomes leader and returns its fencing token.
+pub async fn acquire_leadership<S: LeaderRepositoryTrait>(
+    leader_repository: &S,
+) -> Result<u64, sqlx::Error> {
+    loop {
+        if let Some(fencing_token) = leader_repository.try_acquire().await? {
+            return Ok(fencing_token);
+        }
+        tokio::time::sleep(renew_interval(leader_repository)).await;
+    }
+}
+
+/// Renews leadership until another instance takes over, or until it could not
+/// be renewed for a whole TTL and may have expired.
+pub async fn hold_leadership<S: LeaderRepositoryTrait>(
+    leader_repository: &S,
+    fencing_token: u64,
+) {
+    let ttl = Duration::from_secs(leader_repository.lease_ttl());
+    let mut renewed_at = Instant::now();
+    loop {
+        tokio::time::sleep(renew_interval(leader_repository)).await;
+        match leader_repository.renew(fencing_token).await {
+            Ok(true) => renewed_at = Instant::now(),
+            Ok(false) => return,
+            Err(error) => {
+                tracing::error!("Error renewing leadership: {}", error);
+                if renewed_at.elapsed() >= ttl {
+                    return;
+                }
+            }
+        }
+    }
+}
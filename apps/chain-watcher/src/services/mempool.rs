@@ -0,0 +1,424 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    pin::Pin,
+    time::Duration,
+};
+
+use common::types::{
+    FinalityStatus, PendingResolution, PendingStatus, PendingTransfer, StreamEnvelope,
+    StreamPayload, TransferMethod,
+};
+use ethers::{
+    abi::{self, ParamType, Token},
+    providers::{Middleware, Provider, Ws},
+    types::{Transaction, H160, H256, U256},
+};
+use futures::{Stream, StreamExt};
+
+use crate::{
+    clients::{
+        blockchain_client::ChainVerificationError, redis_client::RedisClientTrait,
+    },
+    config::ChainSyncConfig,
+};
+
+use super::control::ChainControl;
+
+/// `transferFrom(address,address,uint256)`
+const TRANSFER_FROM: [u8; 4] = [0x23, 0xb8, 0x72, 0xdd];
+/// `safeTransferFrom(address,address,uint256)`
+const SAFE_TRANSFER_FROM: [u8; 4] = [0x42, 0x84, 0x2e, 0x0e];
+/// `safeTransferFrom(address,address,uint256,bytes)`
+const SAFE_TRANSFER_FROM_WITH_DATA: [u8; 4] = [0xb8, 0x8d, 0x4f, 0xde];
+/// `safeTransferFrom(address,address,uint256,uint256,bytes)`
+const SAFE_TRANSFER_FROM_AMOUNT: [u8; 4] = [0xf2, 0x42, 0x43, 0x2a];
+/// `safeBatchTransferFrom(address,address,uint256[],uint256[],bytes)`
+const SAFE_BATCH_TRANSFER_FROM: [u8; 4] = [0x2e, 0xb2, 0xc2, 0xd6];
+
+/// Wait before reconnecting after the WebSocket connection failed.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Pending transactions fetched at once when the node only sends hashes.
+const FETCH_CONCURRENCY: usize = 16;
+
+/// Pending transfers followed at once. Beyond it, the oldest is no longer
+/// followed and gets no resolution, as after a restart.
+const MAX_TRACKED_TRANSACTIONS: usize = 10_000;
+
+#[derive(Debug)]
+pub enum MempoolError {
+    SubscriptionClosed,
+}
+
+impl fmt::Display for MempoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MempoolError::SubscriptionClosed => {
+                write!(f, "Mempool Error: the node closed a subscription")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MempoolError {}
+
+/// Decodes a call to one of the ERC-721 or ERC-1155 transfer functions.
+/// Returns `None` for any other transaction, or calldata that does not decode.
+pub fn decode_transfer(transaction: &Transaction) -> Option<PendingTransfer> {
+    let contract = transaction.to?;
+    let input = transaction.input.as_ref();
+    if input.len() < 4 {
+        return None;
+    }
+    let (selector, arguments) = input.split_at(4);
+
+    let address = || ParamType::Address;
+    let uint = || ParamType::Uint(256);
+    let uints = || ParamType::Array(Box::new(ParamType::Uint(256)));
+    let (method, params) = match selector.try_into().ok()? {
+        TRANSFER_FROM => (
+            TransferMethod::TransferFrom,
+            vec![address(), address(), uint()],
+        ),
+        SAFE_TRANSFER_FROM => (
+            TransferMethod::SafeTransferFrom,
+            vec![address(), address(), uint()],
+        ),
+        SAFE_TRANSFER_FROM_WITH_DATA => (
+            TransferMethod::SafeTransferFrom,
+            vec![address(), address(), uint(), ParamType::Bytes],
+        ),
+        SAFE_TRANSFER_FROM_AMOUNT => (
+            TransferMethod::SafeTransferFrom,
+            vec![address(), address(), uint(), uint(), ParamType::Bytes],
+        ),
+        SAFE_BATCH_TRANSFER_FROM => (
+            TransferMethod::SafeBatchTransferFrom,
+            vec![address(), address(), uints(), uints(), ParamType::Bytes],
+        ),
+        _ => return None,
+    };
+    let tokens = abi::decode(&params, arguments).ok()?;
+
+    let (token_ids, amounts) = match (selector.try_into().ok()?, &tokens[2..]) {
+        (SAFE_BATCH_TRANSFER_FROM, [Token::Array(ids), Token::Array(amounts), ..]) => {
+            let ids: Vec<U256> =
+                ids.iter().cloned().filter_map(Token::into_uint).collect();
+            let amounts: Vec<U256> = amounts
+                .iter()
+                .cloned()
+                .filter_map(Token::into_uint)
+                .collect();
+            if ids.len() != amounts.len() {
+                return None;
+            }
+            (ids, amounts)
+        }
+        (SAFE_TRANSFER_FROM_AMOUNT, [Token::Uint(id), Token::Uint(amount), ..]) => {
+            (vec![*id], vec![*amount])
+        }
+        (_, [Token::Uint(id), ..]) => (vec![*id], Vec::new()),
+        _ => return None,
+    };
+
+    Some(PendingTransfer {
+        transaction_hash: transaction.hash,
+        sender: transaction.from,
+        contract,
+        method,
+        from: tokens[0].clone().into_address()?,
+        to: tokens[1].clone().into_address()?,
+        token_ids,
+        amounts,
+        nonce: transaction.nonce,
+        gas_price: transaction.gas_price,
+        max_priority_fee_per_gas: transaction.max_priority_fee_per_gas,
+    })
+}
+
+struct TrackedTransaction {
+    sender: H160,
+    nonce: U256,
+    /// The head when the transaction was seen, or last found still pending.
+    checked_at: u64,
+}
+
+/// Pending transfers published but not yet resolved.
+pub struct PendingTracker {
+    pending: HashMap<H256, TrackedTransaction>,
+    /// Tracked hashes, oldest first. May still hold hashes resolved since.
+    order: VecDeque<H256>,
+    timeout_blocks: u64,
+    max_tracked: usize,
+}
+
+impl PendingTracker {
+    pub fn new(timeout_blocks: u64) -> Self {
+        Self {
+            pending: HashMap::new(),
+            order: VecDeque::new(),
+            timeout_blocks,
+            max_tracked: MAX_TRACKED_TRANSACTIONS,
+        }
+    }
+
+    pub fn with_max_tracked(mut self, max_tracked: usize) -> Self {
+        self.max_tracked = max_tracked.max(1);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Starts tracking `transfer`, and stops tracking the oldest transfer
+    /// when `max_tracked` are. Returns false if it already was tracked.
+    pub fn track(&mut self, transfer: &PendingTransfer, head: u64) -> bool {
+        if self.pending.contains_key(&transfer.transaction_hash) {
+            return false;
+        }
+        while self.pending.len() >= self.max_tracked {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if self.pending.remove(&oldest).is_some() {
+                tracing::debug!("No longer following pending transaction {:?}.", oldest);
+            }
+        }
+        // Forgets resolved hashes once they outnumber the tracked ones.
+        if self.order.len() >= 2 * self.max_tracked {
+            let pending = &self.pending;
+            self.order.retain(|hash| pending.contains_key(hash));
+        }
+        self.order.push_back(transfer.transaction_hash);
+        self.pending.insert(
+            transfer.transaction_hash,
+            TrackedTransaction {
+                sender: transfer.sender,
+                nonce: transfer.nonce,
+                checked_at: head,
+            },
+        );
+        true
+    }
+
+    /// Resolves the tracked transactions a new block includes, and those it
+    /// replaces with another transaction from the same sender and nonce.
+    pub fn resolve_block(
+        &mut self,
+        transactions: &[Transaction],
+    ) -> Vec<PendingResolution> {
+        let mut resolutions = Vec::new();
+        for transaction in transactions {
+            if self.pending.remove(&transaction.hash).is_some() {
+                resolutions.push(PendingResolution {
+                    transaction_hash: transaction.hash,
+                    status: PendingStatus::Included,
+                });
+                continue;
+            }
+
+            let replaced: Vec<H256> = self
+                .pending
+                .iter()
+                .filter(|(_, tracked)| {
+                    tracked.sender == transaction.from
+                        && tracked.nonce == transaction.nonce
+                })
+                .map(|(hash, _)| *hash)
+                .collect();
+            for hash in replaced {
+                self.pending.remove(&hash);
+                resolutions.push(PendingResolution {
+                    transaction_hash: hash,
+                    status: PendingStatus::Dropped,
+                });
+            }
+        }
+        resolutions
+    }
+
+    /// Transactions that stayed out of a block for `timeout_blocks`, to be
+    /// looked up on the node. They are not due again for another
+    /// `timeout_blocks` unless resolved.
+    pub fn due(&mut self, head: u64) -> Vec<H256> {
+        let timeout_blocks = self.timeout_blocks;
+        self.pending
+            .iter_mut()
+            .filter(|(_, tracked)| head >= tracked.checked_at + timeout_blocks)
+            .map(|(hash, tracked)| {
+                tracked.checked_at = head;
+                *hash
+            })
+            .collect()
+    }
+
+    /// Stops tracking `hash`. Returns false if it was not tracked.
+    pub fn resolve(&mut self, hash: H256) -> bool {
+        self.pending.remove(&hash).is_some()
+    }
+}
+
+/// Publishes pending NFT transfers seen on `ws_url`, then whether each was
+/// included or dropped, to the chain's pending stream. Reconnects on errors
+/// and runs until dropped, unless the node serves another chain. While the
+/// chain is paused through `control`, it stays disconnected.
+pub async fn watch_mempool<R: RedisClientTrait>(
+    ws_url: &str,
+    redis_client: &R,
+    config: &ChainSyncConfig,
+    control: &ChainControl,
+) -> Result<(), ChainVerificationError> {
+    let mut tracker = PendingTracker::new(config.pending_timeout_blocks);
+    loop {
+        control.wait_while_paused().await;
+        tokio::select! {
+            result = watch(ws_url, redis_client, config, &mut tracker) => {
+                if let Err(error) = result {
+                    match error.downcast::<ChainVerificationError>() {
+                        Ok(error) => return Err(*error),
+                        Err(error) => tracing::error!("Mempool watcher failed: {}", error),
+                    }
+                }
+            }
+            _ = control.wait_until_paused() => {
+                tracing::info!("Mempool watcher paused.");
+                continue;
+            }
+        }
+        tracing::info!("Reconnecting to the mempool in {:?}.", RECONNECT_DELAY);
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn watch<R: RedisClientTrait>(
+    ws_url: &str,
+    redis_client: &R,
+    config: &ChainSyncConfig,
+    tracker: &mut PendingTracker,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let provider = Provider::<Ws>::connect(ws_url).await?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    if chain_id != config.chain.id as u64 {
+        return Err(Box::new(ChainVerificationError::ChainIdMismatch(
+            config.chain.id as u64,
+            chain_id,
+            ws_url.to_string(),
+        )));
+    }
+
+    let mut head = provider.get_block_number().await?.as_u64();
+    let mut blocks = provider.subscribe_blocks().await?;
+    let mut pending: Pin<Box<dyn Stream<Item = Transaction> + Send + '_>> = match provider
+        .subscribe_full_pending_txs()
+        .await
+    {
+        Ok(stream) => Box::pin(stream),
+        Err(error) => {
+            tracing::info!(
+                "Node does not send full pending transactions ({}). Fetching them by hash.",
+                error
+            );
+            Box::pin(
+                provider
+                    .subscribe_pending_txs()
+                    .await?
+                    .transactions_unordered(FETCH_CONCURRENCY)
+                    .filter_map(|transaction| async { transaction.ok() }),
+            )
+        }
+    };
+    tracing::info!("Watching the mempool on {}", ws_url);
+
+    let stream_key = config.pending_stream_key();
+    let publish = |block_number: u64,
+                   block_hash: Option<H256>,
+                   payload: StreamPayload| {
+        let envelope = StreamEnvelope::new(
+            config.chain.id,
+            block_number,
+            block_hash,
+            FinalityStatus::Unfinalized,
+            config.producer_id.clone(),
+            payload,
+        );
+        let stream_key = stream_key.clone();
+        async move {
+            // Pending messages are only useful while fresh, so they are
+            // neither retried nor spooled.
+            if let Err(error) = redis_client.send_messages(&stream_key, &[envelope]).await
+            {
+                tracing::error!("Error publishing pending message: {}", error);
+            }
+        }
+    };
+
+    loop {
+        tokio::select! {
+            transaction = pending.next() => {
+                let transaction = transaction.ok_or(MempoolError::SubscriptionClosed)?;
+                let Some(transfer) = decode_transfer(&transaction) else {
+                    continue;
+                };
+                if tracker.track(&transfer, head) {
+                    publish(head, None, StreamPayload::PendingTransfer(transfer)).await;
+                }
+            }
+            header = blocks.next() => {
+                let header = header.ok_or(MempoolError::SubscriptionClosed)?;
+                let Some(number) = header.number else {
+                    continue;
+                };
+                head = head.max(number.as_u64());
+                if tracker.is_empty() {
+                    continue;
+                }
+
+                if let Some(block) = provider.get_block_with_txs(number).await? {
+                    for resolution in tracker.resolve_block(&block.transactions) {
+                        let (block_number, block_hash) = match resolution.status {
+                            PendingStatus::Included => (number.as_u64(), block.hash),
+                            PendingStatus::Dropped => (head, None),
+                        };
+                        publish(
+                            block_number,
+                            block_hash,
+                            StreamPayload::PendingResolution(resolution),
+                        )
+                        .await;
+                    }
+                }
+
+                for hash in tracker.due(head) {
+                    let (status, block_number, block_hash) =
+                        match provider.get_transaction(hash).await? {
+                            None => (PendingStatus::Dropped, head, None),
+                            Some(transaction) => match transaction.block_number {
+                                Some(block_number) => (
+                                    PendingStatus::Included,
+                                    block_number.as_u64(),
+                                    transaction.block_hash,
+                                ),
+                                None => continue,
+                            },
+                        };
+                    tracker.resolve(hash);
+                    publish(
+                        block_number,
+                        block_hash,
+                        StreamPayload::PendingResolution(PendingResolution {
+                            transaction_hash: hash,
+                            status,
+                        }),
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use serde::Serialize;
+use tokio::net::TcpListener;
+
+/// Prefix of the exported metric names.
+const METRIC_PREFIX: &str = "chain_watcher";
+
+/// Counters for one chain's publishing, shared by the synchronizer and the
+/// spool replay task.
+#[derive(Debug, Default)]
+pub struct ChainMetrics {
+    pub messages_published: AtomicU64,
+    pub messages_duplicate: AtomicU64,
+    pub publish_errors: AtomicU64,
+    pub messages_spooled: AtomicU64,
+    pub messages_replayed: AtomicU64,
+    pub spool_rejections: AtomicU64,
+    /// Spooled messages that could not be encoded, moved to the quarantine
+    /// file.
+    pub spool_quarantined: AtomicU64,
+    /// Messages currently waiting in the spool.
+    pub spool_messages: AtomicU64,
+    /// Size of the spool's segment files, in bytes.
+    pub spool_bytes: AtomicU64,
+    /// Blocks skipped because no provider served receipts matching them.
+    pub blocks_unverified: AtomicU64,
+    /// Operations received through the admin API.
+    pub admin_operations: AtomicU64,
+    /// 1 while syncing is paused.
+    pub paused: AtomicU64,
+    /// Workers set through the admin API, 0 for the configured ones.
+    pub worker_limit: AtomicU64,
+    pub blocks_in_flight: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub messages_published: u64,
+    pub messages_duplicate: u64,
+    pub publish_errors: u64,
+    pub messages_spooled: u64,
+    pub messages_replayed: u64,
+    pub spool_rejections: u64,
+    pub spool_quarantined: u64,
+    pub spool_messages: u64,
+    pub spool_bytes: u64,
+    pub blocks_unverified: u64,
+    pub admin_operations: u64,
+    pub paused: u64,
+    pub worker_limit: u64,
+    pub blocks_in_flight: u64,
+}
+
+impl ChainMetrics {
+    pub fn add(counter: &AtomicU64, value: u64) {
+        counter.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn set(gauge: &AtomicU64, value: u64) {
+        gauge.store(value, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            messages_published: self.messages_published.load(Ordering::Relaxed),
+            messages_duplicate: self.messages_duplicate.load(Ordering::Relaxed),
+            publish_errors: self.publish_errors.load(Ordering::Relaxed),
+            messages_spooled: self.messages_spooled.load(Ordering::Relaxed),
+            messages_replayed: self.messages_replayed.load(Ordering::Relaxed),
+            spool_rejections: self.spool_rejections.load(Ordering::Relaxed),
+            spool_quarantined: self.spool_quarantined.load(Ordering::Relaxed),
+            spool_messages: self.spool_messages.load(Ordering::Relaxed),
+            spool_bytes: self.spool_bytes.load(Ordering::Relaxed),
+            blocks_unverified: self.blocks_unverified.load(Ordering::Relaxed),
+            admin_operations: self.admin_operations.load(Ordering::Relaxed),
+            paused: self.paused.load(Ordering::Relaxed),
+            worker_limit: self.worker_limit.load(Ordering::Relaxed),
+            blocks_in_flight: self.blocks_in_flight.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Serves the metrics of every chain at `GET /metrics`, in the Prometheus
+/// text format, labelled with the chain id.
+#[derive(Clone)]
+pub struct MetricsExporter {
+    chains: Arc<HashMap<u32, Arc<ChainMetrics>>>,
+}
+
+impl MetricsExporter {
+    pub fn new(chains: HashMap<u32, Arc<ChainMetrics>>) -> Self {
+        Self {
+            chains: Arc::new(chains),
+        }
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/metrics", get(metrics))
+            .with_state(self.clone())
+    }
+
+    /// Binds `addr` and serves in the background.
+    pub async fn serve(&self, addr: SocketAddr) -> std::io::Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        tracing::info!("Metrics served on {}", listener.local_addr()?);
+        let router = self.router();
+        tokio::spawn(async move {
+            if let Err(error) = axum::serve(listener, router).await {
+                tracing::error!("Metrics exporter stopped: {}", error);
+            }
+        });
+        Ok(())
+    }
+
+    pub fn render(&self) -> String {
+        let mut chain_ids: Vec<u32> = self.chains.keys().copied().collect();
+        chain_ids.sort_unstable();
+        let snapshots: Vec<(u32, serde_json::Map<String, serde_json::Value>)> = chain_ids
+            .into_iter()
+            .filter_map(|chain_id| {
+                match serde_json::to_value(self.chains[&chain_id].snapshot()) {
+                    Ok(serde_json::Value::Object(fields)) => Some((chain_id, fields)),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        // Samples of one metric are listed together.
+        let mut output = String::new();
+        let Some((_, names)) = snapshots.first() else {
+            return output;
+        };
+        for name in names.keys() {
+            for (chain_id, fields) in &snapshots {
+                let _ = writeln!(
+                    output,
+                    "{}_{}{{chain_id=\"{}\"}} {}",
+                    METRIC_PREFIX, name, chain_id, fields[name]
+                );
+            }
+        }
+        output
+    }
+}
+
+async fn metrics(State(exporter): State<MetricsExporter>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        exporter.render(),
+    )
+}
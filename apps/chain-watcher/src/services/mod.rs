@@ -1,2 +1,14 @@
+pub mod admin;
+pub mod admin_api;
+pub mod block_time;
+pub mod chain_runner;
+pub mod control;
+pub mod integrity;
+pub mod lanes;
+pub mod leadership;
+pub mod mempool;
+pub mod metrics;
 pub mod repositories;
+pub mod sharding;
+pub mod spool;
 pub mod sync;
@@ -1,18 +1,83 @@
-use std::{sync::Arc, time::Instant};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+    time::Instant,
+};
 
 use async_trait::async_trait;
 use common::types::ChainConfig;
+use ethers::{
+    types::{Block as EthersBlock, Transaction},
+    utils::hex,
+};
 use sqlx::{FromRow, PgPool};
 
+/// Rows per statement in `insert_blocks_bulk`, keeping the bind count well
+/// below Postgres' limit of 65535 parameters.
+const BULK_INSERT_CHUNK_SIZE: usize = 1000;
+
+const BLOCK_COLUMNS: &str = "block_number, chain_id, hash, parent_hash, \"timestamp\", miner, gas_used, gas_limit, base_fee_per_gas, transaction_count, log_count";
+
+const BLOCK_UPSERT: &str = " ON CONFLICT (chain_id, block_number) DO UPDATE SET hash = EXCLUDED.hash, parent_hash = EXCLUDED.parent_hash, \"timestamp\" = EXCLUDED.\"timestamp\", miner = EXCLUDED.miner, gas_used = EXCLUDED.gas_used, gas_limit = EXCLUDED.gas_limit, base_fee_per_gas = EXCLUDED.base_fee_per_gas, transaction_count = EXCLUDED.transaction_count, log_count = EXCLUDED.log_count";
+
 pub enum Bind {
-    BIGINT(i64),
-    INT(i32),
+    BIGINT(Option<i64>),
+    INT(Option<i32>),
+    SMALLINT(Option<i16>),
+    TEXT(Option<String>),
+    /// A decimal string, cast to NUMERIC by its placeholder.
+    NUMERIC(Option<String>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Block {
     pub block_number: u64,
     pub chain_id: u32,
+    pub hash: Option<String>,
+    pub parent_hash: String,
+    pub timestamp: u64,
+    pub miner: Option<String>,
+    pub gas_used: String,
+    pub gas_limit: String,
+    pub base_fee_per_gas: Option<String>,
+    pub transaction_count: u32,
+    pub log_count: u32,
+}
+
+impl Block {
+    pub fn new(block: &EthersBlock<Transaction>, chain_id: u32, log_count: u32) -> Self {
+        Self {
+            block_number: block.number.unwrap_or_default().as_u64(),
+            chain_id,
+            hash: block.hash.map(|hash| format!("0x{}", hex::encode(hash))),
+            parent_hash: format!("0x{}", hex::encode(block.parent_hash)),
+            timestamp: block.timestamp.low_u64(),
+            miner: block
+                .author
+                .map(|miner| format!("0x{}", hex::encode(miner))),
+            gas_used: block.gas_used.to_string(),
+            gas_limit: block.gas_limit.to_string(),
+            base_fee_per_gas: block.base_fee_per_gas.map(|fee| fee.to_string()),
+            transaction_count: block.transactions.len() as u32,
+            log_count,
+        }
+    }
+
+    fn binds(&self) -> [Bind; 11] {
+        [
+            Bind::BIGINT(Some(self.block_number as i64)),
+            Bind::INT(Some(self.chain_id as i32)),
+            Bind::TEXT(self.hash.clone()),
+            Bind::TEXT(Some(self.parent_hash.clone())),
+            Bind::BIGINT(Some(self.timestamp as i64)),
+            Bind::TEXT(self.miner.clone()),
+            Bind::NUMERIC(Some(self.gas_used.clone())),
+            Bind::NUMERIC(Some(self.gas_limit.clone())),
+            Bind::NUMERIC(self.base_fee_per_gas.clone()),
+            Bind::INT(Some(self.transaction_count as i32)),
+            Bind::INT(Some(self.log_count as i32)),
+        ]
+    }
 }
 
 #[derive(Debug, FromRow)]
@@ -20,12 +85,34 @@ pub struct BlockNumber {
     block_number: i64,
 }
 
+/// The first and last indexed blocks, and how many are indexed in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexedRange {
+    pub first_block: u64,
+    pub last_block: u64,
+    pub block_count: u64,
+}
+
 #[async_trait]
 pub trait BlockRepositoryTrait: Clone + Send + Sync + 'static {
     fn new(database_pool: Arc<PgPool>, chain_config: ChainConfig) -> Self;
     async fn get_indexed_blocks(&self) -> Result<Vec<u64>, sqlx::Error>;
+    async fn get_last_indexed_block(&self) -> Result<Option<u64>, sqlx::Error>;
     async fn insert_block(&self, blocks: Block) -> Result<(), sqlx::Error>;
     async fn insert_blocks_bulk(&self, blocks: &[Block]) -> Result<(), sqlx::Error>;
+    async fn get_indexed_range(&self) -> Result<Option<IndexedRange>, sqlx::Error>;
+    /// Up to `limit` ranges of blocks missing between the first and last
+    /// indexed blocks, in order.
+    async fn get_gaps(&self, limit: u64) -> Result<Vec<(u64, u64)>, sqlx::Error>;
+    /// Numbers and hashes of the indexed blocks from `from` to `to`, in order.
+    async fn get_block_hashes(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<(u64, Option<String>)>, sqlx::Error>;
+    /// Deletes the blocks above `block_number`, with their stored
+    /// transactions and archived logs, and returns how many blocks there were.
+    async fn delete_blocks_above(&self, block_number: u64) -> Result<u64, sqlx::Error>;
 }
 
 #[derive(Clone)]
@@ -58,58 +145,178 @@ impl BlockRepositoryTrait for BlockRepository {
         Ok(result)
     }
 
+    async fn get_last_indexed_block(&self) -> Result<Option<u64>, sqlx::Error> {
+        let (last,) = sqlx::query_as::<_, (Option<i64>,)>(
+            "SELECT MAX(block_number) FROM block WHERE chain_id = $1",
+        )
+        .bind(self.chain_config.id as i32)
+        .fetch_one(&*self.database_pool)
+        .await?;
+
+        Ok(last.map(|block_number| block_number as u64))
+    }
+
     async fn insert_block(&self, block: Block) -> Result<(), sqlx::Error> {
         let start_time = Instant::now();
+        let block_number = block.block_number;
 
-        let query = "INSERT INTO block (block_number, chain_id) VALUES ($1, $2)";
-
-        sqlx::query(query)
-            .bind(block.block_number as i64)
-            .bind(block.chain_id as i32)
-            .execute(&*self.database_pool)
-            .await?;
+        self.insert_blocks_bulk(&[block]).await?;
 
         let end_time = Instant::now();
         let duration = end_time.duration_since(start_time);
 
-        tracing::debug!("Block {:?} inserted in {:?}.", block.block_number, duration);
+        tracing::debug!("Block {:?} inserted in {:?}.", block_number, duration);
         Ok(())
     }
 
+    /// Upserts blocks keyed by `(chain_id, block_number)`, so re-indexing a
+    /// block refreshes its row instead of duplicating it.
     async fn insert_blocks_bulk(&self, blocks: &[Block]) -> Result<(), sqlx::Error> {
         if blocks.is_empty() {
             return Ok(());
         }
 
-        let mut query =
-            String::from("INSERT INTO block (block_number, chain_id) VALUES ");
-
-        let mut binds: Vec<Bind> = vec![];
-        for (index, block) in blocks.iter().enumerate() {
-            if index > 0 {
-                query.push_str(", ");
+        // A statement may not upsert the same key twice, keep the last copy.
+        let mut positions: HashMap<(u32, u64), usize> = HashMap::new();
+        let mut unique_blocks: Vec<&Block> = Vec::with_capacity(blocks.len());
+        for block in blocks {
+            match positions.entry((block.chain_id, block.block_number)) {
+                Entry::Occupied(entry) => unique_blocks[*entry.get()] = block,
+                Entry::Vacant(entry) => {
+                    entry.insert(unique_blocks.len());
+                    unique_blocks.push(block);
+                }
             }
-            let placeholder_index = index * 2 + 1;
-            query.push_str(&format!(
-                "(${}, ${})",
-                placeholder_index,
-                placeholder_index + 1,
-            ));
-            binds.push(Bind::BIGINT(block.block_number as i64));
-            binds.push(Bind::INT(block.chain_id as i32));
         }
 
-        let mut query_builder = sqlx::query(&query);
+        for chunk in unique_blocks.chunks(BULK_INSERT_CHUNK_SIZE) {
+            let mut query = format!("INSERT INTO block ({}) VALUES ", BLOCK_COLUMNS);
 
-        for bind in binds.iter() {
-            match bind {
-                Bind::BIGINT(i64_data) => query_builder = query_builder.bind(i64_data),
-                Bind::INT(i32_data) => query_builder = query_builder.bind(i32_data),
+            let mut binds: Vec<Bind> = vec![];
+            for (index, block) in chunk.iter().enumerate() {
+                if index > 0 {
+                    query.push_str(", ");
+                }
+                let placeholder_index = index * 11 + 1;
+                query.push_str(&format!(
+                    "(${}, ${}, ${}, ${}, to_timestamp(${}), ${}, ${}::NUMERIC, ${}::NUMERIC, ${}::NUMERIC, ${}, ${})",
+                    placeholder_index,
+                    placeholder_index + 1,
+                    placeholder_index + 2,
+                    placeholder_index + 3,
+                    placeholder_index + 4,
+                    placeholder_index + 5,
+                    placeholder_index + 6,
+                    placeholder_index + 7,
+                    placeholder_index + 8,
+                    placeholder_index + 9,
+                    placeholder_index + 10,
+                ));
+                binds.extend(block.binds());
+            }
+            query.push_str(BLOCK_UPSERT);
+
+            let mut query_builder = sqlx::query(&query);
+
+            for bind in binds.iter() {
+                match bind {
+                    Bind::BIGINT(i64_data) => {
+                        query_builder = query_builder.bind(i64_data)
+                    }
+                    Bind::INT(i32_data) => query_builder = query_builder.bind(i32_data),
+                    Bind::SMALLINT(i16_data) => {
+                        query_builder = query_builder.bind(i16_data)
+                    }
+                    Bind::TEXT(text_data) | Bind::NUMERIC(text_data) => {
+                        query_builder = query_builder.bind(text_data)
+                    }
+                }
             }
-        }
 
-        query_builder.execute(&*self.database_pool).await?;
+            query_builder.execute(&*self.database_pool).await?;
+        }
 
         Ok(())
     }
+
+    async fn get_indexed_range(&self) -> Result<Option<IndexedRange>, sqlx::Error> {
+        let (first, last, count) = sqlx::query_as::<_, (Option<i64>, Option<i64>, i64)>(
+            "SELECT MIN(block_number), MAX(block_number), COUNT(*) FROM block WHERE chain_id = $1",
+        )
+        .bind(self.chain_config.id as i32)
+        .fetch_one(&*self.database_pool)
+        .await?;
+
+        Ok(first.zip(last).map(|(first, last)| IndexedRange {
+            first_block: first as u64,
+            last_block: last as u64,
+            block_count: count as u64,
+        }))
+    }
+
+    async fn get_gaps(&self, limit: u64) -> Result<Vec<(u64, u64)>, sqlx::Error> {
+        let gaps = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT block_number + 1, next_block - 1 FROM ( \
+                SELECT block_number, LEAD(block_number) OVER (ORDER BY block_number) AS next_block \
+                FROM block WHERE chain_id = $1 \
+             ) AS blocks WHERE next_block > block_number + 1 ORDER BY block_number LIMIT $2",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(limit as i64)
+        .fetch_all(&*self.database_pool)
+        .await?;
+
+        Ok(gaps
+            .into_iter()
+            .map(|(first, last)| (first as u64, last as u64))
+            .collect())
+    }
+
+    async fn get_block_hashes(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<(u64, Option<String>)>, sqlx::Error> {
+        let blocks = sqlx::query_as::<_, (i64, Option<String>)>(
+            "SELECT block_number, hash FROM block WHERE chain_id = $1 AND block_number BETWEEN $2 AND $3 ORDER BY block_number",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(from.min(i64::MAX as u64) as i64)
+        .bind(to.min(i64::MAX as u64) as i64)
+        .fetch_all(&*self.database_pool)
+        .await?;
+
+        Ok(blocks
+            .into_iter()
+            .map(|(block_number, hash)| (block_number as u64, hash))
+            .collect())
+    }
+
+    async fn delete_blocks_above(&self, block_number: u64) -> Result<u64, sqlx::Error> {
+        let chain_id = self.chain_config.id as i32;
+        let block_number = block_number.min(i64::MAX as u64) as i64;
+        let mut transaction = self.database_pool.begin().await?;
+
+        // Rows of the deleted blocks would otherwise sit next to those of the
+        // blocks indexed again.
+        for table in ["transaction", "log"] {
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE chain_id = $1 AND block_number > $2",
+                table
+            ))
+            .bind(chain_id)
+            .bind(block_number)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        let result =
+            sqlx::query("DELETE FROM block WHERE chain_id = $1 AND block_number > $2")
+                .bind(chain_id)
+                .bind(block_number)
+                .execute(&mut *transaction)
+                .await?;
+
+        transaction.commit().await?;
+        Ok(result.rows_affected())
+    }
 }
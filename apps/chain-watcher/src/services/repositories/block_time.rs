@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::types::ChainConfig;
+use sqlx::PgPool;
+
+#[async_trait]
+pub trait BlockTimeRepositoryTrait: Clone + Send + Sync + 'static {
+    fn new(database_pool: Arc<PgPool>, chain_config: ChainConfig) -> Self;
+    /// The cached first block mined at or after `timestamp`.
+    async fn get_first_block_at(
+        &self,
+        timestamp: u64,
+    ) -> Result<Option<u64>, sqlx::Error>;
+    async fn save_first_block_at(
+        &self,
+        timestamp: u64,
+        block_number: u64,
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[derive(Clone)]
+pub struct BlockTimeRepository {
+    pub database_pool: Arc<PgPool>,
+    pub chain_config: ChainConfig,
+}
+
+#[async_trait]
+impl BlockTimeRepositoryTrait for BlockTimeRepository {
+    fn new(database_pool: Arc<PgPool>, chain_config: ChainConfig) -> Self {
+        Self {
+            database_pool,
+            chain_config,
+        }
+    }
+
+    async fn get_first_block_at(
+        &self,
+        timestamp: u64,
+    ) -> Result<Option<u64>, sqlx::Error> {
+        let block = sqlx::query_as::<_, (i64,)>(
+            "SELECT block_number FROM block_time WHERE chain_id = $1 AND timestamp = $2",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(timestamp.min(i64::MAX as u64) as i64)
+        .fetch_optional(&*self.database_pool)
+        .await?;
+
+        Ok(block.map(|(block_number,)| block_number as u64))
+    }
+
+    async fn save_first_block_at(
+        &self,
+        timestamp: u64,
+        block_number: u64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO block_time (chain_id, timestamp, block_number) VALUES ($1, $2, $3) ON CONFLICT (chain_id, timestamp) DO NOTHING",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(timestamp.min(i64::MAX as u64) as i64)
+        .bind(block_number as i64)
+        .execute(&*self.database_pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::types::ChainConfig;
+use sqlx::PgPool;
+
+#[async_trait]
+pub trait ChainRepositoryTrait: Clone + Send + Sync + 'static {
+    fn new(database_pool: Arc<PgPool>, chain_config: ChainConfig) -> Self;
+    async fn record_genesis_hash(
+        &self,
+        genesis_hash: &str,
+    ) -> Result<String, sqlx::Error>;
+}
+
+#[derive(Clone)]
+pub struct ChainRepository {
+    pub database_pool: Arc<PgPool>,
+    pub chain_config: ChainConfig,
+}
+
+#[async_trait]
+impl ChainRepositoryTrait for ChainRepository {
+    fn new(database_pool: Arc<PgPool>, chain_config: ChainConfig) -> Self {
+        Self {
+            database_pool,
+            chain_config,
+        }
+    }
+
+    /// Stores the genesis hash the first time a chain is synced and returns
+    /// the hash recorded for the chain, which may differ from `genesis_hash`.
+    async fn record_genesis_hash(
+        &self,
+        genesis_hash: &str,
+    ) -> Result<String, sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO chain (chain_id, name, genesis_hash) VALUES ($1, $2, $3) ON CONFLICT (chain_id) DO NOTHING",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(&self.chain_config.name)
+        .bind(genesis_hash)
+        .execute(&*self.database_pool)
+        .await?;
+
+        let (recorded,) = sqlx::query_as::<_, (String,)>(
+            "SELECT genesis_hash FROM chain WHERE chain_id = $1",
+        )
+        .bind(self.chain_config.id as i32)
+        .fetch_one(&*self.database_pool)
+        .await?;
+
+        Ok(recorded)
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::types::{ChainConfig, SyncLane};
+use sqlx::PgPool;
+
+/// How far a lane got: every block from `start_block` to `last_block` is
+/// indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncCheckpoint {
+    pub start_block: u64,
+    pub last_block: Option<u64>,
+}
+
+#[async_trait]
+pub trait CheckpointRepositoryTrait: Clone + Send + Sync + 'static {
+    fn new(database_pool: Arc<PgPool>, chain_config: ChainConfig) -> Self;
+    async fn get_checkpoint(
+        &self,
+        lane: SyncLane,
+    ) -> Result<Option<SyncCheckpoint>, sqlx::Error>;
+    async fn save_checkpoint(
+        &self,
+        lane: SyncLane,
+        checkpoint: SyncCheckpoint,
+    ) -> Result<(), sqlx::Error>;
+    /// Moves every lane's progress back to `block_number` at most. A lane
+    /// that starts above it resumes from its start block.
+    async fn rewind_checkpoints(&self, block_number: u64) -> Result<(), sqlx::Error>;
+}
+
+#[derive(Clone)]
+pub struct CheckpointRepository {
+    pub database_pool: Arc<PgPool>,
+    pub chain_config: ChainConfig,
+}
+
+#[async_trait]
+impl CheckpointRepositoryTrait for CheckpointRepository {
+    fn new(database_pool: Arc<PgPool>, chain_config: ChainConfig) -> Self {
+        Self {
+            database_pool,
+            chain_config,
+        }
+    }
+
+    async fn get_checkpoint(
+        &self,
+        lane: SyncLane,
+    ) -> Result<Option<SyncCheckpoint>, sqlx::Error> {
+        let checkpoint = sqlx::query_as::<_, (i64, Option<i64>)>(
+            "SELECT start_block, last_block FROM sync_checkpoint WHERE chain_id = $1 AND lane = $2",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(lane.to_string())
+        .fetch_optional(&*self.database_pool)
+        .await?;
+
+        Ok(checkpoint.map(|(start_block, last_block)| SyncCheckpoint {
+            start_block: start_block as u64,
+            last_block: last_block.map(|block| block as u64),
+        }))
+    }
+
+    /// Never moves `last_block` backwards for the same start block, so a
+    /// late write from a deposed leader cannot undo its successor's progress.
+    async fn save_checkpoint(
+        &self,
+        lane: SyncLane,
+        checkpoint: SyncCheckpoint,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO sync_checkpoint (chain_id, lane, start_block, last_block) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (chain_id, lane) DO UPDATE SET start_block = EXCLUDED.start_block, last_block = CASE WHEN sync_checkpoint.start_block = EXCLUDED.start_block THEN GREATEST(sync_checkpoint.last_block, EXCLUDED.last_block) ELSE EXCLUDED.last_block END, updated_at = now()",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(lane.to_string())
+        .bind(checkpoint.start_block as i64)
+        .bind(checkpoint.last_block.map(|block| block as i64))
+        .execute(&*self.database_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn rewind_checkpoints(&self, block_number: u64) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE sync_checkpoint SET last_block = CASE WHEN start_block > $2 THEN NULL ELSE LEAST(last_block, $2) END, updated_at = now() \
+             WHERE chain_id = $1 AND last_block > $2",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(block_number.min(i64::MAX as u64) as i64)
+        .execute(&*self.database_pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::types::ChainConfig;
+use sqlx::PgPool;
+
+#[async_trait]
+pub trait LeaderRepositoryTrait: Clone + Send + Sync + 'static {
+    fn new(
+        database_pool: Arc<PgPool>,
+        chain_config: ChainConfig,
+        instance_id: String,
+        lease_ttl: u64,
+    ) -> Self;
+    /// Seconds leadership lasts without being renewed.
+    fn lease_ttl(&self) -> u64;
+    async fn try_acquire(&self) -> Result<Option<u64>, sqlx::Error>;
+    async fn renew(&self, fencing_token: u64) -> Result<bool, sqlx::Error>;
+    async fn release(&self, fencing_token: u64) -> Result<(), sqlx::Error>;
+}
+
+#[derive(Clone)]
+pub struct LeaderRepository {
+    pub database_pool: Arc<PgPool>,
+    pub chain_config: ChainConfig,
+    pub instance_id: String,
+    pub lease_ttl: u64,
+}
+
+#[async_trait]
+impl LeaderRepositoryTrait for LeaderRepository {
+    fn new(
+        database_pool: Arc<PgPool>,
+        chain_config: ChainConfig,
+        instance_id: String,
+        lease_ttl: u64,
+    ) -> Self {
+        Self {
+            database_pool,
+            chain_config,
+            instance_id,
+            lease_ttl,
+        }
+    }
+
+    fn lease_ttl(&self) -> u64 {
+        self.lease_ttl
+    }
+
+    /// Takes leadership if nobody holds it or the leader's lease expired, and
+    /// returns the new fencing token, which is greater than any issued before.
+    async fn try_acquire(&self) -> Result<Option<u64>, sqlx::Error> {
+        let acquired = sqlx::query_as::<_, (i64,)>(
+            "INSERT INTO chain_leader (chain_id, owner, fencing_token, expires_at) VALUES ($1, $2, 1, now() + make_interval(secs => $3)) \
+             ON CONFLICT (chain_id) DO UPDATE SET owner = EXCLUDED.owner, fencing_token = chain_leader.fencing_token + 1, expires_at = EXCLUDED.expires_at WHERE chain_leader.expires_at < now() \
+             RETURNING fencing_token",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(&self.instance_id)
+        .bind(self.lease_ttl as f64)
+        .fetch_optional(&*self.database_pool)
+        .await?;
+
+        Ok(acquired.map(|(fencing_token,)| fencing_token as u64))
+    }
+
+    /// Extends leadership by `lease_ttl` seconds. Returns false if another
+    /// instance took over.
+    async fn renew(&self, fencing_token: u64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE chain_leader SET expires_at = now() + make_interval(secs => $3) WHERE chain_id = $1 AND fencing_token = $2",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(fencing_token as i64)
+        .bind(self.lease_ttl as f64)
+        .execute(&*self.database_pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Expires leadership right away, so a standby need not wait for the TTL.
+    async fn release(&self, fencing_token: u64) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE chain_leader SET expires_at = now() WHERE chain_id = $1 AND fencing_token = $2",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(fencing_token as i64)
+        .execute(&*self.database_pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::types::ChainConfig;
+use sqlx::PgPool;
+
+/// A block range held by this instance until `lease_ttl` seconds pass without
+/// a heartbeat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRangeLease {
+    pub start_block: u64,
+    pub end_block: u64,
+    /// Whether the range was taken over from an instance whose lease expired.
+    pub reclaimed: bool,
+}
+
+#[async_trait]
+pub trait LeaseRepositoryTrait: Clone + Send + Sync + 'static {
+    fn new(
+        database_pool: Arc<PgPool>,
+        chain_config: ChainConfig,
+        instance_id: String,
+        lease_ttl: u64,
+    ) -> Self;
+    /// Seconds a claim lasts without being renewed.
+    fn lease_ttl(&self) -> u64;
+    async fn create_ranges(
+        &self,
+        start_block: u64,
+        end_block: u64,
+        range_size: u64,
+    ) -> Result<(), sqlx::Error>;
+    async fn claim_range(&self) -> Result<Option<BlockRangeLease>, sqlx::Error>;
+    async fn renew_lease(&self, lease: &BlockRangeLease) -> Result<bool, sqlx::Error>;
+    async fn complete_range(&self, lease: &BlockRangeLease) -> Result<bool, sqlx::Error>;
+    async fn remaining_ranges(&self) -> Result<u64, sqlx::Error>;
+}
+
+#[derive(Clone)]
+pub struct LeaseRepository {
+    pub database_pool: Arc<PgPool>,
+    pub chain_config: ChainConfig,
+    pub instance_id: String,
+    pub lease_ttl: u64,
+}
+
+#[async_trait]
+impl LeaseRepositoryTrait for LeaseRepository {
+    fn new(
+        database_pool: Arc<PgPool>,
+        chain_config: ChainConfig,
+        instance_id: String,
+        lease_ttl: u64,
+    ) -> Self {
+        Self {
+            database_pool,
+            chain_config,
+            instance_id,
+            lease_ttl,
+        }
+    }
+
+    fn lease_ttl(&self) -> u64 {
+        self.lease_ttl
+    }
+
+    /// Splits `start_block..=end_block` into ranges of `range_size` blocks.
+    /// Ranges that already exist are kept, so every instance can call this
+    /// with the same arguments.
+    async fn create_ranges(
+        &self,
+        start_block: u64,
+        end_block: u64,
+        range_size: u64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO block_range_lease (chain_id, start_block, end_block) SELECT $1, range_start, LEAST(range_start + $4 - 1, $3) FROM generate_series($2::BIGINT, $3::BIGINT, $4::BIGINT) AS range_start ON CONFLICT (chain_id, start_block) DO NOTHING",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(start_block as i64)
+        .bind(end_block as i64)
+        .bind(range_size as i64)
+        .execute(&*self.database_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Claims the lowest pending range, or one whose lease expired. Rows
+    /// locked by other instances' claims are skipped rather than waited on.
+    async fn claim_range(&self) -> Result<Option<BlockRangeLease>, sqlx::Error> {
+        let claimed = sqlx::query_as::<_, (i64, i64, bool)>(
+            "WITH next AS (SELECT chain_id, start_block, status = 'claimed' AS reclaimed FROM block_range_lease WHERE chain_id = $1 AND (status = 'pending' OR (status = 'claimed' AND expires_at < now())) ORDER BY start_block LIMIT 1 FOR UPDATE SKIP LOCKED) \
+             UPDATE block_range_lease AS lease SET status = 'claimed', owner = $2, expires_at = now() + make_interval(secs => $3) FROM next WHERE lease.chain_id = next.chain_id AND lease.start_block = next.start_block \
+             RETURNING lease.start_block, lease.end_block, next.reclaimed",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(&self.instance_id)
+        .bind(self.lease_ttl as f64)
+        .fetch_optional(&*self.database_pool)
+        .await?;
+
+        Ok(
+            claimed.map(|(start_block, end_block, reclaimed)| BlockRangeLease {
+                start_block: start_block as u64,
+                end_block: end_block as u64,
+                reclaimed,
+            }),
+        )
+    }
+
+    /// Extends the lease by `lease_ttl` seconds. Returns false if the range is
+    /// no longer held by this instance.
+    async fn renew_lease(&self, lease: &BlockRangeLease) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE block_range_lease SET expires_at = now() + make_interval(secs => $4) WHERE chain_id = $1 AND start_block = $2 AND owner = $3 AND status = 'claimed'",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(lease.start_block as i64)
+        .bind(&self.instance_id)
+        .bind(self.lease_ttl as f64)
+        .execute(&*self.database_pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Marks the range done. Returns false if the range is no longer held by
+    /// this instance.
+    async fn complete_range(&self, lease: &BlockRangeLease) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE block_range_lease SET status = 'done', expires_at = NULL WHERE chain_id = $1 AND start_block = $2 AND owner = $3 AND status = 'claimed'",
+        )
+        .bind(self.chain_config.id as i32)
+        .bind(lease.start_block as i64)
+        .bind(&self.instance_id)
+        .execute(&*self.database_pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Ranges not yet done, including those claimed by other instances.
+    async fn remaining_ranges(&self) -> Result<u64, sqlx::Error> {
+        let (remaining,) = sqlx::query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM block_range_lease WHERE chain_id = $1 AND status <> 'done'",
+        )
+        .bind(self.chain_config.id as i32)
+        .fetch_one(&*self.database_pool)
+        .await?;
+
+        Ok(remaining as u64)
+    }
+}
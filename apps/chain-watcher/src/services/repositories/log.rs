@@ -0,0 +1,153 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use async_trait::async_trait;
+use common::types::ChainConfig;
+use ethers::{types::Log, utils::hex};
+use sqlx::PgPool;
+
+/// Postgres error code raised when two writers create the same partition.
+const DUPLICATE_TABLE: &str = "42P07";
+
+/// Blocks covered by each partition of `log`. The partitions are shared by
+/// every chain, so their bounds must not depend on the chain or the
+/// configuration.
+pub const LOG_PARTITION_SIZE: u64 = 1_000_000;
+
+#[async_trait]
+pub trait LogRepositoryTrait: Clone + Send + Sync + 'static {
+    fn new(database_pool: Arc<PgPool>, chain_config: ChainConfig) -> Self;
+    async fn archive_logs(&self, logs: &[Log]) -> Result<(), sqlx::Error>;
+}
+
+#[derive(Clone)]
+pub struct LogRepository {
+    pub database_pool: Arc<PgPool>,
+    pub chain_config: ChainConfig,
+    known_partitions: Arc<Mutex<HashSet<u64>>>,
+}
+
+impl LogRepository {
+    fn partition_start(&self, block_number: u64) -> u64 {
+        block_number - block_number % LOG_PARTITION_SIZE
+    }
+
+    async fn ensure_partitions(&self, logs: &[Log]) -> Result<(), sqlx::Error> {
+        let starts: BTreeSet<u64> = logs
+            .iter()
+            .map(|log| {
+                self.partition_start(log.block_number.unwrap_or_default().as_u64())
+            })
+            .collect();
+
+        for start in starts {
+            if self.known_partitions.lock().unwrap().contains(&start) {
+                continue;
+            }
+
+            let statement = format!(
+                "CREATE TABLE IF NOT EXISTS log_p{} PARTITION OF log FOR VALUES FROM ({}) TO ({})",
+                start,
+                start,
+                start + LOG_PARTITION_SIZE
+            );
+            match sqlx::query(&statement).execute(&*self.database_pool).await {
+                Ok(_) => {}
+                Err(sqlx::Error::Database(error))
+                    if error.code().as_deref() == Some(DUPLICATE_TABLE) => {}
+                Err(error) => return Err(error),
+            }
+
+            self.known_partitions.lock().unwrap().insert(start);
+        }
+
+        Ok(())
+    }
+
+    /// Renders logs in `COPY ... FROM STDIN` text format.
+    fn copy_rows(&self, logs: &[Log]) -> String {
+        let mut rows = String::new();
+        for log in logs {
+            let topics: Vec<String> = log
+                .topics
+                .iter()
+                .map(|topic| format!("0x{}", hex::encode(topic)))
+                .collect();
+            let _ = writeln!(
+                rows,
+                "{}\t{}\t{}\t{}\t{}\t{}\t0x{}\t{{{}}}\t0x{}\t{}",
+                self.chain_config.id,
+                log.block_number.unwrap_or_default().as_u64(),
+                log.block_hash
+                    .map_or("\\N".to_string(), |hash| format!("0x{}", hex::encode(hash))),
+                log.transaction_hash
+                    .map_or("\\N".to_string(), |hash| format!("0x{}", hex::encode(hash))),
+                log.transaction_index
+                    .map_or("\\N".to_string(), |index| index.as_u64().to_string()),
+                log.log_index.unwrap_or_default().low_u64(),
+                hex::encode(log.address),
+                topics.join(","),
+                hex::encode(&log.data),
+                log.removed.unwrap_or(false),
+            );
+        }
+        rows
+    }
+}
+
+#[async_trait]
+impl LogRepositoryTrait for LogRepository {
+    fn new(database_pool: Arc<PgPool>, chain_config: ChainConfig) -> Self {
+        Self {
+            database_pool,
+            chain_config,
+            known_partitions: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Copies logs into a staging table and moves them into `log`, replacing
+    /// rows already archived so re-indexed blocks are not duplicated.
+    async fn archive_logs(&self, logs: &[Log]) -> Result<(), sqlx::Error> {
+        if logs.is_empty() {
+            return Ok(());
+        }
+
+        let start_time = Instant::now();
+
+        self.ensure_partitions(logs).await?;
+
+        let mut transaction = self.database_pool.begin().await?;
+
+        sqlx::query(
+            "CREATE TEMPORARY TABLE log_staging (LIKE log INCLUDING DEFAULTS) ON COMMIT DROP",
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        let mut copy = transaction
+            .copy_in_raw(
+                "COPY log_staging (chain_id, block_number, block_hash, tx_hash, tx_index, log_index, address, topics, data, removed) FROM STDIN",
+            )
+            .await?;
+        copy.send(self.copy_rows(logs).into_bytes()).await?;
+        copy.finish().await?;
+
+        sqlx::query(
+            "INSERT INTO log SELECT * FROM log_staging ON CONFLICT (chain_id, block_number, log_index) DO UPDATE SET block_hash = EXCLUDED.block_hash, tx_hash = EXCLUDED.tx_hash, tx_index = EXCLUDED.tx_index, address = EXCLUDED.address, topics = EXCLUDED.topics, data = EXCLUDED.data, removed = EXCLUDED.removed",
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+
+        let end_time = Instant::now();
+        let duration = end_time.duration_since(start_time);
+
+        tracing::debug!("{} logs archived in {:?}.", logs.len(), duration);
+        Ok(())
+    }
+}
@@ -1 +1,8 @@
 pub mod block;
+pub mod block_time;
+pub mod chain;
+pub mod checkpoint;
+pub mod leader;
+pub mod lease;
+pub mod log;
+pub mod transaction;
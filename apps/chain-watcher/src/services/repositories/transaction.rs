@@ -0,0 +1,191 @@
+use std::{sync::Arc, time::Instant};
+
+use async_trait::async_trait;
+use common::types::{ChainConfig, SummaryTransaction};
+use ethers::{types::TransactionReceipt, utils::hex};
+use sqlx::PgPool;
+
+use super::block::Bind;
+
+/// Rows per statement in `insert_transactions`, keeping the bind count well
+/// below Postgres' limit of 65535 parameters.
+const BULK_INSERT_CHUNK_SIZE: usize = 2000;
+
+const TRANSACTION_COLUMN_COUNT: usize = 15;
+
+const TRANSACTION_COLUMNS: &str = "chain_id, block_number, block_hash, hash, tx_index, \"from\", \"to\", value, nonce, input_selector, gas_used, effective_gas_price, status, contract_address, \"type\"";
+
+const TRANSACTION_UPSERT: &str = " ON CONFLICT (chain_id, hash) DO UPDATE SET block_number = EXCLUDED.block_number, block_hash = EXCLUDED.block_hash, tx_index = EXCLUDED.tx_index, \"from\" = EXCLUDED.\"from\", \"to\" = EXCLUDED.\"to\", value = EXCLUDED.value, nonce = EXCLUDED.nonce, input_selector = EXCLUDED.input_selector, gas_used = EXCLUDED.gas_used, effective_gas_price = EXCLUDED.effective_gas_price, status = EXCLUDED.status, contract_address = EXCLUDED.contract_address, \"type\" = EXCLUDED.\"type\"";
+
+#[derive(Debug, Clone)]
+pub struct TransactionRecord {
+    pub chain_id: u32,
+    pub block_number: u64,
+    pub block_hash: Option<String>,
+    pub hash: String,
+    pub tx_index: u64,
+    pub from: String,
+    pub to: Option<String>,
+    pub value: String,
+    pub nonce: String,
+    pub input_selector: Option<String>,
+    pub gas_used: Option<String>,
+    pub effective_gas_price: Option<String>,
+    pub status: Option<u64>,
+    pub contract_address: Option<String>,
+    pub transaction_type: Option<u64>,
+}
+
+impl TransactionRecord {
+    /// Combines the transaction, for what the sender signed, with its receipt,
+    /// for what happened on execution. Hashes and addresses come from the
+    /// receipt, which is the canonical record once mined.
+    pub fn new(
+        transaction: &SummaryTransaction,
+        receipt: &TransactionReceipt,
+        chain_id: u32,
+    ) -> Self {
+        Self {
+            chain_id,
+            block_number: receipt
+                .block_number
+                .map(|number| number.as_u64())
+                .or(transaction.block_number)
+                .unwrap_or_default(),
+            block_hash: receipt
+                .block_hash
+                .map(|hash| format!("0x{}", hex::encode(hash))),
+            hash: format!("0x{}", hex::encode(receipt.transaction_hash)),
+            tx_index: receipt.transaction_index.as_u64(),
+            from: format!("0x{}", hex::encode(receipt.from)),
+            to: receipt.to.map(|to| format!("0x{}", hex::encode(to))),
+            value: transaction.value.to_string(),
+            nonce: transaction.nonce.to_string(),
+            input_selector: transaction
+                .input
+                .get(..4)
+                .map(|selector| format!("0x{}", hex::encode(selector))),
+            gas_used: receipt.gas_used.map(|gas| gas.to_string()),
+            effective_gas_price: receipt
+                .effective_gas_price
+                .map(|price| price.to_string()),
+            status: receipt.status.map(|status| status.as_u64()),
+            contract_address: receipt
+                .contract_address
+                .map(|address| format!("0x{}", hex::encode(address))),
+            transaction_type: receipt.transaction_type.map(|kind| kind.as_u64()),
+        }
+    }
+
+    fn binds(&self) -> [Bind; TRANSACTION_COLUMN_COUNT] {
+        [
+            Bind::INT(Some(self.chain_id as i32)),
+            Bind::BIGINT(Some(self.block_number as i64)),
+            Bind::TEXT(self.block_hash.clone()),
+            Bind::TEXT(Some(self.hash.clone())),
+            Bind::BIGINT(Some(self.tx_index as i64)),
+            Bind::TEXT(Some(self.from.clone())),
+            Bind::TEXT(self.to.clone()),
+            Bind::NUMERIC(Some(self.value.clone())),
+            Bind::NUMERIC(Some(self.nonce.clone())),
+            Bind::TEXT(self.input_selector.clone()),
+            Bind::NUMERIC(self.gas_used.clone()),
+            Bind::NUMERIC(self.effective_gas_price.clone()),
+            Bind::SMALLINT(self.status.map(|status| status as i16)),
+            Bind::TEXT(self.contract_address.clone()),
+            Bind::SMALLINT(self.transaction_type.map(|kind| kind as i16)),
+        ]
+    }
+}
+
+#[async_trait]
+pub trait TransactionRepositoryTrait: Clone + Send + Sync + 'static {
+    fn new(database_pool: Arc<PgPool>, chain_config: ChainConfig) -> Self;
+    async fn insert_transactions(
+        &self,
+        transactions: &[TransactionRecord],
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[derive(Clone)]
+pub struct TransactionRepository {
+    pub database_pool: Arc<PgPool>,
+    pub chain_config: ChainConfig,
+}
+
+#[async_trait]
+impl TransactionRepositoryTrait for TransactionRepository {
+    fn new(database_pool: Arc<PgPool>, chain_config: ChainConfig) -> Self {
+        Self {
+            database_pool,
+            chain_config,
+        }
+    }
+
+    async fn insert_transactions(
+        &self,
+        transactions: &[TransactionRecord],
+    ) -> Result<(), sqlx::Error> {
+        if transactions.is_empty() {
+            return Ok(());
+        }
+
+        let start_time = Instant::now();
+
+        for chunk in transactions.chunks(BULK_INSERT_CHUNK_SIZE) {
+            let mut query =
+                format!("INSERT INTO transaction ({}) VALUES ", TRANSACTION_COLUMNS);
+
+            let mut binds: Vec<Bind> = vec![];
+            for (index, transaction) in chunk.iter().enumerate() {
+                if index > 0 {
+                    query.push_str(", ");
+                }
+                let row_binds = transaction.binds();
+                let placeholders: Vec<String> = row_binds
+                    .iter()
+                    .enumerate()
+                    .map(|(column, bind)| {
+                        let position = index * TRANSACTION_COLUMN_COUNT + column + 1;
+                        match bind {
+                            Bind::NUMERIC(_) => format!("${}::NUMERIC", position),
+                            _ => format!("${}", position),
+                        }
+                    })
+                    .collect();
+                query.push_str(&format!("({})", placeholders.join(", ")));
+                binds.extend(row_binds);
+            }
+            query.push_str(TRANSACTION_UPSERT);
+
+            let mut query_builder = sqlx::query(&query);
+
+            for bind in binds.iter() {
+                match bind {
+                    Bind::BIGINT(i64_data) => {
+                        query_builder = query_builder.bind(i64_data)
+                    }
+                    Bind::INT(i32_data) => query_builder = query_builder.bind(i32_data),
+                    Bind::SMALLINT(i16_data) => {
+                        query_builder = query_builder.bind(i16_data)
+                    }
+                    Bind::TEXT(text_data) | Bind::NUMERIC(text_data) => {
+                        query_builder = query_builder.bind(text_data)
+                    }
+                }
+            }
+
+            query_builder.execute(&*self.database_pool).await?;
+        }
+
+        let end_time = Instant::now();
+        let duration = end_time.duration_since(start_time);
+
+        tracing::debug!(
+            "{} transactions inserted in {:?}.",
+            transactions.len(),
+            duration
+        );
+        Ok(())
+    }
+}
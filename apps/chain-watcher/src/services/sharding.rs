@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use crate::clients::{
+    blockchain_client::BlockchainClientTrait, redis_client::RedisClientTrait,
+};
+
+use super::{
+    repositories::{
+        block::BlockRepositoryTrait,
+        lease::{BlockRangeLease, LeaseRepositoryTrait},
+        log::LogRepositoryTrait,
+        transaction::TransactionRepositoryTrait,
+    },
+    sync::{ChainSynchronizer, SyncError},
+};
+
+/// How long to wait before claiming again while every remaining range is
+/// leased by other instances.
+const CLAIM_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Backfills `start_block..=end_block` together with any other instance
+/// sharing the lease table, one claimed range at a time, until every range
+/// is done.
+pub async fn run_sharded_backfill<B, R, E, T, L, S>(
+    synchronizer: &ChainSynchronizer<B, R, E, T, L>,
+    lease_repository: &S,
+    start_block: u64,
+    end_block: u64,
+    range_size: u64,
+) -> Result<(), SyncError>
+where
+    B: BlockchainClientTrait,
+    R: RedisClientTrait,
+    E: BlockRepositoryTrait,
+    T: TransactionRepositoryTrait,
+    L: LogRepositoryTrait,
+    S: LeaseRepositoryTrait,
+{
+    lease_repository
+        .create_ranges(start_block, end_block, range_size)
+        .await?;
+
+    loop {
+        let lease = match lease_repository.claim_range().await? {
+            Some(lease) => lease,
+            None => {
+                let remaining = lease_repository.remaining_ranges().await?;
+                if remaining == 0 {
+                    return Ok(());
+                }
+                tracing::info!(
+                    "{} ranges are leased by other instances. Retrying in {:?}.",
+                    remaining,
+                    CLAIM_RETRY_DELAY
+                );
+                tokio::time::sleep(CLAIM_RETRY_DELAY).await;
+                continue;
+            }
+        };
+
+        if lease.reclaimed {
+            tracing::warn!(
+                "Reclaimed expired lease on blocks {} to {}",
+                lease.start_block,
+                lease.end_block
+            );
+        }
+        tracing::info!(
+            "Indexing leased range from block {} to block {}",
+            lease.start_block,
+            lease.end_block
+        );
+
+        // Refreshes the chain head, so finality is judged against it.
+        synchronizer.chain_head().await?;
+
+        let missing = tokio::select! {
+            result = index_range(synchronizer, &lease) => result?,
+            _ = hold_lease(lease_repository, &lease) => {
+                tracing::warn!(
+                    "Lost lease on blocks {} to {}. Abandoning the range.",
+                    lease.start_block,
+                    lease.end_block
+                );
+                continue;
+            }
+        };
+        if !missing.is_empty() {
+            // The lease is no longer renewed, so the range is claimed again
+            // once it expires.
+            tracing::warn!(
+                "{} blocks of blocks {} to {} could not be indexed, from block {}. Leaving the range to be reclaimed.",
+                missing.len(),
+                lease.start_block,
+                lease.end_block,
+                missing[0]
+            );
+            continue;
+        }
+
+        if !lease_repository.complete_range(&lease).await? {
+            tracing::warn!(
+                "Lease on blocks {} to {} was taken over before it completed.",
+                lease.start_block,
+                lease.end_block
+            );
+        }
+    }
+}
+
+/// Syncs the leased range, then retries the blocks it skipped, since this
+/// mode has no gap fill. Returns the blocks still missing.
+async fn index_range<B, R, E, T, L>(
+    synchronizer: &ChainSynchronizer<B, R, E, T, L>,
+    lease: &BlockRangeLease,
+) -> Result<Vec<u64>, SyncError>
+where
+    B: BlockchainClientTrait,
+    R: RedisClientTrait,
+    E: BlockRepositoryTrait,
+    T: TransactionRepositoryTrait,
+    L: LogRepositoryTrait,
+{
+    synchronizer
+        .sync(lease.start_block, lease.end_block)
+        .await?;
+    let missing = synchronizer
+        .missing_blocks(lease.start_block, lease.end_block)
+        .await?;
+    if missing.is_empty() {
+        return Ok(missing);
+    }
+
+    tracing::info!(
+        "Filling {} missing blocks of blocks {} to {}",
+        missing.len(),
+        lease.start_block,
+        lease.end_block
+    );
+    synchronizer.sync_missing_blocks(missing).await?;
+    Ok(synchronizer
+        .missing_blocks(lease.start_block, lease.end_block)
+        .await?)
+}
+
+/// Renews `lease` a few times per TTL. Returns once another instance holds
+/// the range.
+async fn hold_lease<S: LeaseRepositoryTrait>(
+    lease_repository: &S,
+    lease: &BlockRangeLease,
+) {
+    let interval = Duration::from_secs(lease_repository.lease_ttl()).div_f64(3.0);
+    loop {
+        tokio::time::sleep(interval).await;
+        match lease_repository.renew_lease(lease).await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(error) => tracing::error!("Error renewing lease: {}", error),
+        }
+    }
+}
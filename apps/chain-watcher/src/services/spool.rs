@@ -0,0 +1,557 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use common::types::StreamEnvelope;
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    sync::Mutex,
+};
+
+use crate::clients::redis_client::{PublishError, PublishOutcome, RedisClientTrait};
+
+use super::{control::ChainControl, metrics::ChainMetrics};
+
+/// A new segment is started once the current one reaches this size, unless
+/// set with [`Spool::with_segment_max_bytes`].
+const SEGMENT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Each record is a little-endian u32 length followed by the JSON envelope.
+const RECORD_HEADER_BYTES: u64 = 4;
+
+const SEGMENT_EXTENSION: &str = "seg";
+
+const CURSOR_FILE: &str = "cursor";
+
+/// Records that can never be published, one JSON envelope per line.
+const QUARANTINE_FILE: &str = "quarantine.jsonl";
+
+const REPLAY_IDLE_DELAY: Duration = Duration::from_secs(1);
+
+const REPLAY_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+const REPLAY_MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum SpoolError {
+    IoError(String, String),
+    Full(u64),
+}
+
+impl fmt::Display for SpoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpoolError::IoError(error, path) => {
+                write!(f, "Spool IO Error: {}. Path: {}", error, path)
+            }
+            SpoolError::Full(max_bytes) => {
+                write!(f, "Spool Full: the spool holds {} bytes or more", max_bytes)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpoolError {}
+
+fn io_error(path: &Path) -> impl Fn(std::io::Error) -> SpoolError + '_ {
+    move |error| SpoolError::IoError(error.to_string(), path.display().to_string())
+}
+
+/// Position of the next record to replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpoolCursor {
+    segment: u64,
+    offset: u64,
+}
+
+struct SpoolState {
+    /// Segment ids and sizes, oldest first.
+    segments: VecDeque<(u64, u64)>,
+    writer: Option<File>,
+    cursor: SpoolCursor,
+    bytes: u64,
+    messages: u64,
+}
+
+/// Records returned by [`Spool::peek`], with the cursor past them.
+pub struct SpoolBatch {
+    pub envelopes: Vec<StreamEnvelope>,
+    cursor: SpoolCursor,
+    records: u64,
+}
+
+/// Durable, append-only buffer for messages that could not be published.
+///
+/// Messages are appended to numbered segment files in the order they were
+/// meant to be published, and replayed from a persisted cursor. Segments are
+/// deleted once fully replayed. A crash between publishing a batch and
+/// saving the cursor replays that batch again.
+pub struct Spool {
+    dir: PathBuf,
+    max_bytes: u64,
+    segment_max_bytes: u64,
+    state: Mutex<SpoolState>,
+    metrics: Arc<ChainMetrics>,
+}
+
+impl Spool {
+    pub async fn open(
+        dir: PathBuf,
+        max_bytes: u64,
+        metrics: Arc<ChainMetrics>,
+    ) -> Result<Self, SpoolError> {
+        fs::create_dir_all(&dir).await.map_err(io_error(&dir))?;
+
+        let mut ids = Vec::new();
+        let mut entries = fs::read_dir(&dir).await.map_err(io_error(&dir))?;
+        while let Some(entry) = entries.next_entry().await.map_err(io_error(&dir))? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SEGMENT_EXTENSION) {
+                continue;
+            }
+            if let Some(id) = path.file_stem().and_then(|s| s.to_str()?.parse().ok()) {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+
+        let cursor_path = dir.join(CURSOR_FILE);
+        let cursor = match fs::read_to_string(&cursor_path).await {
+            Ok(content) => {
+                let mut parts = content.split_whitespace().map(str::parse::<u64>);
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(segment)), Some(Ok(offset))) => {
+                        SpoolCursor { segment, offset }
+                    }
+                    _ => SpoolCursor {
+                        segment: ids.first().copied().unwrap_or_default(),
+                        offset: 0,
+                    },
+                }
+            }
+            Err(_) => SpoolCursor {
+                segment: ids.first().copied().unwrap_or_default(),
+                offset: 0,
+            },
+        };
+
+        let spool = Self {
+            dir,
+            max_bytes,
+            segment_max_bytes: SEGMENT_MAX_BYTES,
+            state: Mutex::new(SpoolState {
+                segments: VecDeque::new(),
+                writer: None,
+                cursor,
+                bytes: 0,
+                messages: 0,
+            }),
+            metrics,
+        };
+
+        let mut state = spool.state.lock().await;
+        let last_id = ids.last().copied();
+        for id in ids {
+            let path = spool.segment_path(id);
+            if id < cursor.segment {
+                // Replayed before a crash, but not yet deleted.
+                fs::remove_file(&path).await.map_err(io_error(&path))?;
+                continue;
+            }
+            let start = if id == cursor.segment {
+                cursor.offset
+            } else {
+                0
+            };
+            let (records, end) = Self::scan(&path, start).await?;
+            if Some(id) == last_id {
+                // Drops a record cut short by a crash while appending.
+                let file = OpenOptions::new()
+                    .write(true)
+                    .open(&path)
+                    .await
+                    .map_err(io_error(&path))?;
+                file.set_len(end).await.map_err(io_error(&path))?;
+            }
+            state.segments.push_back((id, end));
+            state.bytes += end;
+            state.messages += records;
+        }
+        spool.update_gauges(&state);
+        drop(state);
+
+        Ok(spool)
+    }
+
+    pub fn with_segment_max_bytes(mut self, segment_max_bytes: u64) -> Self {
+        self.segment_max_bytes = segment_max_bytes;
+        self
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.state.lock().await.messages == 0
+    }
+
+    /// Appends `envelopes` after everything already spooled.
+    pub async fn append(&self, envelopes: &[StreamEnvelope]) -> Result<(), SpoolError> {
+        let mut buffer = Vec::new();
+        for envelope in envelopes {
+            let record = serde_json::to_vec(envelope).map_err(|e| {
+                SpoolError::IoError(e.to_string(), self.dir.display().to_string())
+            })?;
+            buffer.extend_from_slice(&(record.len() as u32).to_le_bytes());
+            buffer.extend_from_slice(&record);
+        }
+
+        let mut state = self.state.lock().await;
+        if state.bytes + buffer.len() as u64 > self.max_bytes {
+            ChainMetrics::add(&self.metrics.spool_rejections, 1);
+            return Err(SpoolError::Full(self.max_bytes));
+        }
+
+        let rotate = match state.segments.back() {
+            Some((_, size)) => *size >= self.segment_max_bytes,
+            None => true,
+        };
+        if rotate || state.writer.is_none() {
+            let id = match state.segments.back() {
+                Some((id, _)) if rotate => id + 1,
+                Some((id, _)) => *id,
+                None => state.cursor.segment,
+            };
+            let path = self.segment_path(id);
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .await
+                .map_err(io_error(&path))?;
+            if rotate {
+                state.segments.push_back((id, 0));
+            }
+            state.writer = Some(file);
+        }
+
+        let (id, size) = *state
+            .segments
+            .back()
+            .expect("Spool has a segment to write.");
+        let path = self.segment_path(id);
+        let writer = state.writer.as_mut().expect("Spool has an open segment.");
+        let written = match writer.write_all(&buffer).await {
+            Ok(()) => writer.sync_data().await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = written {
+            // Keeps a partial write from corrupting the records after it.
+            let _ = writer.set_len(size).await;
+            state.writer = None;
+            return Err(io_error(&path)(error));
+        }
+
+        let length = buffer.len() as u64;
+        if let Some((_, size)) = state.segments.back_mut() {
+            *size += length;
+        }
+        state.bytes += length;
+        state.messages += envelopes.len() as u64;
+        ChainMetrics::add(&self.metrics.messages_spooled, envelopes.len() as u64);
+        self.update_gauges(&state);
+        Ok(())
+    }
+
+    /// Reads up to `max` of the oldest records without consuming them.
+    /// Records that no longer decode are logged and skipped.
+    pub async fn peek(&self, max: usize) -> Result<SpoolBatch, SpoolError> {
+        let state = self.state.lock().await;
+        let mut cursor = state.cursor;
+        let mut envelopes = Vec::new();
+        let mut records = 0;
+
+        for (id, size) in state.segments.iter().copied() {
+            if id < cursor.segment {
+                continue;
+            }
+            if id > cursor.segment {
+                cursor = SpoolCursor {
+                    segment: id,
+                    offset: 0,
+                };
+            }
+
+            let path = self.segment_path(id);
+            let mut file = File::open(&path).await.map_err(io_error(&path))?;
+            file.seek(SeekFrom::Start(cursor.offset))
+                .await
+                .map_err(io_error(&path))?;
+
+            while (records as usize) < max && cursor.offset + RECORD_HEADER_BYTES <= size
+            {
+                let length = file.read_u32_le().await.map_err(io_error(&path))? as u64;
+                let mut record = vec![0; length as usize];
+                file.read_exact(&mut record)
+                    .await
+                    .map_err(io_error(&path))?;
+                cursor.offset += RECORD_HEADER_BYTES + length;
+                records += 1;
+
+                match serde_json::from_slice(&record) {
+                    Ok(envelope) => envelopes.push(envelope),
+                    Err(error) => tracing::error!(
+                        "Skipping unreadable spool record in {}: {}",
+                        path.display(),
+                        error
+                    ),
+                }
+            }
+
+            if records as usize >= max {
+                break;
+            }
+        }
+
+        Ok(SpoolBatch {
+            envelopes,
+            cursor,
+            records,
+        })
+    }
+
+    /// Consumes the records returned by `peek` and persists the cursor.
+    pub async fn advance(&self, batch: &SpoolBatch) -> Result<(), SpoolError> {
+        let mut state = self.state.lock().await;
+        state.cursor = batch.cursor;
+        state.messages = state.messages.saturating_sub(batch.records);
+
+        while let Some((id, size)) = state.segments.front().copied() {
+            let drained = state.messages == 0;
+            // Read to the end, with a newer segment being written to.
+            let replayed = id == state.cursor.segment
+                && state.cursor.offset >= size
+                && state.segments.len() > 1;
+            if id >= state.cursor.segment && !drained && !replayed {
+                break;
+            }
+            let path = self.segment_path(id);
+            fs::remove_file(&path).await.map_err(io_error(&path))?;
+            state.segments.pop_front();
+            state.bytes -= size;
+            if replayed {
+                if let Some((next, _)) = state.segments.front() {
+                    state.cursor = SpoolCursor {
+                        segment: *next,
+                        offset: 0,
+                    };
+                }
+            }
+            if drained && state.segments.is_empty() {
+                // Start over in a fresh segment.
+                state.writer = None;
+                state.cursor = SpoolCursor {
+                    segment: id + 1,
+                    offset: 0,
+                };
+            }
+        }
+
+        let cursor_path = self.dir.join(CURSOR_FILE);
+        let temporary_path = self.dir.join(format!("{}.tmp", CURSOR_FILE));
+        let content = format!("{} {}", state.cursor.segment, state.cursor.offset);
+        fs::write(&temporary_path, content)
+            .await
+            .map_err(io_error(&temporary_path))?;
+        fs::rename(&temporary_path, &cursor_path)
+            .await
+            .map_err(io_error(&cursor_path))?;
+
+        self.update_gauges(&state);
+        Ok(())
+    }
+
+    /// Moves the records returned by `peek` to the quarantine file, then
+    /// consumes them.
+    pub async fn quarantine(&self, batch: &SpoolBatch) -> Result<(), SpoolError> {
+        let path = self.dir.join(QUARANTINE_FILE);
+        let mut buffer = Vec::new();
+        for envelope in &batch.envelopes {
+            serde_json::to_writer(&mut buffer, envelope).map_err(|e| {
+                SpoolError::IoError(e.to_string(), path.display().to_string())
+            })?;
+            buffer.push(b'\n');
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .map_err(io_error(&path))?;
+        file.write_all(&buffer).await.map_err(io_error(&path))?;
+        file.sync_data().await.map_err(io_error(&path))?;
+        ChainMetrics::add(&self.metrics.spool_quarantined, batch.records);
+
+        self.advance(batch).await
+    }
+
+    fn segment_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{:020}.{}", id, SEGMENT_EXTENSION))
+    }
+
+    fn update_gauges(&self, state: &SpoolState) {
+        ChainMetrics::set(&self.metrics.spool_messages, state.messages);
+        ChainMetrics::set(&self.metrics.spool_bytes, state.bytes);
+    }
+
+    /// Counts the complete records from `offset` and returns the offset
+    /// right after the last one.
+    async fn scan(path: &Path, offset: u64) -> Result<(u64, u64), SpoolError> {
+        let mut file = File::open(path).await.map_err(io_error(path))?;
+        let size = file.metadata().await.map_err(io_error(path))?.len();
+        let mut end = offset.min(size);
+        let mut records = 0;
+
+        file.seek(SeekFrom::Start(end))
+            .await
+            .map_err(io_error(path))?;
+        while end + RECORD_HEADER_BYTES <= size {
+            let length = file.read_u32_le().await.map_err(io_error(path))? as u64;
+            if end + RECORD_HEADER_BYTES + length > size {
+                break;
+            }
+            end += RECORD_HEADER_BYTES + length;
+            records += 1;
+            file.seek(SeekFrom::Start(end))
+                .await
+                .map_err(io_error(path))?;
+        }
+        Ok((records, end))
+    }
+}
+
+/// Publishes spooled messages, oldest first, whenever the broker accepts
+/// them. Runs for as long as the chain is watched.
+///
+/// With `leader_election`, replay starts once this instance leads the chain
+/// and `fencing_token` is set, since unfenced writes are not checked. Once
+/// fenced off, it waits for the token to change, which happens when this
+/// instance becomes leader again. It also waits while the chain is paused
+/// through `control`.
+pub async fn replay_spool<R: RedisClientTrait>(
+    spool: Arc<Spool>,
+    redis_client: R,
+    stream_key: String,
+    batch_size: usize,
+    fencing_token: Arc<AtomicU64>,
+    leader_election: bool,
+    control: Arc<ChainControl>,
+) {
+    let mut delay = REPLAY_RETRY_DELAY;
+    // Drops to 1 after an encoding error, to single out the record causing it.
+    let mut limit = batch_size;
+    loop {
+        control.wait_while_paused().await;
+        if leader_election && fencing_token.load(Ordering::SeqCst) == 0 {
+            tokio::time::sleep(REPLAY_IDLE_DELAY).await;
+            continue;
+        }
+        let batch = match spool.peek(limit).await {
+            Ok(batch) => batch,
+            Err(error) => {
+                tracing::error!("Error reading the spool: {}", error);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(REPLAY_MAX_RETRY_DELAY);
+                continue;
+            }
+        };
+        if batch.records == 0 {
+            tokio::time::sleep(REPLAY_IDLE_DELAY).await;
+            continue;
+        }
+
+        match redis_client
+            .send_messages(&stream_key, &batch.envelopes)
+            .await
+        {
+            Ok(outcomes) => {
+                let duplicates = outcomes
+                    .iter()
+                    .filter(|outcome| **outcome == PublishOutcome::Duplicate)
+                    .count() as u64;
+                let metrics = &spool.metrics;
+                ChainMetrics::add(&metrics.messages_replayed, outcomes.len() as u64);
+                ChainMetrics::add(&metrics.messages_duplicate, duplicates);
+                ChainMetrics::add(
+                    &metrics.messages_published,
+                    outcomes.len() as u64 - duplicates,
+                );
+
+                if let Err(error) = spool.advance(&batch).await {
+                    tracing::error!("Error advancing the spool: {}", error);
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+                delay = REPLAY_RETRY_DELAY;
+                if spool.is_empty().await {
+                    tracing::info!("Spool drained: {:?}", spool.metrics.snapshot());
+                }
+            }
+            // Another instance leads the chain now. These blocks may already
+            // be checkpointed, so the new leader would not publish them again:
+            // they are kept until this instance leads the chain once more.
+            Err(PublishError::Fenced(error)) => {
+                ChainMetrics::add(&spool.metrics.publish_errors, 1);
+                tracing::warn!(
+                    "Holding {} spooled messages until leadership is regained: {}",
+                    batch.envelopes.len(),
+                    error
+                );
+                let fenced_token = fencing_token.load(Ordering::SeqCst);
+                while fencing_token.load(Ordering::SeqCst) == fenced_token {
+                    tokio::time::sleep(REPLAY_IDLE_DELAY).await;
+                }
+            }
+            // Sending it again would fail the same way.
+            Err(PublishError::EncodingError(error)) => {
+                ChainMetrics::add(&spool.metrics.publish_errors, 1);
+                if batch.records > 1 {
+                    limit = 1;
+                    continue;
+                }
+                tracing::error!(
+                    "Quarantining a spooled message of block {:?} in {}: {}",
+                    batch
+                        .envelopes
+                        .first()
+                        .map(|envelope| envelope.block_number),
+                    spool.dir.join(QUARANTINE_FILE).display(),
+                    error
+                );
+                if let Err(error) = spool.quarantine(&batch).await {
+                    tracing::error!("Error quarantining the spooled message: {}", error);
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+                limit = batch_size;
+            }
+            Err(error) => {
+                ChainMetrics::add(&spool.metrics.publish_errors, 1);
+                tracing::warn!(
+                    "Error replaying {} spooled messages, retrying in {:?}: {}",
+                    batch.envelopes.len(),
+                    delay,
+                    error
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(REPLAY_MAX_RETRY_DELAY);
+            }
+        }
+    }
+}
@@ -1,171 +1,843 @@
-use std::time::Instant;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
+use common::{
+    telemetry,
+    types::{ContractCreation, FinalityStatus, StreamEnvelope, StreamPayload, SyncLane},
+};
 use ethers::{
     providers::ProviderError,
-    types::{Block as EthersBlock, Transaction},
+    types::{
+        Block as EthersBlock, CallFrame, Log, NameOrAddress, Transaction,
+        TransactionReceipt, H256, U64,
+    },
+    utils::keccak256,
 };
-use futures::stream::{FuturesUnordered, StreamExt};
-use tokio::task;
+use futures::stream::{self, FuturesOrdered, FuturesUnordered, StreamExt, TryStreamExt};
+use tokio::task::{self, JoinError};
+use tracing::Instrument;
 
 use crate::{
-    clients::{blockchain_client::BlockchainClientTrait, redis_client::RedisClientTrait},
-    config::Config,
+    clients::{
+        blockchain_client::BlockchainClientTrait,
+        redis_client::{PublishOutcome, RedisClientTrait, StreamIdMode},
+    },
+    config::ChainSyncConfig,
 };
 
-use super::repositories::block::{Block, BlockRepositoryTrait};
+use super::{
+    control::{ChainControl, InFlightGuard},
+    integrity::{verify_receipts, IntegrityError},
+    metrics::ChainMetrics,
+    repositories::{
+        block::{Block, BlockRepositoryTrait},
+        log::LogRepositoryTrait,
+        transaction::{TransactionRecord, TransactionRepositoryTrait},
+    },
+    spool::Spool,
+};
+
+pub type SyncError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Processed blocks are recorded in Postgres in batches of this size.
+const BLOCK_INSERT_BATCH_SIZE: usize = 100;
+
+/// Attempts at publishing a block's messages before the sync gives up.
+const PUBLISH_ATTEMPTS: u32 = 5;
+
+const PUBLISH_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Attempts at fetching a block whose receipts fail verification.
+const VERIFICATION_ATTEMPTS: u32 = 3;
+
+/// Blocks whose hashes are compared at once by `verify`.
+const VERIFY_CHUNK_SIZE: u64 = 1000;
+
+/// A block of a verified range that is not indexed with its canonical hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockDiscrepancy {
+    Missing(u64),
+    /// The indexed hash, and the canonical one, absent past the chain head.
+    HashMismatch(u64, Option<H256>, Option<H256>),
+}
+
+impl fmt::Display for BlockDiscrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockDiscrepancy::Missing(block_number) => {
+                write!(f, "Block {} is not indexed", block_number)
+            }
+            BlockDiscrepancy::HashMismatch(block_number, indexed, canonical) => write!(
+                f,
+                "Block {} is indexed as {:?}, the chain has {:?}",
+                block_number, indexed, canonical
+            ),
+        }
+    }
+}
+
+/// What a block keeps from each of its transactions.
+#[derive(Default)]
+struct ProcessedTransaction {
+    log_count: usize,
+    record: Option<TransactionRecord>,
+    archived_logs: Vec<Log>,
+    /// The transaction's logs message, keyed by transaction index.
+    message: Option<(u64, StreamEnvelope)>,
+    creation: Option<ContractCreation>,
+    /// Kept to verify the block's receipts.
+    receipt: Option<TransactionReceipt>,
+}
+
+/// A fetched block, with the messages still to be published for it.
+struct ProcessedBlock {
+    block: Block,
+    messages: Vec<StreamEnvelope>,
+    /// The block's span, under which its messages are published.
+    span: tracing::Span,
+}
 
 #[derive(Clone)]
 pub struct ChainSynchronizer<
     B: BlockchainClientTrait,
     R: RedisClientTrait,
     E: BlockRepositoryTrait,
+    T: TransactionRepositoryTrait,
+    L: LogRepositoryTrait,
 > {
     blockchain_client: B,
     redis_client: R,
     block_repository: E,
-    config: Config,
+    transaction_repository: T,
+    log_repository: L,
+    config: ChainSyncConfig,
+    /// Latest block number reported by the RPC, used to tell whether a block
+    /// is final when its messages are published.
+    chain_head: Arc<AtomicU64>,
+    spool: Option<Arc<Spool>>,
+    metrics: Arc<ChainMetrics>,
+    control: Arc<ChainControl>,
+    /// Tagged on every published message.
+    lane: SyncLane,
 }
 
-impl<B: BlockchainClientTrait, R: RedisClientTrait, E: BlockRepositoryTrait>
-    ChainSynchronizer<B, R, E>
+impl<
+        B: BlockchainClientTrait,
+        R: RedisClientTrait,
+        E: BlockRepositoryTrait,
+        T: TransactionRepositoryTrait,
+        L: LogRepositoryTrait,
+    > ChainSynchronizer<B, R, E, T, L>
 {
     pub fn new(
         blockchain_client: B,
         redis_client: R,
         block_repository: E,
-        config: Config,
+        transaction_repository: T,
+        log_repository: L,
+        config: ChainSyncConfig,
     ) -> Self {
+        let control = Arc::new(ChainControl::default());
         Self {
             blockchain_client,
             redis_client,
             block_repository,
+            transaction_repository,
+            log_repository,
             config,
+            chain_head: Arc::new(AtomicU64::new(0)),
+            spool: None,
+            metrics: control.metrics.clone(),
+            control,
+            lane: SyncLane::Live,
         }
     }
 
-    pub async fn sync_missing_blocks(&self, blocks: Vec<u64>) {
-        self.process_blocks(blocks.into_iter()).await;
+    /// Spools messages to disk when Redis stays unavailable.
+    pub fn with_spool(mut self, spool: Arc<Spool>) -> Self {
+        self.spool = Some(spool);
+        self
     }
 
-    pub async fn sync(&self, start_block: u64, end_block: u64) {
-        self.process_blocks(start_block..=end_block).await;
+    pub fn with_metrics(mut self, metrics: Arc<ChainMetrics>) -> Self {
+        self.metrics = metrics;
+        self
     }
 
+    /// Follows the pauses, worker limit and in-flight blocks of `control`,
+    /// and counts in its metrics.
+    pub fn with_control(mut self, control: Arc<ChainControl>) -> Self {
+        self.metrics = control.metrics.clone();
+        self.control = control;
+        self
+    }
+
+    pub fn with_lane(mut self, lane: SyncLane) -> Self {
+        self.lane = lane;
+        self
+    }
+
+    /// Limits how many blocks, and transactions of each block, are fetched
+    /// at once.
+    pub fn with_workers(mut self, num_workers: usize) -> Self {
+        self.config.num_workers = num_workers;
+        self
+    }
+
+    /// The configured workers, unless overridden through the control.
+    fn workers(&self) -> usize {
+        self.control
+            .worker_limit()
+            .unwrap_or(self.config.num_workers)
+    }
+
+    pub async fn sync_missing_blocks(&self, blocks: Vec<u64>) -> Result<(), SyncError> {
+        self.process_blocks(blocks.into_iter()).await
+    }
+
+    pub async fn sync(&self, start_block: u64, end_block: u64) -> Result<(), SyncError> {
+        self.process_blocks(start_block..=end_block).await
+    }
+
+    /// Stops at the first block whose messages cannot be published. That
+    /// block and the ones after it are not recorded as indexed, so they are
+    /// synced again on restart.
     async fn process_blocks(
         &self,
         block_numbers: impl Iterator<Item = u64> + Send + 'static,
-    ) {
-        let mut futures = FuturesUnordered::new();
+    ) -> Result<(), SyncError> {
+        // Blocks are fetched concurrently but come back, and are published,
+        // in block order, which explicit stream entry ids rely on.
+        let mut futures = FuturesOrdered::new();
+        let mut processed_blocks = Vec::new();
 
         for block_number in block_numbers {
+            self.control.wait_while_paused().await;
+            let in_flight = self.control.track(block_number, self.lane);
             let self_clone = self.clone();
-            futures.push(task::spawn(async move {
-                if let Ok(Some(block)) = self_clone
-                    .blockchain_client
-                    .get_block_with_txs(block_number)
-                    .await
-                {
-                    self_clone.process_block(block.clone()).await;
+            // Each block is a trace of its own, while its logs keep the
+            // chain's fields.
+            let span = tracing::info_span!("block", block_number);
+            telemetry::set_parent(&span, None);
+            futures.push_back(task::spawn(
+                async move {
+                    let processed = self_clone.fetch_block(block_number).await;
+                    (block_number, in_flight, processed)
                 }
-            }));
+                .instrument(span),
+            ));
+
+            if futures.len() >= self.workers() {
+                if let Some(result) = futures.next().await {
+                    if let Err(error) =
+                        self.publish_fetched(result, &mut processed_blocks).await
+                    {
+                        self.insert_blocks(&mut processed_blocks).await;
+                        return Err(error);
+                    }
+                }
+            }
 
-            if futures.len() >= self.config.num_workers {
-                futures.next().await;
+            if processed_blocks.len() >= BLOCK_INSERT_BATCH_SIZE {
+                self.insert_blocks(&mut processed_blocks).await;
             }
         }
 
-        while futures.next().await.is_some() {}
+        while let Some(result) = futures.next().await {
+            if let Err(error) = self.publish_fetched(result, &mut processed_blocks).await
+            {
+                self.insert_blocks(&mut processed_blocks).await;
+                return Err(error);
+            }
+        }
+
+        self.insert_blocks(&mut processed_blocks).await;
+        Ok(())
+    }
+
+    /// Publishes a block fetched by `process_blocks` and keeps it to be
+    /// recorded. A block that could not be fetched is skipped and left to the
+    /// gap fill, except with block-tx ids: the blocks after it would leave it
+    /// below the stream's last entry for good, so syncing stops there.
+    async fn publish_fetched(
+        &self,
+        result: Result<(u64, InFlightGuard, Option<ProcessedBlock>), JoinError>,
+        processed_blocks: &mut Vec<Block>,
+    ) -> Result<(), SyncError> {
+        let block_tx = self.config.stream_id_mode == StreamIdMode::BlockTx;
+        let (block_number, _in_flight, processed) = match result {
+            Ok(fetched) => fetched,
+            Err(error) if block_tx => return Err(Box::new(error)),
+            Err(_) => return Ok(()),
+        };
+        let Some(processed) = processed else {
+            if block_tx {
+                return Err(format!(
+                    "Block {} could not be synced, and block-tx ids must increase",
+                    block_number
+                )
+                .into());
+            }
+            return Ok(());
+        };
+        self.publish_block(&processed).await?;
+        processed_blocks.push(processed.block);
+        Ok(())
+    }
+
+    /// Fetches and processes a block. A block whose receipts fail
+    /// verification is fetched again from another provider, and skipped if
+    /// none serves it consistently. A block missing a receipt is skipped
+    /// too. Skipped blocks are left to the gap fill.
+    async fn fetch_block(&self, block_number: u64) -> Option<ProcessedBlock> {
+        let mut attempts = 0;
+        loop {
+            let block = match self
+                .blockchain_client
+                .get_block_with_txs(block_number)
+                .instrument(tracing::info_span!("fetch_block"))
+                .await
+            {
+                Ok(Some(block)) => block,
+                _ => return None,
+            };
+            let error = match self.process_block(block).await {
+                Ok(processed) => return Some(processed),
+                Err(error) => match error.downcast::<IntegrityError>() {
+                    Ok(error) => error,
+                    Err(error) => {
+                        tracing::error!(
+                            "Block {} could not be processed: {}, skipping the block",
+                            block_number,
+                            error
+                        );
+                        return None;
+                    }
+                },
+            };
+
+            attempts += 1;
+            if attempts < VERIFICATION_ATTEMPTS
+                && self.blockchain_client.switch_provider().await
+            {
+                tracing::warn!("{}, fetching it again", error);
+                continue;
+            }
+            tracing::error!("{}, skipping the block", error);
+            ChainMetrics::add(&self.metrics.blocks_unverified, 1);
+            return None;
+        }
     }
 
-    async fn process_block(&self, block: EthersBlock<Transaction>) {
+    async fn process_block(
+        &self,
+        block: EthersBlock<Transaction>,
+    ) -> Result<ProcessedBlock, SyncError> {
         let start_time = Instant::now();
 
         let mut futures = FuturesUnordered::new();
-        for transaction in block.transactions {
+        let mut log_count = 0;
+        let mut transactions = Vec::new();
+        let mut archived_logs = Vec::new();
+        let mut messages = Vec::new();
+        let mut creations = Vec::new();
+        let mut receipts = Vec::new();
+        let block_number = block
+            .number
+            .map(|number| number.as_u64())
+            .unwrap_or_default();
+        let finality = FinalityStatus::of(
+            block_number,
+            self.chain_head.load(Ordering::SeqCst),
+            self.config.chain.finality_depth,
+        );
+        for transaction in block.transactions.iter().cloned() {
             let blockchain_client = self.blockchain_client.clone();
-            let redis_client = self.redis_client.clone();
-            let stream_key = self.config.redis_config.stream_key.clone();
-            futures.push(task::spawn(async move {
-                if let Ok(Some(receipt)) = blockchain_client
-                    .get_transaction_receipt(transaction.hash)
-                    .await
-                {
+            let store_transactions = self.config.store_transactions;
+            let archive_logs = self.config.archive_logs;
+            let detect_contracts = self.config.detect_contracts;
+            let verify_receipts = self.config.verify_receipts;
+            let chain_id = self.config.chain.id;
+            let block_hash = block.hash;
+            let producer_id = self.config.producer_id.clone();
+            let lane = self.lane;
+            futures.push(task::spawn(
+                async move {
+                    let tx_hash = transaction.hash;
+                    let receipt = blockchain_client
+                        .get_transaction_receipt(tx_hash)
+                        .instrument(tracing::info_span!("fetch_receipt", ?tx_hash))
+                        .await?
+                        .ok_or_else(|| {
+                            format!("Receipt of transaction {:?} not found", tx_hash)
+                        })?;
+                    let creation = detect_contracts
+                        .then(|| receipt_creation(&transaction, &receipt))
+                        .flatten();
+                    let record = store_transactions.then(|| {
+                        TransactionRecord::new(&transaction.into(), &receipt, chain_id)
+                    });
+                    let kept_receipt = verify_receipts.then(|| receipt.clone());
                     let logs = receipt.logs;
-                    if let Err(e) = redis_client.send_logs(stream_key, logs).await {
-                        tracing::error!(
-                            "Error sending logs for transaction hash {} error {}",
-                            transaction.hash,
-                            e
-                        )
-                    }
+                    Ok::<_, SyncError>(ProcessedTransaction {
+                        creation,
+                        receipt: kept_receipt,
+                        log_count: logs.len(),
+                        record,
+                        archived_logs: if archive_logs {
+                            logs.clone()
+                        } else {
+                            Vec::new()
+                        },
+                        message: (!logs.is_empty()).then(|| {
+                            (
+                                receipt.transaction_index.as_u64(),
+                                StreamEnvelope::new(
+                                    chain_id,
+                                    block_number,
+                                    block_hash,
+                                    finality,
+                                    producer_id,
+                                    StreamPayload::Logs(
+                                        logs.into_iter().map(|log| log.into()).collect(),
+                                    ),
+                                )
+                                .with_lane(lane),
+                            )
+                        }),
+                    })
                 }
-            }));
+                .in_current_span(),
+            ));
 
-            if futures.len() >= self.config.num_workers {
-                futures.next().await;
+            if futures.len() >= self.workers() {
+                if let Some(result) = futures.next().await {
+                    let processed = result??;
+                    log_count += processed.log_count;
+                    transactions.extend(processed.record);
+                    archived_logs.extend(processed.archived_logs);
+                    messages.extend(processed.message);
+                    creations.extend(processed.creation);
+                    receipts.extend(processed.receipt);
+                }
             }
         }
 
-        while futures.next().await.is_some() {}
+        // A transaction without its receipt would leave the block's logs
+        // incomplete, so the whole block fails instead.
+        while let Some(result) = futures.next().await {
+            let processed = result??;
+            log_count += processed.log_count;
+            transactions.extend(processed.record);
+            archived_logs.extend(processed.archived_logs);
+            messages.extend(processed.message);
+            creations.extend(processed.creation);
+            receipts.extend(processed.receipt);
+        }
 
-        let block_number = block.number.unwrap().as_u64();
-        match self
-            .block_repository
-            .insert_block(Block {
-                block_number,
-                chain_id: self.config.chain.id,
-            })
-            .await
-        {
-            Ok(_) => {
-                tracing::debug!("Blocks inserted successfully");
-            }
-            Err(error) => {
-                tracing::error!("Error inserting blocks: {:?}", error);
-            }
+        if self.config.verify_receipts {
+            receipts.sort_by_key(|receipt| receipt.transaction_index);
+            verify_receipts(&block, &receipts)?;
         }
 
+        // The block is only recorded once its transactions and logs are, so
+        // they are stored again when it is retried.
+        self.transaction_repository
+            .insert_transactions(&transactions)
+            .await?;
+
+        self.log_repository.archive_logs(&archived_logs).await?;
+
+        let processed_block = Block::new(&block, self.config.chain.id, log_count as u32);
+
         let end_time = Instant::now();
         let duration = end_time.duration_since(start_time);
 
         tracing::info!(
             "Block number {:?} processed in {:?}.",
-            block_number,
+            processed_block.block_number,
             duration
         );
+
+        // Lets consumers continue the block's trace.
+        let trace_context = telemetry::current_trace_context();
+        messages.sort_by_key(|(transaction_index, _)| *transaction_index);
+        let mut messages: Vec<StreamEnvelope> = messages
+            .into_iter()
+            .map(|(_, message)| message.with_trace_context(trace_context.clone()))
+            .collect();
+        if self.config.detect_contracts {
+            let creations = self
+                .contract_creations(&block, block_number, creations)
+                .await;
+            if !creations.is_empty() {
+                messages.push(
+                    StreamEnvelope::new(
+                        self.config.chain.id,
+                        block_number,
+                        block.hash,
+                        finality,
+                        self.config.producer_id.clone(),
+                        StreamPayload::ContractCreations(creations),
+                    )
+                    .with_lane(self.lane)
+                    .with_trace_context(trace_context),
+                );
+            }
+        }
+
+        Ok(ProcessedBlock {
+            block: processed_block,
+            messages,
+            span: tracing::Span::current(),
+        })
+    }
+
+    /// Adds the contracts factories deployed in the block to those deployed by
+    /// its transactions, when tracing, and their code hashes, when requested.
+    async fn contract_creations(
+        &self,
+        block: &EthersBlock<Transaction>,
+        block_number: u64,
+        mut creations: Vec<ContractCreation>,
+    ) -> Vec<ContractCreation> {
+        if self.config.trace_contracts {
+            match self.blockchain_client.trace_block(block_number).await {
+                Ok(traces) => {
+                    for (transaction, trace) in block.transactions.iter().zip(traces) {
+                        creations.extend(traced_creations(transaction, &trace.result));
+                    }
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        "Error tracing block {}, factory deployments are missed: {}",
+                        block_number,
+                        error
+                    );
+                }
+            }
+        }
+        // Stable, so a creation transaction's own contract comes first.
+        creations.sort_by_key(|creation| creation.transaction_index);
+
+        if !self.config.contract_code_hash {
+            return creations;
+        }
+        stream::iter(creations)
+            .map(|mut creation| async move {
+                match self
+                    .blockchain_client
+                    .get_code(creation.address, block_number)
+                    .await
+                {
+                    Ok(code) if !code.is_empty() => {
+                        creation.code_hash = Some(H256(keccak256(&code)));
+                    }
+                    Ok(_) => {}
+                    Err(error) => {
+                        tracing::warn!(
+                            "Error fetching the code of {:?}: {}",
+                            creation.address,
+                            error
+                        );
+                    }
+                }
+                creation
+            })
+            .buffered(self.workers())
+            .collect()
+            .await
+    }
+
+    async fn publish_block(&self, processed: &ProcessedBlock) -> Result<(), SyncError> {
+        self.control.wait_while_paused().await;
+        let span = tracing::info_span!(
+            parent: &processed.span,
+            "publish",
+            messages = processed.messages.len()
+        );
+        self.publish(&processed.messages).instrument(span).await
+    }
+
+    /// Sends a block's messages in transaction order, retrying transient
+    /// Redis errors with exponential backoff. With a spool, messages that
+    /// still cannot be sent are spooled instead, and so are all messages
+    /// while older ones wait in the spool, to keep them in order.
+    ///
+    /// A spooled block counts as published: it is recorded and checkpointed
+    /// at once and not synced again, and its messages reach the stream only
+    /// through the spool's replay. They survive restarts, since the spool is
+    /// synced to disk, but not the loss of the spool directory.
+    async fn publish(&self, messages: &[StreamEnvelope]) -> Result<(), SyncError> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        if let Some(spool) = &self.spool {
+            if !spool.is_empty().await {
+                spool.append(messages).await?;
+                return Ok(());
+            }
+        }
+
+        let stream_key = &self.config.redis_config.stream_key;
+        let mut delay = PUBLISH_RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            match self.redis_client.send_messages(stream_key, messages).await {
+                Ok(outcomes) => {
+                    let duplicates = outcomes
+                        .iter()
+                        .filter(|outcome| **outcome == PublishOutcome::Duplicate)
+                        .count() as u64;
+                    if duplicates > 0 {
+                        tracing::debug!(
+                            "Skipped {} messages already in the stream.",
+                            duplicates
+                        );
+                    }
+                    ChainMetrics::add(&self.metrics.messages_duplicate, duplicates);
+                    ChainMetrics::add(
+                        &self.metrics.messages_published,
+                        outcomes.len() as u64 - duplicates,
+                    );
+                    return Ok(());
+                }
+                Err(error) if error.is_transient() && attempt < PUBLISH_ATTEMPTS => {
+                    ChainMetrics::add(&self.metrics.publish_errors, 1);
+                    tracing::warn!(
+                        "Error publishing {} messages, retrying in {:?}: {}",
+                        messages.len(),
+                        delay,
+                        error
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(error) => {
+                    ChainMetrics::add(&self.metrics.publish_errors, 1);
+                    return match &self.spool {
+                        Some(spool) if error.is_transient() => {
+                            tracing::warn!(
+                                "Spooling {} messages to disk: {}",
+                                messages.len(),
+                                error
+                            );
+                            spool.append(messages).await?;
+                            Ok(())
+                        }
+                        _ => Err(Box::new(error)),
+                    };
+                }
+            }
+        }
+    }
+
+    async fn insert_blocks(&self, blocks: &mut Vec<Block>) {
+        if blocks.is_empty() {
+            return;
+        }
+
+        match self.block_repository.insert_blocks_bulk(blocks).await {
+            Ok(_) => {
+                tracing::debug!("{} blocks inserted successfully", blocks.len());
+            }
+            Err(error) => {
+                tracing::error!("Error inserting blocks: {:?}", error);
+            }
+        }
+        blocks.clear();
     }
 
     pub fn start_block(&self) -> u64 {
         self.config.start_block.unwrap_or(0)
     }
 
-    pub async fn missing_blocks(&self) -> Result<Vec<u64>, sqlx::Error> {
-        let indexed_blocks = match self.block_repository.get_indexed_blocks().await {
-            Ok(result) => result,
-            Err(err) => {
-                tracing::error!("Error on retrieve missing block operation: {}.", err);
-                Vec::new()
+    /// The blocks from `first_block` to `last_block` that are not indexed.
+    pub async fn missing_blocks(
+        &self,
+        first_block: u64,
+        last_block: u64,
+    ) -> Result<Vec<u64>, sqlx::Error> {
+        let mut missing_blocks = Vec::new();
+        let mut start_block = first_block;
+        while start_block <= last_block {
+            let end_block =
+                last_block.min(start_block.saturating_add(VERIFY_CHUNK_SIZE - 1));
+            let indexed: HashSet<u64> = self
+                .block_repository
+                .get_block_hashes(start_block, end_block)
+                .await?
+                .into_iter()
+                .map(|(block_number, _)| block_number)
+                .collect();
+            missing_blocks.extend(
+                (start_block..=end_block).filter(|block| !indexed.contains(block)),
+            );
+            match end_block.checked_add(1) {
+                Some(next) => start_block = next,
+                None => break,
             }
-        };
+        }
+        Ok(missing_blocks)
+    }
+
+    pub async fn end_block(&self) -> Result<u64, ProviderError> {
+        let chain_head = self.chain_head().await?;
+        Ok(self.config.end_block.unwrap_or(chain_head))
+    }
+
+    /// Compares the indexed hash of every block from `from` to `to` with the
+    /// one the RPC serves.
+    pub async fn verify(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<BlockDiscrepancy>, SyncError> {
+        let mut discrepancies = Vec::new();
+        let mut start_block = from;
+        while start_block <= to {
+            let end_block = to.min(start_block.saturating_add(VERIFY_CHUNK_SIZE - 1));
+            let indexed: HashMap<u64, Option<H256>> = self
+                .block_repository
+                .get_block_hashes(start_block, end_block)
+                .await?
+                .into_iter()
+                .map(|(block_number, hash)| {
+                    (block_number, hash.and_then(|hash| hash.parse().ok()))
+                })
+                .collect();
+            let canonical: Vec<(u64, Option<H256>)> =
+                stream::iter(start_block..=end_block)
+                    .map(|block_number| async move {
+                        let hash =
+                            self.blockchain_client.get_block_hash(block_number).await?;
+                        Ok::<_, ProviderError>((block_number, hash))
+                    })
+                    .buffered(self.workers())
+                    .try_collect()
+                    .await?;
 
-        if indexed_blocks.is_empty() {
-            return Ok(Vec::new());
+            for (block_number, canonical) in canonical {
+                match indexed.get(&block_number) {
+                    None => discrepancies.push(BlockDiscrepancy::Missing(block_number)),
+                    Some(hash) if *hash != canonical => discrepancies.push(
+                        BlockDiscrepancy::HashMismatch(block_number, *hash, canonical),
+                    ),
+                    Some(_) => {}
+                }
+            }
+
+            match end_block.checked_add(1) {
+                Some(next) => start_block = next,
+                None => break,
+            }
         }
+        Ok(discrepancies)
+    }
 
-        let end_block = self.end_block().await.unwrap_or(self.start_block());
-        let full_block_range: Vec<u64> = (self.start_block()..end_block).collect();
+    /// Publishes a revert message for every indexed block above
+    /// `block_number`, from the highest down, then deletes them. Returns how
+    /// many blocks were reverted.
+    pub async fn rewind(&self, block_number: u64) -> Result<u64, SyncError> {
+        // Redis would reject the ids of every block indexed again.
+        if self.config.stream_id_mode == StreamIdMode::BlockTx {
+            return Err("Cannot rewind a stream with --stream-id-mode block-tx".into());
+        }
 
-        let missing_blocks: Vec<u64> = full_block_range
-            .into_iter()
-            .filter(|block| !indexed_blocks.contains(block))
+        let blocks = self
+            .block_repository
+            .get_block_hashes(block_number.saturating_add(1), u64::MAX)
+            .await?;
+        let reverts: Vec<StreamEnvelope> = blocks
+            .iter()
+            .rev()
+            .map(|(reverted, hash)| {
+                StreamEnvelope::new(
+                    self.config.chain.id,
+                    *reverted,
+                    hash.as_deref().and_then(|hash| hash.parse().ok()),
+                    FinalityStatus::Unfinalized,
+                    self.config.producer_id.clone(),
+                    StreamPayload::Revert,
+                )
+                .with_lane(self.lane)
+            })
             .collect();
-        Ok(missing_blocks)
+        for chunk in reverts.chunks(self.config.publish_batch_size) {
+            self.publish(chunk).await?;
+        }
+
+        self.block_repository
+            .delete_blocks_above(block_number)
+            .await?;
+        Ok(blocks.len() as u64)
     }
 
-    pub async fn end_block(&self) -> Result<u64, ProviderError> {
-        Ok(self
-            .config
-            .end_block
-            .unwrap_or(self.blockchain_client.get_block_number().await?))
+    /// Fetches the latest block number from the RPC.
+    pub async fn chain_head(&self) -> Result<u64, ProviderError> {
+        let chain_head = self.blockchain_client.get_block_number().await?;
+        self.chain_head.fetch_max(chain_head, Ordering::SeqCst);
+        Ok(chain_head)
+    }
+}
+
+/// The contract a successful creation transaction deployed.
+fn receipt_creation(
+    transaction: &Transaction,
+    receipt: &TransactionReceipt,
+) -> Option<ContractCreation> {
+    if receipt.status == Some(U64::zero()) {
+        return None;
+    }
+    Some(ContractCreation {
+        address: receipt.contract_address?,
+        creator: transaction.from,
+        sender: transaction.from,
+        transaction_hash: transaction.hash,
+        transaction_index: receipt.transaction_index.as_u64(),
+        code_hash: None,
+    })
+}
+
+/// Contracts deployed by other contracts within a transaction's call tree.
+/// Frames that reverted, and everything below them, are skipped.
+fn traced_creations(
+    transaction: &Transaction,
+    trace: &CallFrame,
+) -> Vec<ContractCreation> {
+    let mut creations = Vec::new();
+    if trace.error.is_some() {
+        return creations;
+    }
+    let mut frames: Vec<&CallFrame> = trace.calls.iter().flatten().rev().collect();
+    while let Some(frame) = frames.pop() {
+        if frame.error.is_some() {
+            continue;
+        }
+        if matches!(frame.typ.as_str(), "CREATE" | "CREATE2") {
+            if let Some(NameOrAddress::Address(address)) = frame.to {
+                creations.push(ContractCreation {
+                    address,
+                    creator: frame.from,
+                    sender: transaction.from,
+                    transaction_hash: transaction.hash,
+                    transaction_index: transaction
+                        .transaction_index
+                        .unwrap_or_default()
+                        .as_u64(),
+                    code_hash: None,
+                });
+            }
+        }
+        frames.extend(frame.calls.iter().flatten().rev());
     }
+    creations
 }
@@ -0,0 +1,177 @@
+mod support;
+
+use std::collections::HashMap;
+
+use chain_watcher::{
+    clients::{blockchain_client::BlockchainClient, redis_client::StreamIdMode},
+    services::{
+        admin,
+        repositories::{
+            block::IndexedRange,
+            checkpoint::{CheckpointRepositoryTrait, SyncCheckpoint},
+        },
+        sync::{BlockDiscrepancy, ChainSynchronizer},
+    },
+};
+use common::types::{MessageKind, SyncLane};
+use ethers::types::H256;
+use mock_node::{chain::MockChain, server::ServerHandle};
+use support::{
+    serve, sync_config, synchronizer, BlockStore, CheckpointStore, Discard, StreamSink,
+};
+
+/// Indexes blocks 1 to 5 of a mock chain ten blocks long.
+async fn indexed_chain(
+    stream_id_mode: StreamIdMode,
+) -> (
+    ServerHandle,
+    ChainSynchronizer<BlockchainClient, StreamSink, BlockStore, Discard, Discard>,
+    StreamSink,
+    BlockStore,
+) {
+    let mut chain = MockChain::new(1);
+    chain.mine_empty(10);
+    let (_node, server) = serve(chain).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let sink = StreamSink::default();
+    let blocks = BlockStore::default();
+    let synchronizer = match stream_id_mode {
+        StreamIdMode::Auto => synchronizer(client, sink.clone(), blocks.clone()),
+        stream_id_mode => {
+            let mut config = sync_config();
+            config.stream_id_mode = stream_id_mode;
+            ChainSynchronizer::new(
+                client,
+                sink.clone(),
+                blocks.clone(),
+                Discard,
+                Discard,
+                config,
+            )
+        }
+    };
+
+    synchronizer.chain_head().await.unwrap();
+    synchronizer.sync(1, 5).await.unwrap();
+    (server, synchronizer, sink, blocks)
+}
+
+#[tokio::test]
+async fn verify_reports_missing_and_mismatched_blocks() {
+    let (_server, synchronizer, _sink, blocks) = indexed_chain(StreamIdMode::Auto).await;
+    assert!(synchronizer.verify(1, 5).await.unwrap().is_empty());
+
+    let tampered = H256::repeat_byte(0xab);
+    let canonical = {
+        let mut blocks = blocks.blocks.lock().unwrap();
+        blocks.retain(|block| block.block_number != 4);
+        let block = blocks
+            .iter_mut()
+            .find(|block| block.block_number == 2)
+            .unwrap();
+        let canonical = block.hash.replace(format!("{:?}", tampered));
+        canonical.unwrap().parse().unwrap()
+    };
+
+    assert_eq!(
+        synchronizer.verify(1, 6).await.unwrap(),
+        vec![
+            BlockDiscrepancy::HashMismatch(2, Some(tampered), Some(canonical)),
+            BlockDiscrepancy::Missing(4),
+            BlockDiscrepancy::Missing(6),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn rewind_reverts_blocks_from_the_highest() {
+    let (_server, synchronizer, sink, blocks) = indexed_chain(StreamIdMode::Auto).await;
+    let hashes: HashMap<u64, Option<String>> = blocks
+        .blocks
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|block| (block.block_number, block.hash.clone()))
+        .collect();
+    sink.envelopes.lock().unwrap().clear();
+
+    assert_eq!(synchronizer.rewind(2).await.unwrap(), 3);
+
+    let reverts: Vec<(MessageKind, u64, Option<H256>)> = sink
+        .envelopes
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|envelope| (envelope.kind, envelope.block_number, envelope.block_hash))
+        .collect();
+    let expected: Vec<(MessageKind, u64, Option<H256>)> = [5, 4, 3]
+        .into_iter()
+        .map(|block_number| {
+            let hash = hashes[&block_number].as_ref().unwrap().parse().unwrap();
+            (MessageKind::Revert, block_number, Some(hash))
+        })
+        .collect();
+    assert_eq!(reverts, expected);
+
+    let mut remaining: Vec<u64> = blocks
+        .blocks
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|block| block.block_number)
+        .collect();
+    remaining.sort_unstable();
+    assert_eq!(remaining, vec![1, 2]);
+}
+
+#[tokio::test]
+async fn rewind_refuses_block_tx_stream_ids() {
+    let (_server, synchronizer, sink, blocks) =
+        indexed_chain(StreamIdMode::BlockTx).await;
+    sink.envelopes.lock().unwrap().clear();
+
+    assert!(synchronizer.rewind(2).await.is_err());
+    assert!(sink.envelopes.lock().unwrap().is_empty());
+    assert_eq!(blocks.blocks.lock().unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn status_reports_gaps_checkpoints_and_lag() {
+    let (_server, synchronizer, _sink, blocks) = indexed_chain(StreamIdMode::Auto).await;
+    blocks
+        .blocks
+        .lock()
+        .unwrap()
+        .retain(|block| block.block_number != 3);
+    let checkpoints = CheckpointStore::default();
+    let live = SyncCheckpoint {
+        start_block: 5,
+        last_block: Some(5),
+    };
+    checkpoints
+        .save_checkpoint(SyncLane::Live, live)
+        .await
+        .unwrap();
+
+    let status = admin::status(&synchronizer, &blocks, &checkpoints, &sync_config())
+        .await
+        .unwrap();
+
+    assert_eq!(status.chain_head, 10);
+    assert_eq!(
+        status.indexed,
+        Some(IndexedRange {
+            first_block: 1,
+            last_block: 5,
+            block_count: 4,
+        })
+    );
+    assert_eq!(status.gaps, vec![(3, 3)]);
+    assert_eq!(status.missing_blocks(), 1);
+    assert_eq!(status.lag(), Some(5));
+    assert_eq!(status.live, Some(live));
+    assert_eq!(status.backfill, None);
+    assert!(status.to_string().contains("Lane backfill: no checkpoint"));
+}
@@ -0,0 +1,386 @@
+mod support;
+
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicU64, Arc},
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use chain_watcher::{
+    clients::{blockchain_client::BlockchainClient, redis_client::StreamIdMode},
+    services::{
+        admin_api::AdminApi,
+        control::{ChainControl, ChainRequest},
+        mempool::watch_mempool,
+        spool::{replay_spool, Spool},
+    },
+};
+use common::types::{FinalityStatus, StreamEnvelope, StreamPayload, SyncLane};
+use ethers::types::U256;
+use mock_node::{
+    chain::{MockChain, MockTransaction},
+    events::{address, Event},
+    server::{MockNode, ServerHandle},
+};
+use serde_json::{json, Value};
+use support::{serve, sync_config, synchronizer, BlockStore, StreamSink};
+use tower::ServiceExt;
+
+const TOKEN: &str = "secret";
+
+fn admin_api() -> (Arc<ChainControl>, Router) {
+    admin_api_of(ChainControl::default())
+}
+
+fn admin_api_of(control: ChainControl) -> (Arc<ChainControl>, Router) {
+    let control = Arc::new(control);
+    let router = AdminApi::new(HashMap::from([(1, control.clone())]), TOKEN).router();
+    (control, router)
+}
+
+async fn pause(router: &Router) {
+    let (status, _) =
+        call(router, Method::POST, "/chains/1/pause", Some(TOKEN), None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+async fn resume(router: &Router) {
+    let (status, _) =
+        call(router, Method::POST, "/chains/1/resume", Some(TOKEN), None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+async fn call(
+    router: &Router,
+    method: Method,
+    uri: &str,
+    token: Option<&str>,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let request = match body {
+        Some(body) => request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Blocks 1 to 4, each with an ERC-721 transfer, behind `latency`.
+async fn serve_chain(latency: Duration) -> (MockNode, ServerHandle) {
+    let mut chain = MockChain::new(1);
+    for block in 1..=4 {
+        chain.mine(vec![MockTransaction::new(address(1), address(10))
+            .with_event(Event::Erc721Transfer {
+                contract: address(10),
+                from: address(1),
+                to: address(2),
+                token_id: U256::from(block),
+            })]);
+    }
+    let (node, server) = serve(chain).await;
+    node.set_latency(latency);
+    (node, server)
+}
+
+#[tokio::test]
+async fn requires_the_bearer_token() {
+    let (_control, router) = admin_api();
+
+    let (status, _) = call(&router, Method::GET, "/chains/1", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = call(
+        &router,
+        Method::POST,
+        "/chains/1/pause",
+        Some("guess"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        body["error"],
+        "Admin API Error: missing or invalid bearer token"
+    );
+
+    let (status, body) = call(&router, Method::GET, "/chains/1", Some(TOKEN), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["chain_id"], 1);
+    assert_eq!(body["paused"], false);
+    let (status, _) = call(&router, Method::GET, "/chains/2", Some(TOKEN), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn pauses_publishing_until_resumed() {
+    let (control, router) = admin_api();
+    let (_node, server) = serve_chain(Duration::ZERO).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let sink = StreamSink::default();
+    let blocks = BlockStore::default();
+    let synchronizer =
+        synchronizer(client, sink.clone(), blocks.clone()).with_control(control.clone());
+
+    let (status, body) =
+        call(&router, Method::POST, "/chains/1/pause", Some(TOKEN), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["paused"], true);
+    assert_eq!(body["metrics"]["paused"], 1);
+
+    let sync = tokio::spawn(async move { synchronizer.sync(1, 4).await });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(sink.envelopes.lock().unwrap().is_empty());
+    assert!(blocks.block_numbers().is_empty());
+
+    let (status, body) =
+        call(&router, Method::POST, "/chains/1/resume", Some(TOKEN), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["paused"], false);
+    sync.await.unwrap().unwrap();
+    assert_eq!(blocks.block_numbers(), vec![1, 2, 3, 4]);
+    assert_eq!(sink.envelopes.lock().unwrap().len(), 4);
+    assert_eq!(control.metrics.snapshot().admin_operations, 2);
+}
+
+#[tokio::test]
+async fn lists_in_flight_blocks_within_the_worker_limit() {
+    let (control, router) = admin_api();
+    let (_node, server) = serve_chain(Duration::from_millis(500)).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let synchronizer = synchronizer(client, StreamSink::default(), BlockStore::default())
+        .with_lane(SyncLane::Backfill)
+        .with_control(control.clone());
+
+    let (status, _) = call(
+        &router,
+        Method::PUT,
+        "/chains/1/workers",
+        Some(TOKEN),
+        Some(json!({ "workers": 0 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = call(
+        &router,
+        Method::PUT,
+        "/chains/1/workers",
+        Some(TOKEN),
+        Some(json!({ "workers": 2 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["worker_limit"], 2);
+
+    let sync = tokio::spawn(async move { synchronizer.sync(1, 4).await });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let (status, body) = call(
+        &router,
+        Method::GET,
+        "/chains/1/in-flight",
+        Some(TOKEN),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let in_flight: Vec<(u64, String)> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|block| {
+            (
+                block["block_number"].as_u64().unwrap(),
+                block["lane"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        in_flight,
+        vec![(1, "backfill".to_string()), (2, "backfill".to_string())]
+    );
+    assert_eq!(control.metrics.snapshot().blocks_in_flight, 2);
+
+    sync.await.unwrap().unwrap();
+    assert!(control.in_flight().is_empty());
+    assert_eq!(control.metrics.snapshot().blocks_in_flight, 0);
+
+    let (_, body) = call(
+        &router,
+        Method::PUT,
+        "/chains/1/workers",
+        Some(TOKEN),
+        Some(json!({ "workers": null })),
+    )
+    .await;
+    assert_eq!(body["worker_limit"], Value::Null);
+}
+
+#[tokio::test]
+async fn queues_backfills_and_rewinds_for_the_runner() {
+    let (control, router) = admin_api();
+
+    let (status, _) = call(
+        &router,
+        Method::POST,
+        "/chains/1/backfill",
+        Some(TOKEN),
+        Some(json!({ "from": 5, "to": 2 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = call(
+        &router,
+        Method::POST,
+        "/chains/1/backfill",
+        Some(TOKEN),
+        Some(json!({ "from": 2, "to": 5 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let (status, _) = call(
+        &router,
+        Method::POST,
+        "/chains/1/rewind",
+        Some(TOKEN),
+        Some(json!({ "to": 3 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    assert_eq!(
+        control.next_request().await,
+        ChainRequest::Backfill { from: 2, to: 5 }
+    );
+    assert_eq!(control.next_request().await, ChainRequest::Rewind { to: 3 });
+    assert_eq!(control.metrics.snapshot().admin_operations, 2);
+}
+
+#[tokio::test]
+async fn refuses_backfills_and_rewinds_with_block_tx_ids() {
+    let (control, router) =
+        admin_api_of(ChainControl::default().with_stream_id_mode(StreamIdMode::BlockTx));
+
+    let (status, body) = call(
+        &router,
+        Method::POST,
+        "/chains/1/backfill",
+        Some(TOKEN),
+        Some(json!({ "from": 2, "to": 5 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        body["error"],
+        "Admin API Error: backfill cannot be used with --stream-id-mode block-tx"
+    );
+    let (status, _) = call(
+        &router,
+        Method::POST,
+        "/chains/1/rewind",
+        Some(TOKEN),
+        Some(json!({ "to": 3 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let queued =
+        tokio::time::timeout(Duration::from_millis(100), control.next_request()).await;
+    assert!(queued.is_err());
+    assert_eq!(control.metrics.snapshot().admin_operations, 0);
+}
+
+#[tokio::test]
+async fn pauses_spool_replay_until_resumed() {
+    let (control, router) = admin_api();
+    let dir = std::env::temp_dir()
+        .join(format!("chain-watcher-admin-spool-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let spool = Arc::new(
+        Spool::open(dir.clone(), 1 << 20, control.metrics.clone())
+            .await
+            .unwrap(),
+    );
+    let envelope = StreamEnvelope::new(
+        1,
+        1,
+        None,
+        FinalityStatus::Unfinalized,
+        "test".to_string(),
+        StreamPayload::Logs(Vec::new()),
+    );
+    spool.append(&[envelope]).await.unwrap();
+
+    pause(&router).await;
+    let sink = StreamSink::default();
+    let replay = tokio::spawn(replay_spool(
+        spool.clone(),
+        sink.clone(),
+        "stream".to_string(),
+        10,
+        Arc::new(AtomicU64::new(0)),
+        false,
+        control.clone(),
+    ));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(sink.envelopes.lock().unwrap().is_empty());
+    assert!(!spool.is_empty().await);
+
+    resume(&router).await;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !spool.is_empty().await {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+    replay.abort();
+    assert_eq!(sink.envelopes.lock().unwrap().len(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn keeps_the_mempool_disconnected_while_paused() {
+    let (control, router) = admin_api();
+    let (node, server) = serve_chain(Duration::ZERO).await;
+    let ws_url = server.ws_url();
+
+    pause(&router).await;
+    let watcher = tokio::spawn({
+        let control = control.clone();
+        async move {
+            watch_mempool(&ws_url, &StreamSink::default(), &sync_config(), &control).await
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(node.requests().is_empty());
+
+    resume(&router).await;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !node.requests().iter().any(|method| method == "eth_chainId") {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+    watcher.abort();
+}
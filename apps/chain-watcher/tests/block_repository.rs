@@ -0,0 +1,263 @@
+mod support;
+
+use std::sync::Arc;
+
+use chain_watcher::services::repositories::{
+    block::{Block, BlockRepository, BlockRepositoryTrait},
+    log::{LogRepository, LogRepositoryTrait},
+    transaction::{TransactionRecord, TransactionRepository, TransactionRepositoryTrait},
+};
+use ethers::types::{Log, H256, U256, U64};
+use sqlx::{Executor, PgPool};
+use support::{chain_config_with_id, database_pool};
+
+fn block(chain_id: u32, block_number: u64, hash: &str) -> Block {
+    Block {
+        block_number,
+        chain_id,
+        hash: Some(hash.to_string()),
+        parent_hash: "0x00".to_string(),
+        timestamp: 1_700_000_000,
+        miner: None,
+        gas_used: "21000".to_string(),
+        gas_limit: "30000000".to_string(),
+        base_fee_per_gas: Some("7".to_string()),
+        transaction_count: 1,
+        log_count: 0,
+    }
+}
+
+/// A repository for `chain_id`, with the chain's blocks deleted.
+async fn block_repository(database_pool: &Arc<PgPool>, chain_id: u32) -> BlockRepository {
+    sqlx::query("DELETE FROM block WHERE chain_id = $1")
+        .bind(chain_id as i32)
+        .execute(&**database_pool)
+        .await
+        .unwrap();
+    BlockRepository::new(database_pool.clone(), chain_config_with_id(chain_id))
+}
+
+/// Block number, hash, gas used, gas limit and base fee of each of the
+/// chain's blocks.
+async fn stored(
+    database_pool: &PgPool,
+    chain_id: u32,
+) -> Vec<(i64, Option<String>, String, String, Option<String>)> {
+    sqlx::query_as(
+        "SELECT block_number, hash, gas_used::TEXT, gas_limit::TEXT, base_fee_per_gas::TEXT FROM block WHERE chain_id = $1 ORDER BY block_number",
+    )
+    .bind(chain_id as i32)
+    .fetch_all(database_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn reinserting_a_block_replaces_its_row() {
+    let database_pool = database_pool().await;
+    let repository = block_repository(&database_pool, 34_001).await;
+
+    repository
+        .insert_block(block(34_001, 9, "0xaa"))
+        .await
+        .unwrap();
+    // The same height after a reorg.
+    let reorged = Block {
+        gas_used: "42000".to_string(),
+        base_fee_per_gas: None,
+        ..block(34_001, 9, "0xbb")
+    };
+    repository.insert_block(reorged).await.unwrap();
+
+    assert_eq!(
+        stored(&database_pool, 34_001).await,
+        vec![(
+            9,
+            Some("0xbb".to_string()),
+            "42000".to_string(),
+            "30000000".to_string(),
+            None
+        )]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn keeps_the_last_copy_of_a_block_in_a_batch() {
+    let database_pool = database_pool().await;
+    let repository = block_repository(&database_pool, 34_002).await;
+
+    repository
+        .insert_blocks_bulk(&[
+            block(34_002, 1, "0xaa"),
+            block(34_002, 2, "0xbb"),
+            block(34_002, 1, "0xcc"),
+        ])
+        .await
+        .unwrap();
+
+    let hashes: Vec<(i64, Option<String>)> = stored(&database_pool, 34_002)
+        .await
+        .into_iter()
+        .map(|(block_number, hash, _, _, _)| (block_number, hash))
+        .collect();
+    assert_eq!(
+        hashes,
+        vec![(1, Some("0xcc".to_string())), (2, Some("0xbb".to_string()))]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn stores_gas_beyond_64_bits() {
+    let database_pool = database_pool().await;
+    let repository = block_repository(&database_pool, 34_003).await;
+    let base_fee = U256::MAX.to_string();
+
+    repository
+        .insert_block(Block {
+            gas_limit: (U256::from(u64::MAX) + 1).to_string(),
+            base_fee_per_gas: Some(base_fee.clone()),
+            ..block(34_003, 1, "0xaa")
+        })
+        .await
+        .unwrap();
+
+    let (_, _, _, gas_limit, stored_base_fee) =
+        stored(&database_pool, 34_003).await.remove(0);
+    assert_eq!(gas_limit, "18446744073709551616");
+    assert_eq!(stored_base_fee, Some(base_fee));
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn rejects_duplicate_block_numbers() {
+    let database_pool = database_pool().await;
+    block_repository(&database_pool, 34_004).await;
+    let insert = "INSERT INTO block (chain_id, block_number) VALUES (34004, 1)";
+
+    database_pool.execute(insert).await.unwrap();
+    let error = database_pool.execute(insert).await.unwrap_err();
+
+    let constraint = error
+        .as_database_error()
+        .and_then(|error| error.constraint())
+        .map(str::to_string);
+    assert_eq!(
+        constraint.as_deref(),
+        Some("block_chain_id_block_number_key")
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn migration_drops_duplicate_blocks() {
+    let database_pool = database_pool().await;
+    // Replays the migrations on a scratch schema, rolled back afterwards.
+    let mut transaction = database_pool.begin().await.unwrap();
+    transaction
+        .execute(
+            "CREATE SCHEMA block_migration; SET LOCAL search_path TO block_migration",
+        )
+        .await
+        .unwrap();
+    transaction
+        .execute(include_str!(
+            "../../../migrations/20240317162941_create_initial_schema.sql"
+        ))
+        .await
+        .unwrap();
+    transaction
+        .execute(
+            "INSERT INTO block (chain_id, block_number) VALUES (1, 1), (1, 1), (1, 2), (2, 1), (1, 2)",
+        )
+        .await
+        .unwrap();
+
+    transaction
+        .execute(include_str!(
+            "../../../migrations/20240418110742_extend_block_table.sql"
+        ))
+        .await
+        .unwrap();
+
+    let blocks: Vec<(i32, i32, i64)> =
+        sqlx::query_as("SELECT id, chain_id, block_number FROM block ORDER BY id")
+            .fetch_all(&mut *transaction)
+            .await
+            .unwrap();
+    assert_eq!(blocks, vec![(1, 1, 1), (3, 1, 2), (4, 2, 1)]);
+    transaction.rollback().await.unwrap();
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn deletes_transactions_and_logs_with_the_blocks() {
+    let database_pool = database_pool().await;
+    let chain_id = 34_005;
+    let repository = block_repository(&database_pool, chain_id).await;
+    let config = chain_config_with_id(chain_id);
+    let transactions = TransactionRepository::new(database_pool.clone(), config.clone());
+    let logs = LogRepository::new(database_pool.clone(), config);
+    for table in ["transaction", "log"] {
+        sqlx::query(&format!("DELETE FROM {} WHERE chain_id = $1", table))
+            .bind(chain_id as i32)
+            .execute(&*database_pool)
+            .await
+            .unwrap();
+    }
+
+    for block_number in 1..=3u64 {
+        repository
+            .insert_block(block(chain_id, block_number, "0xaa"))
+            .await
+            .unwrap();
+        transactions
+            .insert_transactions(&[TransactionRecord {
+                chain_id,
+                block_number,
+                block_hash: None,
+                hash: format!("0x{:064x}", block_number),
+                tx_index: 0,
+                from: "0x0000000000000000000000000000000000000001".to_string(),
+                to: None,
+                value: "0".to_string(),
+                nonce: "0".to_string(),
+                input_selector: None,
+                gas_used: None,
+                effective_gas_price: None,
+                status: Some(1),
+                contract_address: None,
+                transaction_type: None,
+            }])
+            .await
+            .unwrap();
+        logs.archive_logs(&[Log {
+            address: mock_node::events::address(10),
+            block_hash: Some(H256::repeat_byte(0xaa)),
+            block_number: Some(U64::from(block_number)),
+            transaction_hash: Some(H256::from_low_u64_be(block_number)),
+            transaction_index: Some(U64::zero()),
+            log_index: Some(U256::zero()),
+            removed: Some(false),
+            ..Default::default()
+        }])
+        .await
+        .unwrap();
+    }
+
+    assert_eq!(repository.delete_blocks_above(1).await.unwrap(), 2);
+
+    for table in ["block", "transaction", "log"] {
+        let block_numbers: Vec<(i64,)> = sqlx::query_as(&format!(
+            "SELECT block_number FROM {} WHERE chain_id = $1 ORDER BY block_number",
+            table
+        ))
+        .bind(chain_id as i32)
+        .fetch_all(&*database_pool)
+        .await
+        .unwrap();
+        assert_eq!(block_numbers, vec![(1,)], "{} rows", table);
+    }
+}
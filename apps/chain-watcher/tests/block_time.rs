@@ -0,0 +1,148 @@
+mod support;
+
+use std::collections::HashMap;
+
+use chain_watcher::{
+    clients::blockchain_client::BlockchainClient,
+    config::parse_timestamp,
+    services::block_time::{first_block_at, resolve_block_range},
+};
+use mock_node::{
+    chain::{MockChain, BLOCK_INTERVAL, GENESIS_TIMESTAMP},
+    server::{MockNode, ServerHandle},
+};
+use support::{serve, sync_config, synchronizer, BlockStore, BlockTimeStore, StreamSink};
+
+/// A chain of 100 empty blocks.
+async fn serve_chain() -> (MockNode, ServerHandle, BlockchainClient) {
+    let mut chain = MockChain::new(1);
+    chain.mine_empty(100);
+    let (node, server) = serve(chain).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    (node, server, client)
+}
+
+fn mined_at(block_number: u64) -> u64 {
+    GENESIS_TIMESTAMP + block_number * BLOCK_INTERVAL
+}
+
+#[tokio::test]
+async fn finds_the_first_block_mined_at_or_after_a_time() {
+    let (_node, _server, client) = serve_chain().await;
+    let store = BlockTimeStore::default();
+
+    for (timestamp, expected) in [
+        (0, 0),
+        (mined_at(0), 0),
+        (mined_at(10), 10),
+        (mined_at(10) + 1, 11),
+        (mined_at(99) + 5, 100),
+        (mined_at(100), 100),
+    ] {
+        let block_number = first_block_at(&client, &store, timestamp, 64)
+            .await
+            .unwrap();
+        assert_eq!(block_number, expected, "timestamp {}", timestamp);
+    }
+    assert!(first_block_at(&client, &store, mined_at(100) + 1, 64)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn caches_only_final_blocks() {
+    let (node, _server, client) = serve_chain().await;
+    let store = BlockTimeStore::default();
+
+    assert_eq!(
+        first_block_at(&client, &store, mined_at(10), 64)
+            .await
+            .unwrap(),
+        10
+    );
+    assert_eq!(
+        first_block_at(&client, &store, mined_at(90), 64)
+            .await
+            .unwrap(),
+        90
+    );
+    assert_eq!(
+        *store.blocks.lock().unwrap(),
+        HashMap::from([(mined_at(10), 10)])
+    );
+
+    let requests = node.requests().len();
+    assert_eq!(
+        first_block_at(&client, &store, mined_at(10), 64)
+            .await
+            .unwrap(),
+        10
+    );
+    assert_eq!(node.requests().len(), requests);
+}
+
+#[tokio::test]
+async fn syncs_the_blocks_mined_between_two_times() {
+    let (_node, _server, client) = serve_chain().await;
+    let mut config = sync_config();
+    config.start_time = Some(mined_at(20) - 1);
+    config.end_time = Some(mined_at(30) + 1);
+
+    resolve_block_range(&client, &BlockTimeStore::default(), &mut config)
+        .await
+        .unwrap();
+    assert_eq!(config.start_block, Some(20));
+    assert_eq!(config.end_block, Some(30));
+
+    let blocks = BlockStore::default();
+    synchronizer(client.clone(), StreamSink::default(), blocks.clone())
+        .sync(20, 30)
+        .await
+        .unwrap();
+    let mut indexed: Vec<u64> = blocks
+        .blocks
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|block| block.block_number)
+        .collect();
+    indexed.sort_unstable();
+    assert_eq!(indexed, (20..=30).collect::<Vec<_>>());
+
+    config.end_time = Some(GENESIS_TIMESTAMP - 1);
+    assert!(
+        resolve_block_range(&client, &BlockTimeStore::default(), &mut config)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn ends_at_the_chain_head_without_a_later_block() {
+    let (_node, _server, client) = serve_chain().await;
+
+    for end_time in [mined_at(100), mined_at(100) + 1, u64::MAX] {
+        let mut config = sync_config();
+        config.end_time = Some(end_time);
+        resolve_block_range(&client, &BlockTimeStore::default(), &mut config)
+            .await
+            .unwrap();
+
+        assert_eq!(config.end_block, Some(100), "end time {}", end_time);
+    }
+}
+
+#[test]
+fn parses_timestamps_dates_and_rfc_3339_times() {
+    assert_eq!(parse_timestamp("1709424000"), Ok(1_709_424_000));
+    assert_eq!(parse_timestamp("2024-03-03"), Ok(1_709_424_000));
+    assert_eq!(
+        parse_timestamp("2024-03-03T12:00:00+01:00"),
+        Ok(1_709_463_600)
+    );
+    assert_eq!(parse_timestamp("2024-03-03T11:00:00Z"), Ok(1_709_463_600));
+    assert!(parse_timestamp("March 3rd").is_err());
+    assert!(parse_timestamp("1969-12-31").is_err());
+}
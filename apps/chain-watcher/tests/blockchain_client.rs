@@ -0,0 +1,109 @@
+mod support;
+
+use chain_watcher::clients::blockchain_client::{
+    BlockchainClient, BlockchainClientTrait, ChainVerificationError,
+};
+use mock_node::chain::MockChain;
+use support::serve;
+
+/// A URL nothing listens on.
+const UNREACHABLE_URL: &str = "http://127.0.0.1:1";
+
+fn chain(chain_id: u64) -> MockChain {
+    let mut chain = MockChain::new(chain_id);
+    chain.mine_empty(3);
+    chain
+}
+
+#[tokio::test]
+async fn connects_to_the_first_provider_serving_the_chain() {
+    let (_other, other_server) = serve(chain(2)).await;
+    let (_node, server) = serve(chain(1)).await;
+
+    let client = BlockchainClient::connect(
+        &[
+            UNREACHABLE_URL.to_string(),
+            other_server.http_url(),
+            server.http_url(),
+        ],
+        1,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(client.active_url(), server.http_url());
+    assert_eq!(client.get_block_number().await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn refuses_to_connect_to_another_chain() {
+    let (_other, other_server) = serve(chain(2)).await;
+
+    let error = BlockchainClient::connect(
+        &[UNREACHABLE_URL.to_string(), other_server.http_url()],
+        1,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    // The mismatch is reported rather than the unreachable provider.
+    assert!(matches!(
+        error.downcast_ref::<ChainVerificationError>(),
+        Some(ChainVerificationError::ChainIdMismatch(1, 2, _))
+    ));
+}
+
+#[tokio::test]
+async fn fails_over_past_providers_serving_another_chain() {
+    let (failing, failing_server) = serve(chain(1)).await;
+    let (other, other_server) = serve(chain(1)).await;
+    let (_healthy, healthy_server) = serve(chain(1)).await;
+    let client = BlockchainClient::connect(
+        &[
+            failing_server.http_url(),
+            other_server.http_url(),
+            healthy_server.http_url(),
+        ],
+        1,
+    )
+    .await
+    .unwrap();
+
+    other.update(|chain| *chain = MockChain::new(2));
+    failing.fail_every(1);
+
+    assert_eq!(client.get_block_number().await.unwrap(), 3);
+    assert_eq!(client.active_url(), healthy_server.http_url());
+
+    // The rejected provider is not verified again.
+    let verified = other.requests().len();
+    failing.fail_every(0);
+    client.get_block_number().await.unwrap();
+    assert_eq!(other.requests().len(), verified);
+}
+
+#[tokio::test]
+async fn fails_over_from_a_rejected_active_provider() {
+    let (active, active_server) = serve(chain(1)).await;
+    let (standby, standby_server) = serve(chain(1)).await;
+    let client = BlockchainClient::connect(
+        &[active_server.http_url(), standby_server.http_url()],
+        1,
+    )
+    .await
+    .unwrap();
+
+    // The active provider fails and now serves another chain, while the
+    // standby one is down.
+    active.update(|chain| *chain = MockChain::new(2));
+    active.fail_next(1);
+    standby.fail_every(1);
+    let error = client.get_block_number().await.unwrap_err();
+    assert!(error.to_string().contains("No Verified Provider"));
+    assert_eq!(client.active_url(), active_server.http_url());
+
+    standby.fail_every(0);
+    assert_eq!(client.get_block_number().await.unwrap(), 3);
+    assert_eq!(client.active_url(), standby_server.http_url());
+}
@@ -0,0 +1,153 @@
+use std::io::Write;
+
+use chain_watcher::config::{
+    parse_timestamp, ChainWatcherArgs, Command, Config, ConfigError,
+};
+use clap::Parser;
+use tempfile::NamedTempFile;
+
+/// Parses the required options followed by `extra`.
+fn config(extra: &[&str]) -> Result<Config, ConfigError> {
+    let mut args = vec![
+        "chain_watcher",
+        "--redis-url",
+        "redis://localhost",
+        "--redis-group-name",
+        "group",
+        "--db-url",
+        "postgres://localhost",
+    ];
+    args.extend_from_slice(extra);
+    Config::from_args(ChainWatcherArgs::try_parse_from(args).unwrap())
+}
+
+fn watch_file(content: &str) -> NamedTempFile {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(content.as_bytes()).unwrap();
+    file
+}
+
+#[test]
+fn builds_a_chain_from_the_command_line() {
+    let config = config(&[
+        "--rpc",
+        "http://a, http://b,",
+        "--redis-stream-key",
+        "ethereum-logs",
+        "--start-block",
+        "5",
+    ])
+    .unwrap();
+
+    assert_eq!(config.command, Command::Sync);
+    assert_eq!(config.chains.len(), 1);
+    let chain = &config.chains[0];
+    assert_eq!(chain.chain.id, 1);
+    assert_eq!(chain.rpc, vec!["http://a", "http://b"]);
+    assert_eq!(chain.redis_config.stream_key, "ethereum-logs");
+    assert_eq!(chain.pending_stream_key(), "ethereum-logs:pending");
+    assert_eq!(chain.start_block, Some(5));
+}
+
+#[test]
+fn requires_a_stream_key_without_a_watch_file() {
+    let error = config(&["--rpc", "http://a"]).err().unwrap();
+
+    assert!(matches!(error, ConfigError::MissingOption(_)));
+}
+
+#[test]
+fn reads_chains_from_the_watch_file() {
+    let file = watch_file(
+        r#"[
+            { "chain_id": 1, "rpc": "http://a", "redis_stream_key": "ethereum-logs", "start_time": "2024-03-03" },
+            { "chain_id": 137, "rpc": "http://b", "redis_stream_key": "polygon-logs" }
+        ]"#,
+    );
+
+    let config = config(&["--watch-file", file.path().to_str().unwrap()]).unwrap();
+
+    let chains: Vec<(u32, &str)> = config
+        .chains
+        .iter()
+        .map(|chain| (chain.chain.id, chain.redis_config.stream_key.as_str()))
+        .collect();
+    assert_eq!(chains, vec![(1, "ethereum-logs"), (137, "polygon-logs")]);
+    assert_eq!(config.chains[0].start_time, Some(1_709_424_000));
+}
+
+#[test]
+fn rejects_chains_listed_twice() {
+    let file = watch_file(
+        r#"[
+            { "chain_id": 1, "rpc": "http://a", "redis_stream_key": "first" },
+            { "chain_id": 1, "rpc": "http://b", "redis_stream_key": "second" }
+        ]"#,
+    );
+
+    let error = config(&["--watch-file", file.path().to_str().unwrap()])
+        .err()
+        .unwrap();
+
+    assert!(matches!(error, ConfigError::DuplicateChain(1)));
+}
+
+#[test]
+fn rejects_a_watched_chain_with_a_start_block_and_time() {
+    let file = watch_file(
+        r#"[{ "chain_id": 1, "rpc": "http://a", "redis_stream_key": "logs", "start_block": 1, "start_time": 1 }]"#,
+    );
+
+    let error = config(&["--watch-file", file.path().to_str().unwrap()])
+        .err()
+        .unwrap();
+
+    assert!(matches!(error, ConfigError::ParseError(_, _)));
+}
+
+#[test]
+fn sharding_needs_an_end_block() {
+    let error = config(&[
+        "--rpc",
+        "http://a",
+        "--redis-stream-key",
+        "logs",
+        "--shard-backfill",
+    ])
+    .err()
+    .unwrap();
+
+    assert!(matches!(error, ConfigError::MissingEndBlock(1)));
+}
+
+#[test]
+fn refuses_to_republish_with_block_tx_ids() {
+    let block_tx = [
+        "--rpc",
+        "http://a",
+        "--redis-stream-key",
+        "logs",
+        "--stream-id-mode",
+        "block-tx",
+    ];
+    for command in [
+        &["rewind", "--to", "5"][..],
+        &["backfill", "--from", "5", "--to", "10"][..],
+    ] {
+        let error = config(&[&block_tx[..], command].concat()).err().unwrap();
+
+        assert!(matches!(error, ConfigError::IncompatibleOptions(_)));
+    }
+}
+
+#[test]
+fn parses_timestamps() {
+    assert_eq!(parse_timestamp("1709424000"), Ok(1_709_424_000));
+    assert_eq!(parse_timestamp("2024-03-03"), Ok(1_709_424_000));
+    assert_eq!(
+        parse_timestamp("2024-03-03T01:00:00+01:00"),
+        Ok(1_709_424_000)
+    );
+    assert!(parse_timestamp("yesterday").is_err());
+    assert!(parse_timestamp("1969-12-31").is_err());
+}
@@ -0,0 +1,68 @@
+use chain_watcher::services::integrity::{verify_receipts, IntegrityError};
+use ethers::types::{Bloom, U256};
+use mock_node::{
+    chain::{MockChain, MockTransaction},
+    events::{address, Event},
+};
+
+/// A block with two transfers and a transaction without logs.
+fn chain() -> MockChain {
+    let transfer = |token_id: u64| {
+        MockTransaction::new(address(1), address(10)).with_event(Event::Erc721Transfer {
+            contract: address(10),
+            from: address(1),
+            to: address(2),
+            token_id: U256::from(token_id),
+        })
+    };
+    let mut chain = MockChain::new(1);
+    chain.mine(vec![
+        transfer(1),
+        MockTransaction::new(address(1), address(2)),
+        transfer(2),
+    ]);
+    chain
+}
+
+#[test]
+fn accepts_complete_receipts() {
+    let chain = chain();
+    let block = chain.block(1).unwrap();
+    assert_eq!(verify_receipts(&block.block, &block.receipts), Ok(()));
+
+    let empty = chain.block(0).unwrap();
+    assert_eq!(verify_receipts(&empty.block, &[]), Ok(()));
+}
+
+#[test]
+fn rejects_missing_or_altered_receipts() {
+    let chain = chain();
+    let block = chain.block(1).unwrap();
+
+    let missing = &block.receipts[..2];
+    assert_eq!(
+        verify_receipts(&block.block, missing),
+        Err(IntegrityError::ReceiptCount(1, 3, 2))
+    );
+
+    let mut without_logs = block.receipts.clone();
+    without_logs[2].logs.clear();
+    assert!(matches!(
+        verify_receipts(&block.block, &without_logs),
+        Err(IntegrityError::ReceiptsRoot(1, _, _))
+    ));
+
+    let mut swapped = block.receipts.clone();
+    swapped.swap(0, 2);
+    assert!(matches!(
+        verify_receipts(&block.block, &swapped),
+        Err(IntegrityError::ReceiptsRoot(1, _, _))
+    ));
+
+    let mut header = block.block.clone();
+    header.logs_bloom = Some(Bloom::zero());
+    assert_eq!(
+        verify_receipts(&header, &block.receipts),
+        Err(IntegrityError::LogsBloom(1))
+    );
+}
@@ -0,0 +1,156 @@
+mod support;
+
+use chain_watcher::{
+    clients::blockchain_client::BlockchainClient,
+    config::ChainSyncConfig,
+    services::{
+        lanes::run_lanes,
+        repositories::checkpoint::{CheckpointRepositoryTrait, SyncCheckpoint},
+        sync::ChainSynchronizer,
+    },
+};
+use common::types::SyncLane;
+use mock_node::{
+    chain::{MockChain, MockTransaction},
+    events::address,
+};
+use support::{
+    serve, sync_config, BlockStore, CheckpointStore, Discard, FlakyStore, StreamSink,
+};
+
+#[tokio::test]
+async fn fills_blocks_the_live_lane_skipped() {
+    let mut chain = MockChain::new(1);
+    chain.mine_empty(10);
+    let (_node, server) = serve(chain).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let config = ChainSyncConfig {
+        start_block: Some(1),
+        end_block: Some(10),
+        ..sync_config()
+    };
+    let blocks = BlockStore::default();
+    let synchronizer = |lane| {
+        ChainSynchronizer::new(
+            client.clone(),
+            StreamSink::default(),
+            blocks.clone(),
+            Discard,
+            Discard,
+            config.clone(),
+        )
+        .with_lane(lane)
+    };
+    let live = synchronizer(SyncLane::Live);
+    let backfill = synchronizer(SyncLane::Backfill);
+
+    // The backfill lane finished at block 5, and the live lane, which
+    // started at block 6, went past block 7 without indexing it.
+    backfill.sync(1, 5).await.unwrap();
+    live.sync(6, 6).await.unwrap();
+    live.sync(8, 8).await.unwrap();
+    let checkpoints = CheckpointStore::default();
+    checkpoints
+        .save_checkpoint(
+            SyncLane::Backfill,
+            SyncCheckpoint {
+                start_block: 1,
+                last_block: Some(5),
+            },
+        )
+        .await
+        .unwrap();
+    checkpoints
+        .save_checkpoint(
+            SyncLane::Live,
+            SyncCheckpoint {
+                start_block: 6,
+                last_block: Some(8),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(blocks.block_numbers(), vec![1, 2, 3, 4, 5, 6, 8]);
+
+    run_lanes(&live, &backfill, &checkpoints, &blocks, &config)
+        .await
+        .unwrap();
+
+    assert_eq!(blocks.block_numbers(), (1..=10).collect::<Vec<u64>>());
+}
+
+#[tokio::test]
+async fn retries_blocks_the_live_lane_skipped() {
+    let mut chain = MockChain::new(1);
+    for _ in 0..10 {
+        chain.mine(vec![MockTransaction::new(address(1), address(2))]);
+    }
+    let (_node, server) = serve(chain.clone()).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    // The backfill lane's gap fill cannot fetch anything, so only the live
+    // lane can index the skipped block.
+    let (down, down_server) = serve(chain).await;
+    let down_client = BlockchainClient::connect(&[down_server.http_url()], 1)
+        .await
+        .unwrap();
+    down.fail_every(1);
+    let config = ChainSyncConfig {
+        start_block: Some(1),
+        end_block: Some(10),
+        store_transactions: true,
+        ..sync_config()
+    };
+    let blocks = BlockStore::default();
+    // Block 6's transactions cannot be stored the first time.
+    let live = ChainSynchronizer::new(
+        client,
+        StreamSink::default(),
+        blocks.clone(),
+        FlakyStore::failing(1),
+        Discard,
+        config.clone(),
+    )
+    .with_lane(SyncLane::Live);
+    let backfill = ChainSynchronizer::new(
+        down_client,
+        StreamSink::default(),
+        blocks.clone(),
+        FlakyStore::failing(0),
+        Discard,
+        config.clone(),
+    )
+    .with_lane(SyncLane::Backfill);
+    let checkpoints = CheckpointStore::default();
+    checkpoints
+        .save_checkpoint(
+            SyncLane::Backfill,
+            SyncCheckpoint {
+                start_block: 1,
+                last_block: Some(5),
+            },
+        )
+        .await
+        .unwrap();
+    checkpoints
+        .save_checkpoint(
+            SyncLane::Live,
+            SyncCheckpoint {
+                start_block: 6,
+                last_block: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    run_lanes(&live, &backfill, &checkpoints, &blocks, &config)
+        .await
+        .unwrap();
+
+    assert_eq!(blocks.block_numbers(), (6..=10).collect::<Vec<u64>>());
+    let live_checkpoint = checkpoints.get_checkpoint(SyncLane::Live).await.unwrap();
+    assert_eq!(live_checkpoint.unwrap().last_block, Some(10));
+}
@@ -0,0 +1,107 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use chain_watcher::services::{
+    leadership::{acquire_leadership, hold_leadership},
+    repositories::leader::{LeaderRepository, LeaderRepositoryTrait},
+};
+use sqlx::PgPool;
+use support::{chain_config_with_id, database_pool};
+
+/// Deletes the chain's leader, so each run starts over.
+async fn clear_leader(database_pool: &PgPool, chain_id: u32) {
+    sqlx::query("DELETE FROM chain_leader WHERE chain_id = $1")
+        .bind(chain_id as i32)
+        .execute(database_pool)
+        .await
+        .unwrap();
+}
+
+fn instance(
+    database_pool: &Arc<PgPool>,
+    chain_id: u32,
+    instance_id: &str,
+    lease_ttl: u64,
+) -> LeaderRepository {
+    LeaderRepository::new(
+        database_pool.clone(),
+        chain_config_with_id(chain_id),
+        instance_id.to_string(),
+        lease_ttl,
+    )
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn acquires_renews_and_loses_leadership() {
+    let database_pool = database_pool().await;
+    clear_leader(&database_pool, 33_001).await;
+    let leader = instance(&database_pool, 33_001, "instance-a", 60);
+    let standby = instance(&database_pool, 33_001, "instance-b", 60);
+
+    let token = leader.try_acquire().await.unwrap().unwrap();
+    assert_eq!(standby.try_acquire().await.unwrap(), None);
+    assert!(leader.renew(token).await.unwrap());
+
+    // Released leadership goes to the standby, under a greater token.
+    leader.release(token).await.unwrap();
+    let standby_token = standby.try_acquire().await.unwrap().unwrap();
+    assert!(standby_token > token);
+    assert!(!leader.renew(token).await.unwrap());
+    assert_eq!(leader.try_acquire().await.unwrap(), None);
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn loses_leadership_once_the_lease_expires() {
+    let database_pool = database_pool().await;
+    clear_leader(&database_pool, 33_002).await;
+    let stalled = instance(&database_pool, 33_002, "instance-a", 0);
+    let standby = instance(&database_pool, 33_002, "instance-b", 60);
+
+    let token = stalled.try_acquire().await.unwrap().unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let standby_token = standby.try_acquire().await.unwrap().unwrap();
+    assert_eq!(standby_token, token + 1);
+    assert!(!stalled.renew(token).await.unwrap());
+    // Releasing a lost leadership leaves the new leader's alone.
+    stalled.release(token).await.unwrap();
+    assert!(standby.renew(standby_token).await.unwrap());
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn holds_leadership_until_another_instance_takes_over() {
+    let database_pool = database_pool().await;
+    clear_leader(&database_pool, 33_003).await;
+    let leader = instance(&database_pool, 33_003, "instance-a", 1);
+    let standby = instance(&database_pool, 33_003, "instance-b", 1);
+
+    let token = acquire_leadership(&leader).await.unwrap();
+    let standing_by = tokio::spawn({
+        let standby = standby.clone();
+        async move { acquire_leadership(&standby).await.unwrap() }
+    });
+
+    // Renewals keep the standby out for several TTLs.
+    let held = tokio::time::timeout(
+        Duration::from_millis(2500),
+        hold_leadership(&leader, token),
+    )
+    .await;
+    assert!(held.is_err());
+    assert!(!standing_by.is_finished());
+
+    // Once released, the standby takes over and the old leader stops
+    // holding.
+    leader.release(token).await.unwrap();
+    let standby_token = tokio::time::timeout(Duration::from_secs(5), standing_by)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(standby_token > token);
+    tokio::time::timeout(Duration::from_secs(5), hold_leadership(&leader, token))
+        .await
+        .unwrap();
+}
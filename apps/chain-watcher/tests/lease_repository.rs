@@ -0,0 +1,112 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use chain_watcher::services::repositories::lease::{
+    LeaseRepository, LeaseRepositoryTrait,
+};
+use sqlx::PgPool;
+use support::{chain_config_with_id, database_pool};
+
+/// Deletes the chain's ranges, so each run starts over.
+async fn clear_ranges(database_pool: &PgPool, chain_id: u32) {
+    sqlx::query("DELETE FROM block_range_lease WHERE chain_id = $1")
+        .bind(chain_id as i32)
+        .execute(database_pool)
+        .await
+        .unwrap();
+}
+
+fn worker(
+    database_pool: &Arc<PgPool>,
+    chain_id: u32,
+    instance_id: &str,
+    lease_ttl: u64,
+) -> LeaseRepository {
+    LeaseRepository::new(
+        database_pool.clone(),
+        chain_config_with_id(chain_id),
+        instance_id.to_string(),
+        lease_ttl,
+    )
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn concurrent_workers_claim_different_ranges() {
+    let database_pool = database_pool().await;
+    clear_ranges(&database_pool, 32_001).await;
+    let first = worker(&database_pool, 32_001, "worker-a", 60);
+    let second = worker(&database_pool, 32_001, "worker-b", 60);
+    first.create_ranges(1, 200, 100).await.unwrap();
+    // Another instance creating the same ranges adds none.
+    second.create_ranges(1, 200, 100).await.unwrap();
+    assert_eq!(first.remaining_ranges().await.unwrap(), 2);
+
+    let (a, b) = tokio::join!(first.claim_range(), second.claim_range());
+    let (a, b) = (a.unwrap().unwrap(), b.unwrap().unwrap());
+    let mut claimed = vec![(a.start_block, a.end_block), (b.start_block, b.end_block)];
+    claimed.sort_unstable();
+    assert_eq!(claimed, vec![(1, 100), (101, 200)]);
+    assert!(!a.reclaimed && !b.reclaimed);
+
+    // Both ranges are held.
+    let (a, b) = tokio::join!(first.claim_range(), second.claim_range());
+    assert_eq!((a.unwrap(), b.unwrap()), (None, None));
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn takes_over_expired_leases() {
+    let database_pool = database_pool().await;
+    clear_ranges(&database_pool, 32_002).await;
+    let stalled = worker(&database_pool, 32_002, "worker-a", 0);
+    let standby = worker(&database_pool, 32_002, "worker-b", 60);
+    stalled.create_ranges(1, 100, 100).await.unwrap();
+
+    let lease = stalled.claim_range().await.unwrap().unwrap();
+    assert!(!lease.reclaimed);
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let taken_over = standby.claim_range().await.unwrap().unwrap();
+    assert_eq!(taken_over.start_block, lease.start_block);
+    assert!(taken_over.reclaimed);
+
+    // The stalled worker no longer holds the range.
+    assert!(!stalled.renew_lease(&lease).await.unwrap());
+    assert!(!stalled.complete_range(&lease).await.unwrap());
+    assert!(standby.renew_lease(&taken_over).await.unwrap());
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn completed_ranges_are_done() {
+    let database_pool = database_pool().await;
+    clear_ranges(&database_pool, 32_003).await;
+    let repository = worker(&database_pool, 32_003, "worker-a", 60);
+    repository.create_ranges(1, 150, 100).await.unwrap();
+
+    let lease = repository.claim_range().await.unwrap().unwrap();
+    assert_eq!((lease.start_block, lease.end_block), (1, 100));
+    assert!(repository.complete_range(&lease).await.unwrap());
+    assert_eq!(repository.remaining_ranges().await.unwrap(), 1);
+
+    let (status, owner, expires_at) =
+        sqlx::query_as::<_, (String, Option<String>, Option<String>)>(
+            "SELECT status, owner, expires_at::TEXT FROM block_range_lease WHERE chain_id = $1 AND start_block = 1",
+        )
+        .bind(32_003)
+        .fetch_one(&*database_pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "done");
+    assert_eq!(owner.as_deref(), Some("worker-a"));
+    assert_eq!(expires_at, None);
+
+    // A done range is never claimed again.
+    let lease = repository.claim_range().await.unwrap().unwrap();
+    assert_eq!((lease.start_block, lease.end_block), (101, 150));
+    assert!(repository.complete_range(&lease).await.unwrap());
+    assert_eq!(repository.claim_range().await.unwrap(), None);
+    assert_eq!(repository.remaining_ranges().await.unwrap(), 0);
+}
@@ -0,0 +1,119 @@
+mod support;
+
+use std::sync::Arc;
+
+use chain_watcher::services::repositories::log::{LogRepository, LogRepositoryTrait};
+use ethers::types::{Bytes, Log, H256, U256, U64};
+use sqlx::PgPool;
+use support::{chain_config_with_id, database_pool};
+
+fn log(block_number: u64, log_index: u64, block_hash: H256, data: &[u8]) -> Log {
+    Log {
+        address: mock_node::events::address(10),
+        topics: vec![H256::repeat_byte(0xdd)],
+        data: Bytes::from(data.to_vec()),
+        block_hash: Some(block_hash),
+        block_number: Some(U64::from(block_number)),
+        transaction_hash: Some(H256::repeat_byte(0x01)),
+        transaction_index: Some(U64::zero()),
+        log_index: Some(U256::from(log_index)),
+        removed: Some(false),
+        ..Default::default()
+    }
+}
+
+/// A repository for `chain_id`, with the chain's archived logs deleted.
+async fn log_repository(database_pool: &Arc<PgPool>, chain_id: u32) -> LogRepository {
+    sqlx::query("DELETE FROM log WHERE chain_id = $1")
+        .bind(chain_id as i32)
+        .execute(&**database_pool)
+        .await
+        .unwrap();
+    LogRepository::new(database_pool.clone(), chain_config_with_id(chain_id))
+}
+
+/// Block number, block hash, data, removed flag and partition of each of
+/// the chain's logs.
+async fn archived(
+    database_pool: &PgPool,
+    chain_id: u32,
+) -> Vec<(i64, Option<String>, String, bool, String)> {
+    sqlx::query_as(
+        "SELECT block_number, block_hash, data, removed, tableoid::regclass::text FROM log WHERE chain_id = $1 ORDER BY block_number, log_index",
+    )
+    .bind(chain_id as i32)
+    .fetch_all(database_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn archives_logs_into_block_range_partitions() {
+    let database_pool = database_pool().await;
+    let repository = log_repository(&database_pool, 31_001).await;
+    let hash = H256::repeat_byte(0xaa);
+
+    repository
+        .archive_logs(&[log(5, 0, hash, &[1]), log(2_000_005, 0, hash, &[2])])
+        .await
+        .unwrap();
+
+    let partitions: Vec<String> = archived(&database_pool, 31_001)
+        .await
+        .into_iter()
+        .map(|(_, _, _, _, partition)| partition)
+        .collect();
+    assert_eq!(partitions, vec!["log_p0", "log_p2000000"]);
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn replaces_logs_archived_before() {
+    let database_pool = database_pool().await;
+    let repository = log_repository(&database_pool, 31_002).await;
+
+    repository
+        .archive_logs(&[log(7, 0, H256::repeat_byte(0xaa), &[1])])
+        .await
+        .unwrap();
+    // The same log after a reorg, in a block of another hash.
+    let mut reorged = log(7, 0, H256::repeat_byte(0xbb), &[2]);
+    reorged.removed = Some(true);
+    repository.archive_logs(&[reorged]).await.unwrap();
+
+    assert_eq!(
+        archived(&database_pool, 31_002).await,
+        vec![(
+            7,
+            Some(format!("{:?}", H256::repeat_byte(0xbb))),
+            "0x02".to_string(),
+            true,
+            "log_p0".to_string()
+        )]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn shares_partitions_between_chains() {
+    let database_pool = database_pool().await;
+    let first = log_repository(&database_pool, 31_003).await;
+    let second = log_repository(&database_pool, 31_004).await;
+    let hash = H256::repeat_byte(0xaa);
+
+    first
+        .archive_logs(&[log(1_500_000, 0, hash, &[1])])
+        .await
+        .unwrap();
+    second
+        .archive_logs(&[log(1_500_000, 0, hash, &[2])])
+        .await
+        .unwrap();
+
+    for chain_id in [31_003, 31_004] {
+        let logs = archived(&database_pool, chain_id).await;
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].4, "log_p1000000");
+    }
+}
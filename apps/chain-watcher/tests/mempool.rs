@@ -0,0 +1,212 @@
+mod support;
+
+use chain_watcher::{
+    clients::blockchain_client::ChainVerificationError,
+    services::{
+        control::ChainControl,
+        mempool::{decode_transfer, watch_mempool, PendingTracker},
+    },
+};
+use common::types::{PendingStatus, TransferMethod};
+use ethers::{
+    abi::{self, Token},
+    types::{Bytes, Transaction, H160, H256, U256},
+    utils::id,
+};
+use mock_node::chain::MockChain;
+use support::{serve, sync_config, StreamSink};
+
+fn address(seed: u8) -> H160 {
+    H160::repeat_byte(seed)
+}
+
+fn call(signature: &str, arguments: &[Token]) -> Bytes {
+    let mut input = id(signature).to_vec();
+    input.extend(abi::encode(arguments));
+    input.into()
+}
+
+fn transaction(hash: u8, from: H160, nonce: u64, input: Bytes) -> Transaction {
+    Transaction {
+        hash: H256::repeat_byte(hash),
+        from,
+        to: Some(address(0xcc)),
+        nonce: U256::from(nonce),
+        input,
+        max_priority_fee_per_gas: Some(U256::from(2)),
+        ..Default::default()
+    }
+}
+
+fn transfer(hash: u8, from: H160, nonce: u64) -> Transaction {
+    let input = call(
+        "transferFrom(address,address,uint256)",
+        &[
+            Token::Address(from),
+            Token::Address(address(2)),
+            Token::Uint(U256::from(7)),
+        ],
+    );
+    transaction(hash, from, nonce, input)
+}
+
+#[test]
+fn decodes_erc721_transfers() {
+    let decoded = decode_transfer(&transfer(1, address(1), 3)).unwrap();
+    assert_eq!(decoded.method, TransferMethod::TransferFrom);
+    assert_eq!(decoded.contract, address(0xcc));
+    assert_eq!(decoded.from, address(1));
+    assert_eq!(decoded.to, address(2));
+    assert_eq!(decoded.token_ids, vec![U256::from(7)]);
+    assert!(decoded.amounts.is_empty());
+    assert_eq!(decoded.nonce, U256::from(3));
+    assert_eq!(decoded.max_priority_fee_per_gas, Some(U256::from(2)));
+
+    let arguments = [
+        Token::Address(address(1)),
+        Token::Address(address(2)),
+        Token::Uint(U256::from(8)),
+    ];
+    for signature in [
+        "safeTransferFrom(address,address,uint256)",
+        "safeTransferFrom(address,address,uint256,bytes)",
+    ] {
+        let mut arguments = arguments.to_vec();
+        if signature.ends_with("bytes)") {
+            arguments.push(Token::Bytes(vec![1, 2, 3]));
+        }
+        let input = call(signature, &arguments);
+        let decoded = decode_transfer(&transaction(1, address(1), 0, input)).unwrap();
+        assert_eq!(
+            decoded.method,
+            TransferMethod::SafeTransferFrom,
+            "{}",
+            signature
+        );
+        assert_eq!(decoded.token_ids, vec![U256::from(8)]);
+    }
+}
+
+#[test]
+fn decodes_erc1155_transfers() {
+    let input = call(
+        "safeTransferFrom(address,address,uint256,uint256,bytes)",
+        &[
+            Token::Address(address(1)),
+            Token::Address(address(2)),
+            Token::Uint(U256::from(5)),
+            Token::Uint(U256::from(10)),
+            Token::Bytes(Vec::new()),
+        ],
+    );
+    let decoded = decode_transfer(&transaction(1, address(1), 0, input)).unwrap();
+    assert_eq!(decoded.method, TransferMethod::SafeTransferFrom);
+    assert_eq!(decoded.token_ids, vec![U256::from(5)]);
+    assert_eq!(decoded.amounts, vec![U256::from(10)]);
+
+    let input = call(
+        "safeBatchTransferFrom(address,address,uint256[],uint256[],bytes)",
+        &[
+            Token::Address(address(1)),
+            Token::Address(address(2)),
+            Token::Array(vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))]),
+            Token::Array(vec![Token::Uint(U256::from(3)), Token::Uint(U256::from(4))]),
+            Token::Bytes(Vec::new()),
+        ],
+    );
+    let decoded = decode_transfer(&transaction(1, address(1), 0, input)).unwrap();
+    assert_eq!(decoded.method, TransferMethod::SafeBatchTransferFrom);
+    assert_eq!(decoded.token_ids, vec![U256::from(1), U256::from(2)]);
+    assert_eq!(decoded.amounts, vec![U256::from(3), U256::from(4)]);
+}
+
+#[test]
+fn ignores_other_calls() {
+    let approve = call(
+        "approve(address,uint256)",
+        &[Token::Address(address(2)), Token::Uint(U256::from(7))],
+    );
+    assert!(decode_transfer(&transaction(1, address(1), 0, approve)).is_none());
+
+    let truncated = Bytes::from(id("transferFrom(address,address,uint256)").to_vec());
+    assert!(decode_transfer(&transaction(1, address(1), 0, truncated)).is_none());
+
+    let mut creation = transfer(1, address(1), 0);
+    creation.to = None;
+    assert!(decode_transfer(&creation).is_none());
+}
+
+#[test]
+fn resolves_included_and_replaced_transfers() {
+    let mut tracker = PendingTracker::new(25);
+    let included = decode_transfer(&transfer(1, address(1), 0)).unwrap();
+    let replaced = decode_transfer(&transfer(2, address(3), 4)).unwrap();
+    assert!(tracker.track(&included, 100));
+    assert!(!tracker.track(&included, 101));
+    assert!(tracker.track(&replaced, 100));
+
+    let replacement = transaction(3, address(3), 4, Bytes::new());
+    let resolutions = tracker.resolve_block(&[transfer(1, address(1), 0), replacement]);
+
+    assert_eq!(resolutions.len(), 2);
+    assert_eq!(resolutions[0].transaction_hash, H256::repeat_byte(1));
+    assert_eq!(resolutions[0].status, PendingStatus::Included);
+    assert_eq!(resolutions[1].transaction_hash, H256::repeat_byte(2));
+    assert_eq!(resolutions[1].status, PendingStatus::Dropped);
+    assert!(tracker.is_empty());
+}
+
+#[test]
+fn transfers_become_due_after_the_timeout() {
+    let mut tracker = PendingTracker::new(25);
+    let pending = decode_transfer(&transfer(1, address(1), 0)).unwrap();
+    tracker.track(&pending, 100);
+
+    assert!(tracker.due(124).is_empty());
+    assert_eq!(tracker.due(125), vec![H256::repeat_byte(1)]);
+    // Still pending on the node: checked again after another timeout.
+    assert!(tracker.due(126).is_empty());
+    assert_eq!(tracker.due(150), vec![H256::repeat_byte(1)]);
+
+    assert!(tracker.resolve(H256::repeat_byte(1)));
+    assert!(!tracker.resolve(H256::repeat_byte(1)));
+    assert_eq!(tracker.len(), 0);
+}
+
+#[test]
+fn stops_tracking_the_oldest_transfers_when_full() {
+    let mut tracker = PendingTracker::new(25).with_max_tracked(2);
+    for hash in 1..=3 {
+        let pending = decode_transfer(&transfer(hash, address(hash), 0)).unwrap();
+        assert!(tracker.track(&pending, 100));
+    }
+
+    assert_eq!(tracker.len(), 2);
+    assert!(!tracker.resolve(H256::repeat_byte(1)));
+    assert!(tracker.resolve(H256::repeat_byte(2)));
+    assert!(tracker.resolve(H256::repeat_byte(3)));
+}
+
+#[tokio::test]
+async fn refuses_a_node_serving_another_chain() {
+    let (_node, server) = serve(MockChain::new(2)).await;
+    let sink = StreamSink::default();
+
+    let result = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        watch_mempool(
+            &server.ws_url(),
+            &sink,
+            &sync_config(),
+            &ChainControl::default(),
+        ),
+    )
+    .await
+    .expect("the watcher kept retrying");
+
+    assert!(matches!(
+        result,
+        Err(ChainVerificationError::ChainIdMismatch(1, 2, _))
+    ));
+    assert!(sink.envelopes.lock().unwrap().is_empty());
+}
@@ -0,0 +1,356 @@
+mod support;
+
+use std::sync::{atomic::Ordering, Arc};
+
+use chain_watcher::{
+    clients::{blockchain_client::BlockchainClient, redis_client::StreamIdMode},
+    services::{metrics::ChainMetrics, sync::ChainSynchronizer},
+};
+use common::types::{ContractCreation, StreamPayload};
+use ethers::{
+    types::{H256, U256},
+    utils::{get_contract_address, keccak256},
+};
+use mock_node::{
+    chain::{MockChain, MockTransaction},
+    events::{address, Event},
+};
+use support::{
+    serve, sync_config, synchronizer, BlockStore, Discard, FlakyStore, StreamSink,
+};
+
+/// Blocks 1 to 5, each with an ERC-721 transfer of the token numbered after
+/// the block, followed by a transaction without logs.
+fn chain() -> MockChain {
+    let mut chain = MockChain::new(1);
+    for block in 1..=5 {
+        chain.mine(vec![
+            MockTransaction::new(address(1), address(10)).with_event(
+                Event::Erc721Transfer {
+                    contract: address(10),
+                    from: address(1),
+                    to: address(2),
+                    token_id: U256::from(block),
+                },
+            ),
+            MockTransaction::new(address(1), address(2)),
+        ]);
+    }
+    chain
+}
+
+/// Block numbers and token ids of the published transfers.
+fn published_transfers(sink: &StreamSink) -> Vec<(u64, H256)> {
+    sink.envelopes
+        .lock()
+        .unwrap()
+        .iter()
+        .flat_map(|envelope| match &envelope.payload {
+            StreamPayload::Logs(logs) => logs
+                .iter()
+                .map(|log| (envelope.block_number, log.topics[3]))
+                .collect::<Vec<_>>(),
+            payload => panic!("unexpected {} payload", payload.kind()),
+        })
+        .collect()
+}
+
+fn expected_transfers() -> Vec<(u64, H256)> {
+    (1..=5)
+        .map(|block| (block, H256::from_low_u64_be(block)))
+        .collect()
+}
+
+#[tokio::test]
+async fn syncs_transfers_from_a_mock_node() {
+    let (_node, server) = serve(chain()).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let sink = StreamSink::default();
+    let blocks = BlockStore::default();
+    let synchronizer = synchronizer(client, sink.clone(), blocks.clone());
+
+    synchronizer.chain_head().await.unwrap();
+    synchronizer.sync(1, 5).await.unwrap();
+
+    assert_eq!(published_transfers(&sink), expected_transfers());
+    assert_eq!(blocks.blocks.lock().unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn fails_over_to_a_healthy_mock_node() {
+    let (faulty, faulty_server) = serve(chain()).await;
+    let (healthy, healthy_server) = serve(chain()).await;
+    let client = BlockchainClient::connect(
+        &[faulty_server.http_url(), healthy_server.http_url()],
+        1,
+    )
+    .await
+    .unwrap();
+    let sink = StreamSink::default();
+    let synchronizer = synchronizer(client.clone(), sink.clone(), BlockStore::default());
+
+    faulty.fail_every(1);
+    synchronizer.chain_head().await.unwrap();
+    synchronizer.sync(1, 5).await.unwrap();
+
+    assert_eq!(published_transfers(&sink), expected_transfers());
+    assert_eq!(client.active_url(), healthy_server.http_url());
+    assert!(healthy
+        .requests()
+        .contains(&"eth_getTransactionReceipt".to_string()));
+}
+
+/// A synchronizer that checks every block's receipts against its header.
+fn verifying_synchronizer(
+    client: BlockchainClient,
+    sink: StreamSink,
+    blocks: BlockStore,
+) -> ChainSynchronizer<BlockchainClient, StreamSink, BlockStore, Discard, Discard> {
+    let mut config = sync_config();
+    config.verify_receipts = true;
+    ChainSynchronizer::new(client, sink, blocks, Discard, Discard, config)
+}
+
+#[tokio::test]
+async fn refetches_incomplete_receipts_from_another_node() {
+    let (stripping, stripping_server) = serve(chain()).await;
+    let (_healthy, healthy_server) = serve(chain()).await;
+    let client = BlockchainClient::connect(
+        &[stripping_server.http_url(), healthy_server.http_url()],
+        1,
+    )
+    .await
+    .unwrap();
+    let sink = StreamSink::default();
+    let blocks = BlockStore::default();
+    let synchronizer =
+        verifying_synchronizer(client.clone(), sink.clone(), blocks.clone());
+
+    stripping.strip_receipt_logs(true);
+    synchronizer.chain_head().await.unwrap();
+    synchronizer.sync(1, 5).await.unwrap();
+
+    assert_eq!(published_transfers(&sink), expected_transfers());
+    assert_eq!(blocks.blocks.lock().unwrap().len(), 5);
+    assert_eq!(client.active_url(), healthy_server.http_url());
+}
+
+#[tokio::test]
+async fn skips_blocks_that_fail_verification() {
+    let (stripping, server) = serve(chain()).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let sink = StreamSink::default();
+    let blocks = BlockStore::default();
+    let metrics = Arc::new(ChainMetrics::default());
+    let synchronizer = verifying_synchronizer(client, sink.clone(), blocks.clone())
+        .with_metrics(metrics.clone());
+
+    stripping.strip_receipt_logs(true);
+    synchronizer.chain_head().await.unwrap();
+    synchronizer.sync(1, 5).await.unwrap();
+
+    assert!(published_transfers(&sink).is_empty());
+    assert!(blocks.blocks.lock().unwrap().is_empty());
+    assert_eq!(metrics.blocks_unverified.load(Ordering::Relaxed), 5);
+}
+
+#[tokio::test]
+async fn skips_blocks_missing_a_receipt() {
+    let (node, server) = serve(chain()).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let sink = StreamSink::default();
+    let blocks = BlockStore::default();
+    let synchronizer = synchronizer(client, sink.clone(), blocks.clone());
+    synchronizer.chain_head().await.unwrap();
+
+    // The block is served, then one of its two receipts fails.
+    node.fail_every(2);
+    synchronizer.sync(1, 1).await.unwrap();
+
+    assert!(sink.envelopes.lock().unwrap().is_empty());
+    assert!(blocks.blocks.lock().unwrap().is_empty());
+
+    node.fail_every(0);
+    synchronizer.sync_missing_blocks(vec![1]).await.unwrap();
+    assert_eq!(published_transfers(&sink), expected_transfers()[..1]);
+    assert_eq!(blocks.block_numbers(), vec![1]);
+}
+
+#[tokio::test]
+async fn retries_blocks_whose_transactions_were_not_stored() {
+    let (_node, server) = serve(chain()).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let sink = StreamSink::default();
+    let blocks = BlockStore::default();
+    let mut config = sync_config();
+    config.store_transactions = true;
+    let synchronizer = ChainSynchronizer::new(
+        client,
+        sink.clone(),
+        blocks.clone(),
+        FlakyStore::failing(1),
+        Discard,
+        config,
+    );
+    synchronizer.chain_head().await.unwrap();
+
+    synchronizer.sync(1, 1).await.unwrap();
+    assert!(sink.envelopes.lock().unwrap().is_empty());
+    assert!(blocks.blocks.lock().unwrap().is_empty());
+
+    synchronizer.sync_missing_blocks(vec![1]).await.unwrap();
+    assert_eq!(published_transfers(&sink), expected_transfers()[..1]);
+    assert_eq!(blocks.block_numbers(), vec![1]);
+}
+
+#[tokio::test]
+async fn retries_blocks_whose_logs_were_not_archived() {
+    let (_node, server) = serve(chain()).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let sink = StreamSink::default();
+    let blocks = BlockStore::default();
+    let mut config = sync_config();
+    config.archive_logs = true;
+    let synchronizer = ChainSynchronizer::new(
+        client,
+        sink.clone(),
+        blocks.clone(),
+        Discard,
+        FlakyStore::failing(1),
+        config,
+    );
+    synchronizer.chain_head().await.unwrap();
+
+    synchronizer.sync(1, 1).await.unwrap();
+    assert!(sink.envelopes.lock().unwrap().is_empty());
+    assert!(blocks.blocks.lock().unwrap().is_empty());
+
+    synchronizer.sync_missing_blocks(vec![1]).await.unwrap();
+    assert_eq!(published_transfers(&sink), expected_transfers()[..1]);
+    assert_eq!(blocks.block_numbers(), vec![1]);
+}
+
+#[tokio::test]
+async fn stops_at_a_block_that_fails_verification_with_block_tx_ids() {
+    let (stripping, server) = serve(chain()).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let sink = StreamSink::default();
+    let blocks = BlockStore::default();
+    let mut config = sync_config();
+    config.verify_receipts = true;
+    config.stream_id_mode = StreamIdMode::BlockTx;
+    let synchronizer = ChainSynchronizer::new(
+        client,
+        sink.clone(),
+        blocks.clone(),
+        Discard,
+        Discard,
+        config,
+    );
+
+    synchronizer.chain_head().await.unwrap();
+    synchronizer.sync(1, 2).await.unwrap();
+    stripping.strip_receipt_logs(true);
+    let error = synchronizer.sync(3, 5).await.unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Block 3 could not be synced, and block-tx ids must increase"
+    );
+    assert_eq!(published_transfers(&sink), expected_transfers()[..2]);
+    assert_eq!(blocks.block_numbers(), vec![1, 2]);
+}
+
+/// Syncs block 1 of a chain where a creation transaction deploys a contract
+/// and a factory deploys two more, and returns the published creations.
+async fn sync_contract_creations(
+    trace_contracts: bool,
+    contract_code_hash: bool,
+) -> Vec<ContractCreation> {
+    let mut chain = MockChain::new(1);
+    chain.mine(vec![
+        MockTransaction::deploy(address(1)),
+        MockTransaction::new(address(2), address(20))
+            .with_created(address(30))
+            .with_created(address(31)),
+    ]);
+    let (_node, server) = serve(chain).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let sink = StreamSink::default();
+    let mut config = sync_config();
+    config.detect_contracts = true;
+    config.trace_contracts = trace_contracts;
+    config.contract_code_hash = contract_code_hash;
+    let synchronizer = ChainSynchronizer::new(
+        client,
+        sink.clone(),
+        BlockStore::default(),
+        Discard,
+        Discard,
+        config,
+    );
+
+    synchronizer.sync(1, 1).await.unwrap();
+
+    let envelopes = sink.envelopes.lock().unwrap();
+    assert_eq!(envelopes.len(), 1);
+    let StreamPayload::ContractCreations(creations) = &envelopes[0].payload else {
+        panic!("unexpected {} payload", envelopes[0].payload.kind());
+    };
+    assert_eq!(envelopes[0].block_number, 1);
+    creations.clone()
+}
+
+#[tokio::test]
+async fn publishes_contracts_deployed_by_transactions() {
+    let creations = sync_contract_creations(false, false).await;
+
+    assert_eq!(creations.len(), 1);
+    assert_eq!(
+        creations[0].address,
+        get_contract_address(address(1), U256::one())
+    );
+    assert_eq!(creations[0].creator, address(1));
+    assert_eq!(creations[0].transaction_index, 0);
+    assert_eq!(creations[0].code_hash, None);
+}
+
+#[tokio::test]
+async fn traces_contracts_deployed_by_factories() {
+    let creations = sync_contract_creations(true, true).await;
+
+    let deployed: Vec<_> = creations
+        .iter()
+        .map(|creation| (creation.address, creation.creator, creation.sender))
+        .collect();
+    let deployer = get_contract_address(address(1), U256::one());
+    assert_eq!(
+        deployed,
+        vec![
+            (deployer, address(1), address(1)),
+            (address(30), address(20), address(2)),
+            (address(31), address(20), address(2)),
+        ]
+    );
+
+    let mut code = vec![0x60, 0x80, 0x60, 0x40, 0x52];
+    code.extend_from_slice(address(30).as_bytes());
+    assert_eq!(creations[1].code_hash, Some(H256(keccak256(code))));
+    assert!(creations
+        .iter()
+        .all(|creation| creation.code_hash.is_some()));
+}
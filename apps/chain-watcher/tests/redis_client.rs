@@ -0,0 +1,128 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use chain_watcher::clients::redis_client::{
+    PublishError, PublishOutcome, RedisClient, RedisClientTrait, StreamIdMode,
+};
+use common::{
+    codec::Encoding,
+    redis::redis_pool_factory,
+    types::{FinalityStatus, StreamEnvelope, StreamPayload},
+};
+use ethers::types::{Log, U64};
+
+/// A client for the Redis at `REDIS_URL`. Tests using it are ignored unless
+/// run with `--include-ignored`.
+async fn redis_client(id_mode: StreamIdMode) -> RedisClient {
+    let redis_url =
+        std::env::var("REDIS_URL").expect("REDIS_URL must be set to run the Redis tests");
+    RedisClient {
+        pool: Arc::new(redis_pool_factory(redis_url).await.unwrap()),
+        encoding: Encoding::default(),
+        id_mode,
+        dedup_ttl: 60,
+        batch_size: 100,
+        fencing_token: Arc::new(AtomicU64::new(0)),
+    }
+}
+
+/// A stream key of its own for each run.
+fn stream_key(name: &str) -> String {
+    format!("chain-watcher-test:{}:{}", name, std::process::id())
+}
+
+fn logs_message(block_number: u64) -> StreamEnvelope {
+    let log = Log {
+        block_number: Some(U64::from(block_number)),
+        transaction_index: Some(U64::zero()),
+        ..Default::default()
+    };
+    StreamEnvelope::new(
+        1,
+        block_number,
+        None,
+        FinalityStatus::Unfinalized,
+        "test".to_string(),
+        StreamPayload::Logs(vec![log.into()]),
+    )
+}
+
+#[tokio::test]
+#[ignore = "needs REDIS_URL"]
+async fn refuses_block_tx_ids_below_the_last_entry() {
+    let client = redis_client(StreamIdMode::BlockTx).await;
+    let stream_key = stream_key("block-tx");
+
+    let outcomes = client
+        .send_messages(&stream_key, &[logs_message(2), logs_message(5)])
+        .await
+        .unwrap();
+    assert_eq!(outcomes, vec![PublishOutcome::Added; 2]);
+
+    // Published before: skipped.
+    let outcomes = client
+        .send_messages(&stream_key, &[logs_message(2)])
+        .await
+        .unwrap();
+    assert_eq!(outcomes, vec![PublishOutcome::Duplicate]);
+
+    // Never published, below the last entry: refused.
+    let error = client
+        .send_messages(&stream_key, &[logs_message(3)])
+        .await
+        .unwrap_err();
+    assert!(matches!(error, PublishError::OutOfOrder(_)), "{}", error);
+    assert!(!error.is_transient());
+
+    let mut conn = client.pool.get().await.unwrap();
+    let _: () = redis::cmd("DEL")
+        .arg(&stream_key)
+        .query_async(&mut *conn)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[ignore = "needs REDIS_URL"]
+async fn rejects_writes_under_a_stale_fencing_token() {
+    let leader = redis_client(StreamIdMode::Auto).await;
+    let stale = redis_client(StreamIdMode::Auto).await;
+    let stream_key = stream_key("fenced");
+    stale.fencing_token.store(1, Ordering::SeqCst);
+    leader.fencing_token.store(2, Ordering::SeqCst);
+
+    let outcomes = stale
+        .send_messages(&stream_key, &[logs_message(1)])
+        .await
+        .unwrap();
+    assert_eq!(outcomes, vec![PublishOutcome::Added]);
+    // The new leader's first write raises the fence.
+    let outcomes = leader
+        .send_messages(&stream_key, &[logs_message(2)])
+        .await
+        .unwrap();
+    assert_eq!(outcomes, vec![PublishOutcome::Added]);
+
+    let error = stale
+        .send_messages(&stream_key, &[logs_message(3)])
+        .await
+        .unwrap_err();
+    assert!(matches!(error, PublishError::Fenced(_)), "{}", error);
+    assert!(!error.is_transient());
+
+    let mut conn = leader.pool.get().await.unwrap();
+    let length: u64 = redis::cmd("XLEN")
+        .arg(&stream_key)
+        .query_async(&mut *conn)
+        .await
+        .unwrap();
+    assert_eq!(length, 2);
+    let _: () = redis::cmd("DEL")
+        .arg(&stream_key)
+        .arg(format!("{}:fence", stream_key))
+        .query_async(&mut *conn)
+        .await
+        .unwrap();
+}
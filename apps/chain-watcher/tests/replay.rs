@@ -0,0 +1,218 @@
+mod support;
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chain_watcher::clients::{
+    blockchain_client::{BlockchainClientTrait, TransactionTrace},
+    recording_client::RecordingClient,
+};
+use common::types::{FinalityStatus, StreamPayload, SyncLane};
+use ethers::{
+    providers::ProviderError,
+    types::{
+        Block as EthersBlock, Bytes, Log, Transaction, TransactionReceipt, H160, H256,
+        U64,
+    },
+};
+use support::{synchronizer, BlockStore, StreamSink};
+
+/// Chain 1, blocks 100 and 101, with the head at block 200. Block 100 has a
+/// transaction with one log and one without logs; block 101 has a single
+/// transaction with two logs.
+fn fixtures() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rpc/1")
+}
+
+/// Serves the chain the fixtures were recorded from.
+#[derive(Clone)]
+struct SyntheticChain;
+
+impl SyntheticChain {
+    fn transaction(block_number: u64, index: u64) -> Transaction {
+        Transaction {
+            hash: H256::from_low_u64_be(block_number * 100 + index),
+            block_number: Some(U64::from(block_number)),
+            block_hash: Some(H256::from_low_u64_be(block_number)),
+            transaction_index: Some(U64::from(index)),
+            from: H160::repeat_byte(0x11),
+            to: Some(H160::repeat_byte(0x22)),
+            ..Default::default()
+        }
+    }
+
+    fn log_counts(block_number: u64) -> &'static [usize] {
+        match block_number {
+            100 => &[1, 0],
+            101 => &[2],
+            _ => &[],
+        }
+    }
+}
+
+#[async_trait]
+impl BlockchainClientTrait for SyntheticChain {
+    async fn get_block_with_txs(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<EthersBlock<Transaction>>, ProviderError> {
+        if Self::log_counts(block_number).is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(EthersBlock {
+            hash: Some(H256::from_low_u64_be(block_number)),
+            parent_hash: H256::from_low_u64_be(block_number - 1),
+            number: Some(U64::from(block_number)),
+            timestamp: (1_700_000_000 + block_number * 12).into(),
+            transactions: (0..Self::log_counts(block_number).len() as u64)
+                .map(|index| Self::transaction(block_number, index))
+                .collect(),
+            ..Default::default()
+        }))
+    }
+
+    async fn get_transaction_receipt(
+        &self,
+        tx_hash: H256,
+    ) -> Result<Option<TransactionReceipt>, ProviderError> {
+        let id = tx_hash.to_low_u64_be();
+        let (block_number, index) = (id / 100, id % 100);
+        let Some(log_count) = Self::log_counts(block_number).get(index as usize) else {
+            return Ok(None);
+        };
+        Ok(Some(TransactionReceipt {
+            transaction_hash: tx_hash,
+            transaction_index: U64::from(index),
+            block_hash: Some(H256::from_low_u64_be(block_number)),
+            block_number: Some(U64::from(block_number)),
+            logs: (0..*log_count)
+                .map(|log_index| Log {
+                    address: H160::repeat_byte(0x33),
+                    topics: vec![H256::repeat_byte(0xdd)],
+                    block_hash: Some(H256::from_low_u64_be(block_number)),
+                    block_number: Some(U64::from(block_number)),
+                    transaction_hash: Some(tx_hash),
+                    transaction_index: Some(U64::from(index)),
+                    log_index: Some(log_index.into()),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }))
+    }
+
+    async fn get_block_number(&self) -> Result<u64, ProviderError> {
+        Ok(200)
+    }
+
+    async fn get_chain_id(&self) -> Result<u64, ProviderError> {
+        Ok(1)
+    }
+
+    async fn get_block_hash(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<H256>, ProviderError> {
+        Ok(Some(H256::from_low_u64_be(block_number)))
+    }
+
+    async fn get_block_timestamp(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<u64>, ProviderError> {
+        Ok(Some(1_700_000_000 + block_number * 12))
+    }
+
+    async fn trace_block(
+        &self,
+        _block_number: u64,
+    ) -> Result<Vec<TransactionTrace>, ProviderError> {
+        Ok(Vec::new())
+    }
+
+    async fn get_code(
+        &self,
+        _address: H160,
+        _block_number: u64,
+    ) -> Result<Bytes, ProviderError> {
+        Ok(Bytes::new())
+    }
+
+    async fn switch_provider(&self) -> bool {
+        false
+    }
+}
+
+#[tokio::test]
+async fn replayed_blocks_are_published_in_order() {
+    let sink = StreamSink::default();
+    let blocks = BlockStore::default();
+    let synchronizer = synchronizer(
+        RecordingClient::<SyntheticChain>::replay(fixtures()),
+        sink.clone(),
+        blocks.clone(),
+    )
+    .with_lane(SyncLane::Backfill);
+
+    synchronizer.chain_head().await.unwrap();
+    synchronizer.sync(100, 101).await.unwrap();
+
+    let envelopes = sink.envelopes.lock().unwrap();
+    let published: Vec<(u64, usize)> = envelopes
+        .iter()
+        .map(|envelope| match &envelope.payload {
+            StreamPayload::Logs(logs) => (envelope.block_number, logs.len()),
+            payload => panic!("unexpected {} payload", payload.kind()),
+        })
+        .collect();
+    assert_eq!(published, vec![(100, 1), (101, 2)]);
+    assert!(envelopes.iter().all(|envelope| {
+        envelope.chain_id == 1
+            && envelope.finality == FinalityStatus::Finalized
+            && envelope.lane == SyncLane::Backfill
+    }));
+
+    let blocks = blocks.blocks.lock().unwrap();
+    let indexed: Vec<(u64, u32)> = blocks
+        .iter()
+        .map(|block| (block.block_number, block.log_count))
+        .collect();
+    assert_eq!(indexed, vec![(100, 1), (101, 2)]);
+}
+
+#[tokio::test]
+async fn replay_fails_on_unrecorded_requests() {
+    let client = RecordingClient::<SyntheticChain>::replay(fixtures());
+
+    assert_eq!(client.get_chain_id().await.unwrap(), 1);
+    let error = client.get_block_with_txs(102).await.unwrap_err();
+    assert!(
+        error.to_string().contains("no recorded response"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn recorded_responses_replay_identically() {
+    let dir = tempfile::tempdir().unwrap();
+    let recorder = RecordingClient::record(SyntheticChain, dir.path())
+        .await
+        .unwrap();
+    let block = recorder.get_block_with_txs(101).await.unwrap();
+    let receipt = recorder
+        .get_transaction_receipt(H256::from_low_u64_be(10_100))
+        .await
+        .unwrap();
+
+    let replay = RecordingClient::<SyntheticChain>::replay(dir.path());
+    assert_eq!(replay.get_block_with_txs(101).await.unwrap(), block);
+    assert_eq!(
+        replay
+            .get_transaction_receipt(H256::from_low_u64_be(10_100))
+            .await
+            .unwrap(),
+        receipt
+    );
+    assert!(replay.get_block_number().await.is_err());
+}
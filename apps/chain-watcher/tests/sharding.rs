@@ -0,0 +1,92 @@
+mod support;
+
+use std::time::Duration;
+
+use chain_watcher::{
+    clients::blockchain_client::BlockchainClient,
+    services::{sharding::run_sharded_backfill, sync::ChainSynchronizer},
+};
+use ethers::types::U256;
+use mock_node::{
+    chain::{MockChain, MockTransaction},
+    events::{address, Event},
+};
+use support::{
+    serve, sync_config, BlockStore, Discard, FlakyStore, LeaseTable, StreamSink,
+};
+
+/// Blocks 1 to 4, each with an ERC-721 transfer.
+fn chain() -> MockChain {
+    let mut chain = MockChain::new(1);
+    for block in 1..=4 {
+        chain.mine(vec![MockTransaction::new(address(1), address(10))
+            .with_event(Event::Erc721Transfer {
+                contract: address(10),
+                from: address(1),
+                to: address(2),
+                token_id: U256::from(block),
+            })]);
+    }
+    chain
+}
+
+#[tokio::test]
+async fn fills_blocks_skipped_in_a_range_before_completing_it() {
+    let (_node, server) = serve(chain()).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let blocks = BlockStore::default();
+    let leases = LeaseTable::default();
+    let mut config = sync_config();
+    config.store_transactions = true;
+    // The first block's transactions cannot be stored, so it is skipped.
+    let synchronizer = ChainSynchronizer::new(
+        client,
+        StreamSink::default(),
+        blocks.clone(),
+        FlakyStore::failing(1),
+        Discard,
+        config,
+    );
+
+    run_sharded_backfill(&synchronizer, &leases, 1, 4, 2)
+        .await
+        .unwrap();
+
+    assert_eq!(blocks.block_numbers(), vec![1, 2, 3, 4]);
+    assert_eq!(leases.statuses(), vec!["done", "done"]);
+}
+
+#[tokio::test]
+async fn leaves_a_range_with_unfetchable_blocks_to_be_reclaimed() {
+    let (node, server) = serve(chain()).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let sink = StreamSink::default();
+    let blocks = BlockStore::default();
+    let leases = LeaseTable::default();
+    let mut config = sync_config();
+    config.verify_receipts = true;
+    let synchronizer = ChainSynchronizer::new(
+        client,
+        sink.clone(),
+        blocks.clone(),
+        Discard,
+        Discard,
+        config,
+    );
+
+    // Every receipt fails verification, so no block can be indexed.
+    node.strip_receipt_logs(true);
+    let backfill = run_sharded_backfill(&synchronizer, &leases, 1, 4, 2);
+    // It waits for the ranges to be reclaimed rather than finishing.
+    assert!(tokio::time::timeout(Duration::from_secs(2), backfill)
+        .await
+        .is_err());
+
+    assert!(sink.envelopes.lock().unwrap().is_empty());
+    assert!(blocks.block_numbers().is_empty());
+    assert_eq!(leases.statuses(), vec!["claimed", "claimed"]);
+}
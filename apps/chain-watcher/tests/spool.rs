@@ -0,0 +1,438 @@
+mod support;
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chain_watcher::{
+    clients::{
+        blockchain_client::BlockchainClient,
+        redis_client::{PublishError, PublishOutcome, RedisClientTrait},
+    },
+    services::{
+        control::ChainControl,
+        metrics::{ChainMetrics, MetricsExporter},
+        spool::{replay_spool, Spool, SpoolError},
+        sync::ChainSynchronizer,
+    },
+};
+use common::{
+    codec::CodecError,
+    types::{FinalityStatus, StreamEnvelope, StreamPayload},
+};
+use ethers::types::U256;
+use mock_node::{
+    chain::{MockChain, MockTransaction},
+    events::{address, Event},
+};
+use support::{serve, sync_config, BlockStore, Discard};
+
+/// A spool directory of its own for each test.
+fn spool_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "chain-watcher-spool-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+async fn open(dir: &Path, max_bytes: u64) -> Spool {
+    Spool::open(
+        dir.to_path_buf(),
+        max_bytes,
+        Arc::new(ChainMetrics::default()),
+    )
+    .await
+    .unwrap()
+}
+
+/// The spool's segment files, oldest first.
+fn segments(dir: &Path) -> Vec<PathBuf> {
+    let mut segments: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "seg"))
+        .collect();
+    segments.sort();
+    segments
+}
+
+/// Block numbers of the next `max` records, without consuming them.
+async fn peek_blocks(spool: &Spool, max: usize) -> Vec<u64> {
+    let batch = spool.peek(max).await.unwrap();
+    batch
+        .envelopes
+        .iter()
+        .map(|envelope| envelope.block_number)
+        .collect()
+}
+
+/// Waits for the replay task to drain `spool`.
+async fn drained(spool: &Spool) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !spool.is_empty().await {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+fn envelope(block_number: u64) -> StreamEnvelope {
+    StreamEnvelope::new(
+        1,
+        block_number,
+        None,
+        FinalityStatus::Unfinalized,
+        "test".to_string(),
+        StreamPayload::Logs(Vec::new()),
+    )
+}
+
+/// A broker that is unreachable while `down` is set, rejects writes as
+/// fenced off while `fenced` is set, and cannot encode the messages of
+/// `unencodable`.
+#[derive(Clone, Default)]
+struct Broker {
+    down: Arc<AtomicBool>,
+    fenced: Arc<AtomicBool>,
+    unencodable: Option<u64>,
+    published: Arc<Mutex<Vec<u64>>>,
+}
+
+#[async_trait]
+impl RedisClientTrait for Broker {
+    async fn send_messages(
+        &self,
+        _key_stream: &str,
+        envelopes: &[StreamEnvelope],
+    ) -> Result<Vec<PublishOutcome>, PublishError> {
+        if self.down.load(Ordering::SeqCst) {
+            return Err(PublishError::PoolError("connection refused".to_string()));
+        }
+        if self.fenced.load(Ordering::SeqCst) {
+            return Err(PublishError::Fenced("stale fencing token".to_string()));
+        }
+        if envelopes
+            .iter()
+            .any(|envelope| Some(envelope.block_number) == self.unencodable)
+        {
+            return Err(PublishError::EncodingError(CodecError::EncodeError(
+                "unsupported payload".to_string(),
+            )));
+        }
+        let mut published = self.published.lock().unwrap();
+        published.extend(envelopes.iter().map(|envelope| envelope.block_number));
+        Ok(vec![PublishOutcome::Added; envelopes.len()])
+    }
+}
+
+#[tokio::test]
+async fn keeps_fenced_messages_until_leadership_is_regained() {
+    let dir = spool_dir("fenced");
+    let spool = Arc::new(
+        Spool::open(dir.clone(), 1 << 20, Arc::new(ChainMetrics::default()))
+            .await
+            .unwrap(),
+    );
+    spool.append(&[envelope(1), envelope(2)]).await.unwrap();
+
+    let broker = Broker::default();
+    broker.fenced.store(true, Ordering::SeqCst);
+    let fencing_token = Arc::new(AtomicU64::new(1));
+    let replay = tokio::spawn(replay_spool(
+        spool.clone(),
+        broker.clone(),
+        "stream".to_string(),
+        10,
+        fencing_token.clone(),
+        true,
+        Arc::new(ChainControl::default()),
+    ));
+
+    // Fenced off: nothing is published or dropped, even once the broker
+    // accepts writes again, until this instance leads with a new token.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    broker.fenced.store(false, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(broker.published.lock().unwrap().is_empty());
+    assert!(!spool.is_empty().await);
+
+    fencing_token.store(2, Ordering::SeqCst);
+    drained(&spool).await;
+    replay.abort();
+    assert_eq!(*broker.published.lock().unwrap(), vec![1, 2]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn replays_nothing_before_leading_the_chain() {
+    let dir = spool_dir("standby");
+    let spool = Arc::new(open(&dir, 1 << 20).await);
+    spool.append(&[envelope(1), envelope(2)]).await.unwrap();
+
+    let broker = Broker::default();
+    let fencing_token = Arc::new(AtomicU64::new(0));
+    let replay = tokio::spawn(replay_spool(
+        spool.clone(),
+        broker.clone(),
+        "stream".to_string(),
+        10,
+        fencing_token.clone(),
+        true,
+        Arc::new(ChainControl::default()),
+    ));
+
+    // A standby has no fencing token, and its writes would not be fenced.
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(broker.published.lock().unwrap().is_empty());
+    assert!(!spool.is_empty().await);
+
+    fencing_token.store(1, Ordering::SeqCst);
+    drained(&spool).await;
+    replay.abort();
+    assert_eq!(*broker.published.lock().unwrap(), vec![1, 2]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn rotates_segments_and_deletes_them_once_replayed() {
+    let dir = spool_dir("rotation");
+    let spool = open(&dir, 1 << 20).await.with_segment_max_bytes(1);
+
+    spool.append(&[envelope(1), envelope(2)]).await.unwrap();
+    spool.append(&[envelope(3)]).await.unwrap();
+    spool.append(&[envelope(4)]).await.unwrap();
+    assert_eq!(segments(&dir).len(), 3);
+
+    // The first segment holds two records.
+    let batch = spool.peek(2).await.unwrap();
+    spool.advance(&batch).await.unwrap();
+    assert_eq!(segments(&dir).len(), 2);
+    assert_eq!(peek_blocks(&spool, 10).await, vec![3, 4]);
+
+    let batch = spool.peek(10).await.unwrap();
+    spool.advance(&batch).await.unwrap();
+    assert!(spool.is_empty().await);
+    assert!(segments(&dir).is_empty());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn resumes_from_the_saved_cursor_after_a_restart() {
+    let dir = spool_dir("cursor");
+    let spool = open(&dir, 1 << 20).await;
+    spool
+        .append(&[envelope(1), envelope(2), envelope(3)])
+        .await
+        .unwrap();
+    let batch = spool.peek(2).await.unwrap();
+    spool.advance(&batch).await.unwrap();
+    drop(spool);
+
+    let spool = open(&dir, 1 << 20).await;
+    assert_eq!(peek_blocks(&spool, 10).await, vec![3]);
+    spool.append(&[envelope(4)]).await.unwrap();
+    assert_eq!(peek_blocks(&spool, 10).await, vec![3, 4]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn drops_a_record_torn_by_a_crash() {
+    let dir = spool_dir("torn");
+    let spool = open(&dir, 1 << 20).await;
+    spool.append(&[envelope(1), envelope(2)]).await.unwrap();
+    drop(spool);
+
+    // A header announcing 100 bytes, followed by only 10 of them.
+    let segment = segments(&dir).pop().unwrap();
+    let size = std::fs::metadata(&segment).unwrap().len();
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&segment)
+        .unwrap();
+    file.write_all(&100u32.to_le_bytes()).unwrap();
+    file.write_all(&[b'{'; 10]).unwrap();
+    drop(file);
+
+    let spool = open(&dir, 1 << 20).await;
+    assert_eq!(std::fs::metadata(&segment).unwrap().len(), size);
+    spool.append(&[envelope(3)]).await.unwrap();
+    assert_eq!(peek_blocks(&spool, 10).await, vec![1, 2, 3]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn refuses_messages_beyond_the_size_limit() {
+    let dir = spool_dir("full");
+    let metrics = Arc::new(ChainMetrics::default());
+    let record_bytes = 4 + serde_json::to_vec(&envelope(1)).unwrap().len() as u64;
+    let spool = Spool::open(dir.clone(), record_bytes * 2, metrics.clone())
+        .await
+        .unwrap();
+
+    spool.append(&[envelope(1), envelope(2)]).await.unwrap();
+    let error = spool.append(&[envelope(3)]).await.unwrap_err();
+    assert!(matches!(error, SpoolError::Full(_)), "{}", error);
+    assert_eq!(metrics.snapshot().spool_rejections, 1);
+    assert_eq!(metrics.snapshot().spool_messages, 2);
+
+    // Room is made as the spool is replayed.
+    let batch = spool.peek(10).await.unwrap();
+    spool.advance(&batch).await.unwrap();
+    spool.append(&[envelope(3)]).await.unwrap();
+    assert_eq!(peek_blocks(&spool, 10).await, vec![3]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn replays_in_the_order_messages_were_spooled() {
+    let dir = spool_dir("order");
+    let spool = Arc::new(open(&dir, 1 << 20).await.with_segment_max_bytes(1));
+    spool.append(&[envelope(1), envelope(2)]).await.unwrap();
+    spool.append(&[envelope(3)]).await.unwrap();
+    spool
+        .append(&[envelope(4), envelope(5), envelope(6)])
+        .await
+        .unwrap();
+
+    let broker = Broker::default();
+    let replay = tokio::spawn(replay_spool(
+        spool.clone(),
+        broker.clone(),
+        "stream".to_string(),
+        2,
+        Arc::new(AtomicU64::new(0)),
+        false,
+        Arc::new(ChainControl::default()),
+    ));
+    drained(&spool).await;
+    replay.abort();
+    assert_eq!(*broker.published.lock().unwrap(), vec![1, 2, 3, 4, 5, 6]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn quarantines_messages_that_cannot_be_encoded() {
+    let dir = spool_dir("quarantine");
+    let metrics = Arc::new(ChainMetrics::default());
+    let spool = Arc::new(
+        Spool::open(dir.clone(), 1 << 20, metrics.clone())
+            .await
+            .unwrap(),
+    );
+    spool
+        .append(&[envelope(1), envelope(2), envelope(3), envelope(4)])
+        .await
+        .unwrap();
+
+    let broker = Broker {
+        unencodable: Some(2),
+        ..Broker::default()
+    };
+    let replay = tokio::spawn(replay_spool(
+        spool.clone(),
+        broker.clone(),
+        "stream".to_string(),
+        10,
+        Arc::new(AtomicU64::new(0)),
+        false,
+        Arc::new(ChainControl::default()),
+    ));
+    drained(&spool).await;
+    replay.abort();
+
+    assert_eq!(*broker.published.lock().unwrap(), vec![1, 3, 4]);
+    let quarantined = std::fs::read_to_string(dir.join("quarantine.jsonl")).unwrap();
+    let quarantined: Vec<StreamEnvelope> = quarantined
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(quarantined.len(), 1);
+    assert_eq!(quarantined[0].block_number, 2);
+
+    let exporter = MetricsExporter::new(HashMap::from([(1, metrics)]));
+    let rendered = exporter.render();
+    assert!(rendered.contains("chain_watcher_spool_quarantined{chain_id=\"1\"} 1\n"));
+    assert!(rendered.contains("chain_watcher_messages_replayed{chain_id=\"1\"} 3\n"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn records_spooled_blocks_and_publishes_them_after_a_restart() {
+    let mut chain = MockChain::new(1);
+    for block in 1..=3 {
+        chain.mine(vec![MockTransaction::new(address(1), address(10))
+            .with_event(Event::Erc721Transfer {
+                contract: address(10),
+                from: address(1),
+                to: address(2),
+                token_id: U256::from(block),
+            })]);
+    }
+    let (_node, server) = serve(chain).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let dir = spool_dir("recorded");
+    let spool = Arc::new(open(&dir, 1 << 20).await);
+    let broker = Broker::default();
+    broker.down.store(true, Ordering::SeqCst);
+    let blocks = BlockStore::default();
+    let synchronizer = ChainSynchronizer::new(
+        client,
+        broker.clone(),
+        blocks.clone(),
+        Discard,
+        Discard,
+        sync_config(),
+    )
+    .with_spool(spool.clone());
+
+    // Spooled blocks count as published: they are recorded at once, and
+    // their messages wait in the spool.
+    synchronizer.sync(1, 3).await.unwrap();
+    assert_eq!(blocks.block_numbers(), vec![1, 2, 3]);
+    assert!(broker.published.lock().unwrap().is_empty());
+    assert!(!spool.is_empty().await);
+    drop(synchronizer);
+    drop(spool);
+
+    // The spool outlives a restart, and is replayed once Redis is back.
+    broker.down.store(false, Ordering::SeqCst);
+    let spool = Arc::new(open(&dir, 1 << 20).await);
+    let replay = tokio::spawn(replay_spool(
+        spool.clone(),
+        broker.clone(),
+        "stream".to_string(),
+        10,
+        Arc::new(AtomicU64::new(0)),
+        false,
+        Arc::new(ChainControl::default()),
+    ));
+    drained(&spool).await;
+    replay.abort();
+    let mut published = broker.published.lock().unwrap().clone();
+    published.dedup();
+    assert_eq!(published, vec![1, 2, 3]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
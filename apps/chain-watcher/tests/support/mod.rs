@@ -0,0 +1,495 @@
+// Each test crate uses a different subset of these helpers.
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use chain_watcher::{
+    clients::{
+        blockchain_client::BlockchainClientTrait,
+        redis_client::{PublishError, PublishOutcome, RedisClientTrait, StreamIdMode},
+    },
+    config::{ChainSyncConfig, Command},
+    services::{
+        repositories::{
+            block::{Block, BlockRepositoryTrait, IndexedRange},
+            block_time::BlockTimeRepositoryTrait,
+            checkpoint::{CheckpointRepositoryTrait, SyncCheckpoint},
+            lease::{BlockRangeLease, LeaseRepositoryTrait},
+            log::LogRepositoryTrait,
+            transaction::{TransactionRecord, TransactionRepositoryTrait},
+        },
+        sync::ChainSynchronizer,
+    },
+};
+use common::{
+    codec::Encoding,
+    types::{ChainConfig, NativeCurrency, RedisConfig, StreamEnvelope, SyncLane},
+};
+use ethers::types::Log;
+use mock_node::{
+    chain::MockChain,
+    server::{MockNode, ServerHandle},
+};
+use sqlx::PgPool;
+
+pub fn chain_config() -> ChainConfig {
+    ChainConfig {
+        id: 1,
+        name: "Ethereum".to_string(),
+        native_currency: NativeCurrency {
+            name: "Ether".to_string(),
+            symbol: "ETH".to_string(),
+            decimals: 18,
+        },
+        average_block_time_ms: 12_000,
+        finality_depth: 64,
+        supports_block_receipts: true,
+        max_logs_range: 10_000,
+    }
+}
+
+pub fn sync_config() -> ChainSyncConfig {
+    ChainSyncConfig {
+        chain: chain_config(),
+        redis_config: RedisConfig {
+            url: String::new(),
+            stream_key: "ethereum-logs".to_string(),
+            group_name: String::new(),
+        },
+        start_block: Some(100),
+        end_block: Some(101),
+        start_time: None,
+        end_time: None,
+        rpc: Vec::new(),
+        rpc_record: None,
+        rpc_replay: None,
+        num_workers: 4,
+        backfill_workers: 4,
+        backfill_rate: None,
+        store_transactions: false,
+        archive_logs: false,
+        producer_id: "replay".to_string(),
+        stream_encoding: Encoding::default(),
+        stream_id_mode: StreamIdMode::Auto,
+        dedup_ttl: 86_400,
+        publish_batch_size: 500,
+        spool_dir: None,
+        spool_max_bytes: 1 << 20,
+        shard_backfill: false,
+        range_size: 10_000,
+        instance_id: "replay".to_string(),
+        lease_ttl: 300,
+        leader_election: false,
+        leader_ttl: 10,
+        mempool_ws: None,
+        pending_timeout_blocks: 25,
+        detect_contracts: false,
+        trace_contracts: false,
+        contract_code_hash: false,
+        verify_receipts: false,
+        command: Command::Sync,
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct StreamSink {
+    pub envelopes: Arc<Mutex<Vec<StreamEnvelope>>>,
+}
+
+#[async_trait]
+impl RedisClientTrait for StreamSink {
+    async fn send_messages(
+        &self,
+        _key_stream: &str,
+        envelopes: &[StreamEnvelope],
+    ) -> Result<Vec<PublishOutcome>, PublishError> {
+        self.envelopes.lock().unwrap().extend_from_slice(envelopes);
+        Ok(vec![PublishOutcome::Added; envelopes.len()])
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct BlockStore {
+    pub blocks: Arc<Mutex<Vec<Block>>>,
+}
+
+impl BlockStore {
+    /// Indexed block numbers, in order and without repeats.
+    pub fn block_numbers(&self) -> Vec<u64> {
+        let blocks = self.blocks.lock().unwrap();
+        let mut numbers: Vec<u64> =
+            blocks.iter().map(|block| block.block_number).collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        numbers
+    }
+}
+
+#[async_trait]
+impl BlockRepositoryTrait for BlockStore {
+    fn new(_database_pool: Arc<PgPool>, _chain_config: ChainConfig) -> Self {
+        Self::default()
+    }
+
+    async fn get_indexed_blocks(&self) -> Result<Vec<u64>, sqlx::Error> {
+        let blocks = self.blocks.lock().unwrap();
+        Ok(blocks.iter().map(|block| block.block_number).collect())
+    }
+
+    async fn get_last_indexed_block(&self) -> Result<Option<u64>, sqlx::Error> {
+        let blocks = self.blocks.lock().unwrap();
+        Ok(blocks.iter().map(|block| block.block_number).max())
+    }
+
+    async fn insert_block(&self, block: Block) -> Result<(), sqlx::Error> {
+        self.blocks.lock().unwrap().push(block);
+        Ok(())
+    }
+
+    async fn insert_blocks_bulk(&self, blocks: &[Block]) -> Result<(), sqlx::Error> {
+        self.blocks.lock().unwrap().extend_from_slice(blocks);
+        Ok(())
+    }
+
+    async fn get_indexed_range(&self) -> Result<Option<IndexedRange>, sqlx::Error> {
+        let numbers = self.block_numbers();
+        Ok(match (numbers.first(), numbers.last()) {
+            (Some(&first_block), Some(&last_block)) => Some(IndexedRange {
+                first_block,
+                last_block,
+                block_count: numbers.len() as u64,
+            }),
+            _ => None,
+        })
+    }
+
+    async fn get_gaps(&self, limit: u64) -> Result<Vec<(u64, u64)>, sqlx::Error> {
+        Ok(self
+            .block_numbers()
+            .windows(2)
+            .filter(|pair| pair[1] > pair[0] + 1)
+            .map(|pair| (pair[0] + 1, pair[1] - 1))
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn get_block_hashes(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<(u64, Option<String>)>, sqlx::Error> {
+        let blocks = self.blocks.lock().unwrap();
+        let mut hashes: Vec<(u64, Option<String>)> = blocks
+            .iter()
+            .filter(|block| (from..=to).contains(&block.block_number))
+            .map(|block| (block.block_number, block.hash.clone()))
+            .collect();
+        hashes.sort_by_key(|(number, _)| *number);
+        hashes.dedup_by_key(|(number, _)| *number);
+        Ok(hashes)
+    }
+
+    async fn delete_blocks_above(&self, block_number: u64) -> Result<u64, sqlx::Error> {
+        let mut blocks = self.blocks.lock().unwrap();
+        let count = blocks.len();
+        blocks.retain(|block| block.block_number <= block_number);
+        Ok((count - blocks.len()) as u64)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Discard;
+
+#[async_trait]
+impl TransactionRepositoryTrait for Discard {
+    fn new(_database_pool: Arc<PgPool>, _chain_config: ChainConfig) -> Self {
+        Self
+    }
+
+    async fn insert_transactions(
+        &self,
+        _transactions: &[TransactionRecord],
+    ) -> Result<(), sqlx::Error> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LogRepositoryTrait for Discard {
+    fn new(_database_pool: Arc<PgPool>, _chain_config: ChainConfig) -> Self {
+        Self
+    }
+
+    async fn archive_logs(&self, _logs: &[Log]) -> Result<(), sqlx::Error> {
+        Ok(())
+    }
+}
+
+/// Fails the next `failures` writes, as during a database outage.
+#[derive(Clone, Default)]
+pub struct FlakyStore {
+    pub failures: Arc<AtomicU64>,
+}
+
+impl FlakyStore {
+    pub fn failing(failures: u64) -> Self {
+        Self {
+            failures: Arc::new(AtomicU64::new(failures)),
+        }
+    }
+
+    fn write(&self) -> Result<(), sqlx::Error> {
+        match self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        {
+            Ok(_) => Err(sqlx::Error::PoolTimedOut),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionRepositoryTrait for FlakyStore {
+    fn new(_database_pool: Arc<PgPool>, _chain_config: ChainConfig) -> Self {
+        Self::default()
+    }
+
+    async fn insert_transactions(
+        &self,
+        _transactions: &[TransactionRecord],
+    ) -> Result<(), sqlx::Error> {
+        self.write()
+    }
+}
+
+#[async_trait]
+impl LogRepositoryTrait for FlakyStore {
+    fn new(_database_pool: Arc<PgPool>, _chain_config: ChainConfig) -> Self {
+        Self::default()
+    }
+
+    async fn archive_logs(&self, _logs: &[Log]) -> Result<(), sqlx::Error> {
+        self.write()
+    }
+}
+
+/// Start block, end block and status of a leased range.
+type LeasedRange = (u64, u64, &'static str);
+
+/// Ranges of a sharded backfill run by a single instance, whose leases never
+/// expire.
+#[derive(Clone, Default)]
+pub struct LeaseTable {
+    pub ranges: Arc<Mutex<Vec<LeasedRange>>>,
+}
+
+impl LeaseTable {
+    pub fn statuses(&self) -> Vec<&'static str> {
+        let ranges = self.ranges.lock().unwrap();
+        ranges.iter().map(|(_, _, status)| *status).collect()
+    }
+
+    fn set_status(&self, lease: &BlockRangeLease, status: &'static str) -> bool {
+        let mut ranges = self.ranges.lock().unwrap();
+        match ranges.iter_mut().find(|(start_block, _, current)| {
+            *start_block == lease.start_block && *current == "claimed"
+        }) {
+            Some(range) => {
+                range.2 = status;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[async_trait]
+impl LeaseRepositoryTrait for LeaseTable {
+    fn new(
+        _database_pool: Arc<PgPool>,
+        _chain_config: ChainConfig,
+        _instance_id: String,
+        _lease_ttl: u64,
+    ) -> Self {
+        Self::default()
+    }
+
+    fn lease_ttl(&self) -> u64 {
+        300
+    }
+
+    async fn create_ranges(
+        &self,
+        start_block: u64,
+        end_block: u64,
+        range_size: u64,
+    ) -> Result<(), sqlx::Error> {
+        let mut ranges = self.ranges.lock().unwrap();
+        for range_start in (start_block..=end_block).step_by(range_size as usize) {
+            if !ranges.iter().any(|(start, _, _)| *start == range_start) {
+                let range_end = end_block.min(range_start + range_size - 1);
+                ranges.push((range_start, range_end, "pending"));
+            }
+        }
+        Ok(())
+    }
+
+    async fn claim_range(&self) -> Result<Option<BlockRangeLease>, sqlx::Error> {
+        let mut ranges = self.ranges.lock().unwrap();
+        Ok(ranges
+            .iter_mut()
+            .find(|(_, _, status)| *status == "pending")
+            .map(|range| {
+                range.2 = "claimed";
+                BlockRangeLease {
+                    start_block: range.0,
+                    end_block: range.1,
+                    reclaimed: false,
+                }
+            }))
+    }
+
+    async fn renew_lease(&self, lease: &BlockRangeLease) -> Result<bool, sqlx::Error> {
+        Ok(self.set_status(lease, "claimed"))
+    }
+
+    async fn complete_range(&self, lease: &BlockRangeLease) -> Result<bool, sqlx::Error> {
+        Ok(self.set_status(lease, "done"))
+    }
+
+    async fn remaining_ranges(&self) -> Result<u64, sqlx::Error> {
+        let ranges = self.ranges.lock().unwrap();
+        Ok(ranges
+            .iter()
+            .filter(|(_, _, status)| *status != "done")
+            .count() as u64)
+    }
+}
+
+pub fn synchronizer<B: BlockchainClientTrait>(
+    blockchain_client: B,
+    sink: StreamSink,
+    blocks: BlockStore,
+) -> ChainSynchronizer<B, StreamSink, BlockStore, Discard, Discard> {
+    ChainSynchronizer::new(
+        blockchain_client,
+        sink,
+        blocks,
+        Discard,
+        Discard,
+        sync_config(),
+    )
+}
+
+/// Serves `chain` on a free port.
+pub async fn serve(chain: MockChain) -> (MockNode, ServerHandle) {
+    let node = MockNode::new(chain);
+    let server = node.serve(([127, 0, 0, 1], 0).into()).await.unwrap();
+    (node, server)
+}
+
+#[derive(Clone, Default)]
+pub struct CheckpointStore {
+    /// Keyed by lane name.
+    pub checkpoints: Arc<Mutex<HashMap<String, SyncCheckpoint>>>,
+}
+
+#[async_trait]
+impl CheckpointRepositoryTrait for CheckpointStore {
+    fn new(_database_pool: Arc<PgPool>, _chain_config: ChainConfig) -> Self {
+        Self::default()
+    }
+
+    async fn get_checkpoint(
+        &self,
+        lane: SyncLane,
+    ) -> Result<Option<SyncCheckpoint>, sqlx::Error> {
+        Ok(self
+            .checkpoints
+            .lock()
+            .unwrap()
+            .get(&lane.to_string())
+            .copied())
+    }
+
+    async fn save_checkpoint(
+        &self,
+        lane: SyncLane,
+        checkpoint: SyncCheckpoint,
+    ) -> Result<(), sqlx::Error> {
+        self.checkpoints
+            .lock()
+            .unwrap()
+            .insert(lane.to_string(), checkpoint);
+        Ok(())
+    }
+
+    async fn rewind_checkpoints(&self, block_number: u64) -> Result<(), sqlx::Error> {
+        for checkpoint in self.checkpoints.lock().unwrap().values_mut() {
+            checkpoint.last_block = match checkpoint.last_block {
+                Some(_) if checkpoint.start_block > block_number => None,
+                last_block => last_block.map(|last| last.min(block_number)),
+            };
+        }
+        Ok(())
+    }
+}
+
+/// First blocks at timestamps, keyed by timestamp.
+#[derive(Clone, Default)]
+pub struct BlockTimeStore {
+    pub blocks: Arc<Mutex<HashMap<u64, u64>>>,
+}
+
+#[async_trait]
+impl BlockTimeRepositoryTrait for BlockTimeStore {
+    fn new(_database_pool: Arc<PgPool>, _chain_config: ChainConfig) -> Self {
+        Self::default()
+    }
+
+    async fn get_first_block_at(
+        &self,
+        timestamp: u64,
+    ) -> Result<Option<u64>, sqlx::Error> {
+        Ok(self.blocks.lock().unwrap().get(&timestamp).copied())
+    }
+
+    async fn save_first_block_at(
+        &self,
+        timestamp: u64,
+        block_number: u64,
+    ) -> Result<(), sqlx::Error> {
+        self.blocks.lock().unwrap().insert(timestamp, block_number);
+        Ok(())
+    }
+}
+
+/// A pool on the migrated database at `DATABASE_URL`. Tests keep apart by
+/// using chain ids of their own, and are ignored unless run with
+/// `--include-ignored`.
+pub async fn database_pool() -> Arc<PgPool> {
+    let database_url = std::env::var("DATABASE_URL")
+        .expect("DATABASE_URL must be set to run the database tests");
+    let database_pool = PgPool::connect(&database_url).await.unwrap();
+    sqlx::migrate!("../../migrations")
+        .run(&database_pool)
+        .await
+        .unwrap();
+    Arc::new(database_pool)
+}
+
+/// The test chain under another id.
+pub fn chain_config_with_id(id: u32) -> ChainConfig {
+    ChainConfig {
+        id,
+        ..chain_config()
+    }
+}
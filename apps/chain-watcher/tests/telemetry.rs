@@ -0,0 +1,122 @@
+mod support;
+
+use std::time::Duration;
+
+use chain_watcher::clients::blockchain_client::BlockchainClient;
+use common::telemetry;
+use ethers::types::U256;
+use mock_node::{
+    chain::{MockChain, MockTransaction},
+    collector::{CollectedSpan, MockCollector},
+    events::{address, Event},
+};
+use support::{serve, synchronizer, BlockStore, StreamSink};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// Polls the collector until the trace has a span named after each of
+/// `names`, and returns the trace's spans.
+async fn collected_trace(
+    collector: &MockCollector,
+    trace_id: &str,
+    names: &[&str],
+) -> Vec<CollectedSpan> {
+    for _ in 0..50 {
+        let spans: Vec<CollectedSpan> = collector
+            .spans()
+            .into_iter()
+            .filter(|span| span.trace_id == trace_id)
+            .collect();
+        if names
+            .iter()
+            .all(|name| spans.iter().any(|span| span.name == *name))
+        {
+            return spans;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!(
+        "trace {} is missing some of {:?}, collected {:?}",
+        trace_id,
+        names,
+        collector.spans()
+    );
+}
+
+fn span<'a>(spans: &'a [CollectedSpan], name: &str) -> &'a CollectedSpan {
+    spans.iter().find(|span| span.name == name).unwrap()
+}
+
+// The subscriber is global, since blocks are fetched on other threads, so
+// this file holds a single test.
+#[tokio::test(flavor = "multi_thread")]
+async fn follows_a_block_from_the_rpc_to_the_indexer() {
+    let collector = MockCollector::new();
+    let collector_server = collector.serve(([127, 0, 0, 1], 0).into()).await.unwrap();
+    tracing_subscriber::registry()
+        .with(
+            telemetry::otlp_layer("chain-watcher", &collector_server.http_url()).unwrap(),
+        )
+        .with(EnvFilter::new("info"))
+        .init();
+
+    let mut chain = MockChain::new(1);
+    chain.mine(vec![MockTransaction::new(address(1), address(10))
+        .with_event(Event::Erc721Transfer {
+            contract: address(10),
+            from: address(1),
+            to: address(2),
+            token_id: U256::one(),
+        })]);
+    let (_node, server) = serve(chain).await;
+    let client = BlockchainClient::connect(&[server.http_url()], 1)
+        .await
+        .unwrap();
+    let sink = StreamSink::default();
+    synchronizer(client, sink.clone(), BlockStore::default())
+        .sync(1, 1)
+        .await
+        .unwrap();
+
+    let envelope = sink.envelopes.lock().unwrap()[0].clone();
+    let trace_context = envelope.trace_context.clone().unwrap();
+    let [_, trace_id, parent_id, _]: [&str; 4] = trace_context
+        .split('-')
+        .collect::<Vec<_>>()
+        .try_into()
+        .unwrap();
+
+    // What the indexer does with the message.
+    let index_span = tracing::info_span!("index", block_number = envelope.block_number);
+    telemetry::set_parent(&index_span, Some(&trace_context));
+    index_span.in_scope(|| tracing::info_span!("insert_transfer").in_scope(|| {}));
+    drop(index_span);
+    // Exports the buffered spans.
+    tokio::task::spawn_blocking(telemetry::shutdown)
+        .await
+        .unwrap();
+
+    let spans = collected_trace(
+        &collector,
+        trace_id,
+        &[
+            "block",
+            "fetch_block",
+            "fetch_receipt",
+            "publish",
+            "index",
+            "insert_transfer",
+        ],
+    )
+    .await;
+    let block = span(&spans, "block");
+    assert_eq!(block.service_name, "chain-watcher");
+    assert_eq!(block.span_id, parent_id);
+    assert_eq!(block.parent_span_id, "");
+    for name in ["fetch_block", "fetch_receipt", "publish", "index"] {
+        assert_eq!(span(&spans, name).parent_span_id, block.span_id, "{}", name);
+    }
+    assert_eq!(
+        span(&spans, "insert_transfer").parent_span_id,
+        span(&spans, "index").span_id
+    );
+}
@@ -0,0 +1,136 @@
+mod support;
+
+use std::sync::Arc;
+
+use chain_watcher::services::repositories::transaction::{
+    TransactionRecord, TransactionRepository, TransactionRepositoryTrait,
+};
+use ethers::types::U256;
+use sqlx::{Executor, PgPool};
+use support::{chain_config_with_id, database_pool};
+
+fn transaction(chain_id: u32, block_number: u64, hash: &str) -> TransactionRecord {
+    TransactionRecord {
+        chain_id,
+        block_number,
+        block_hash: Some(format!("0x{:064x}", block_number)),
+        hash: hash.to_string(),
+        tx_index: 0,
+        from: "0x0000000000000000000000000000000000000001".to_string(),
+        to: Some("0x0000000000000000000000000000000000000002".to_string()),
+        value: "1000".to_string(),
+        nonce: "3".to_string(),
+        input_selector: Some("0x23b872dd".to_string()),
+        gas_used: Some("21000".to_string()),
+        effective_gas_price: Some("7".to_string()),
+        status: Some(1),
+        contract_address: None,
+        transaction_type: Some(2),
+    }
+}
+
+/// A repository for `chain_id`, with the chain's transactions deleted.
+async fn transaction_repository(
+    database_pool: &Arc<PgPool>,
+    chain_id: u32,
+) -> TransactionRepository {
+    sqlx::query("DELETE FROM transaction WHERE chain_id = $1")
+        .bind(chain_id as i32)
+        .execute(&**database_pool)
+        .await
+        .unwrap();
+    TransactionRepository::new(database_pool.clone(), chain_config_with_id(chain_id))
+}
+
+/// Block number, value, nonce, gas used, gas price and status of each of the
+/// chain's transactions.
+async fn stored(
+    database_pool: &PgPool,
+    chain_id: u32,
+) -> Vec<(
+    i64,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<i16>,
+)> {
+    sqlx::query_as(
+        "SELECT block_number, value::TEXT, nonce::TEXT, gas_used::TEXT, effective_gas_price::TEXT, status FROM transaction WHERE chain_id = $1 ORDER BY hash",
+    )
+    .bind(chain_id as i32)
+    .fetch_all(database_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn reinserting_a_transaction_replaces_its_row() {
+    let database_pool = database_pool().await;
+    let repository = transaction_repository(&database_pool, 34_101).await;
+
+    repository
+        .insert_transactions(&[transaction(34_101, 5, "0xaa")])
+        .await
+        .unwrap();
+    // The same transaction mined again in another block after a reorg.
+    let reorged = TransactionRecord {
+        gas_used: Some("42000".to_string()),
+        status: Some(0),
+        ..transaction(34_101, 6, "0xaa")
+    };
+    repository.insert_transactions(&[reorged]).await.unwrap();
+
+    assert_eq!(
+        stored(&database_pool, 34_101).await,
+        vec![(
+            6,
+            "1000".to_string(),
+            "3".to_string(),
+            Some("42000".to_string()),
+            Some("7".to_string()),
+            Some(0)
+        )]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn stores_quantities_beyond_64_bits() {
+    let database_pool = database_pool().await;
+    let repository = transaction_repository(&database_pool, 34_102).await;
+    let value = U256::MAX.to_string();
+    let gas_price = (U256::from(u64::MAX) + 1).to_string();
+
+    repository
+        .insert_transactions(&[TransactionRecord {
+            value: value.clone(),
+            effective_gas_price: Some(gas_price.clone()),
+            ..transaction(34_102, 1, "0xaa")
+        }])
+        .await
+        .unwrap();
+
+    let (_, stored_value, _, _, stored_gas_price, _) =
+        stored(&database_pool, 34_102).await.remove(0);
+    assert_eq!(stored_value, value);
+    assert_eq!(stored_gas_price, Some(gas_price));
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn rejects_duplicate_hashes() {
+    let database_pool = database_pool().await;
+    transaction_repository(&database_pool, 34_103).await;
+    let insert = "INSERT INTO transaction (chain_id, block_number, hash, tx_index, \"from\", value, nonce) VALUES (34103, 1, '0xaa', 0, '0x01', 0, 0)";
+
+    database_pool.execute(insert).await.unwrap();
+    let error = database_pool.execute(insert).await.unwrap_err();
+
+    let constraint = error
+        .as_database_error()
+        .and_then(|error| error.constraint())
+        .map(str::to_string);
+    assert_eq!(constraint.as_deref(), Some("transaction_chain_id_hash_key"));
+}
@@ -0,0 +1,92 @@
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use crate::types::ChainConfig;
+
+const BUNDLED_CHAINS: &str = include_str!("../chains.json");
+
+#[derive(Debug)]
+pub enum ChainRegistryError {
+    ReadError(String, String),
+    ParseError(String, String),
+    UnknownChain(u32),
+}
+
+impl fmt::Display for ChainRegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainRegistryError::ReadError(error, path) => {
+                write!(f, "Read Error: {}. Path: {}", error, path)
+            }
+            ChainRegistryError::ParseError(error, source) => {
+                write!(f, "Parse Error: {}. Source: {}", error, source)
+            }
+            ChainRegistryError::UnknownChain(id) => {
+                write!(
+                    f,
+                    "Unknown chain id {}. Add it to a chains file and pass it with --chains-file.",
+                    id
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChainRegistryError {}
+
+/// Chains known to the services, keyed by chain id.
+///
+/// The registry always starts from the chains bundled with the crate. A
+/// user-supplied file, in the same JSON format, adds new chains and replaces
+/// bundled entries that share a chain id.
+#[derive(Debug, Clone)]
+pub struct ChainRegistry {
+    chains: HashMap<u32, ChainConfig>,
+}
+
+impl ChainRegistry {
+    pub fn bundled() -> Self {
+        let chains = Self::parse(BUNDLED_CHAINS, "bundled chains.json")
+            .expect("Bundled chains.json is invalid.");
+        Self::from_chains(chains)
+    }
+
+    pub fn load(path: Option<&Path>) -> Result<Self, ChainRegistryError> {
+        let mut registry = Self::bundled();
+        if let Some(path) = path {
+            let source = path.display().to_string();
+            let content = fs::read_to_string(path).map_err(|e| {
+                ChainRegistryError::ReadError(e.to_string(), source.clone())
+            })?;
+            for chain in Self::parse(&content, &source)? {
+                registry.chains.insert(chain.id, chain);
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn from_chains(chains: Vec<ChainConfig>) -> Self {
+        Self {
+            chains: chains.into_iter().map(|chain| (chain.id, chain)).collect(),
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Result<ChainConfig, ChainRegistryError> {
+        self.chains
+            .get(&id)
+            .cloned()
+            .ok_or(ChainRegistryError::UnknownChain(id))
+    }
+
+    pub fn chains(&self) -> impl Iterator<Item = &ChainConfig> {
+        self.chains.values()
+    }
+
+    fn parse(
+        content: &str,
+        source: &str,
+    ) -> Result<Vec<ChainConfig>, ChainRegistryError> {
+        serde_json::from_str(content).map_err(|e| {
+            ChainRegistryError::ParseError(e.to_string(), source.to_string())
+        })
+    }
+}
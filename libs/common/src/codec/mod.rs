@@ -0,0 +1,192 @@
+mod proto;
+
+use std::{fmt, str::FromStr};
+
+use prost::Message;
+use serde::Deserialize;
+
+use crate::types::{EnvelopeError, StreamEnvelope, STREAM_SCHEMA_VERSION};
+
+/// Stream field naming the encoding of the `message` field. Entries without
+/// it are JSON.
+pub const ENCODING_FIELD: &str = "encoding";
+
+const ZSTD_SUFFIX: &str = "+zstd";
+
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug)]
+pub enum CodecError {
+    UnknownEncoding(String),
+    EncodeError(String),
+    DecodeError(String),
+    EnvelopeError(EnvelopeError),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::UnknownEncoding(name) => write!(
+                f,
+                "Unknown Encoding: {}. Expected json, msgpack, cbor or protobuf, optionally followed by +zstd.",
+                name
+            ),
+            CodecError::EncodeError(error) => write!(f, "Encode Error: {}", error),
+            CodecError::DecodeError(error) => write!(f, "Decode Error: {}", error),
+            CodecError::EnvelopeError(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<EnvelopeError> for CodecError {
+    fn from(error: EnvelopeError) -> Self {
+        CodecError::EnvelopeError(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+    /// The schema in `libs/common/proto/stream.proto`. Hashes, addresses and
+    /// amounts are raw bytes rather than hex strings.
+    Protobuf,
+}
+
+impl Format {
+    fn name(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::MessagePack => "msgpack",
+            Format::Cbor => "cbor",
+            Format::Protobuf => "protobuf",
+        }
+    }
+}
+
+/// How a [`StreamEnvelope`] is written to the `message` field, named in the
+/// stream as e.g. `msgpack` or `protobuf+zstd`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encoding {
+    pub format: Format,
+    pub compressed: bool,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self {
+            format: Format::Json,
+            compressed: false,
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let suffix = if self.compressed { ZSTD_SUFFIX } else { "" };
+        write!(f, "{}{}", self.format.name(), suffix)
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = CodecError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let (format, compressed) = match name.strip_suffix(ZSTD_SUFFIX) {
+            Some(format) => (format, true),
+            None => (name, false),
+        };
+        let format = match format {
+            "json" => Format::Json,
+            "msgpack" => Format::MessagePack,
+            "cbor" => Format::Cbor,
+            "protobuf" => Format::Protobuf,
+            _ => return Err(CodecError::UnknownEncoding(name.to_string())),
+        };
+        Ok(Self { format, compressed })
+    }
+}
+
+impl Encoding {
+    pub fn encode(&self, envelope: &StreamEnvelope) -> Result<Vec<u8>, CodecError> {
+        let encoded = match self.format {
+            Format::Json => serde_json::to_vec(envelope)
+                .map_err(|e| CodecError::EncodeError(e.to_string()))?,
+            Format::MessagePack => rmp_serde::to_vec_named(envelope)
+                .map_err(|e| CodecError::EncodeError(e.to_string()))?,
+            Format::Cbor => {
+                let mut buffer = Vec::new();
+                ciborium::into_writer(envelope, &mut buffer)
+                    .map_err(|e| CodecError::EncodeError(e.to_string()))?;
+                buffer
+            }
+            Format::Protobuf => proto::Envelope::from(envelope).encode_to_vec(),
+        };
+
+        if !self.compressed {
+            return Ok(encoded);
+        }
+        zstd::encode_all(encoded.as_slice(), ZSTD_LEVEL)
+            .map_err(|e| CodecError::EncodeError(e.to_string()))
+    }
+
+    /// Decodes and validates a message read from a stream of `chain_id`. See
+    /// [`StreamEnvelope::decode`] for the checks, which apply to every format.
+    pub fn decode(
+        &self,
+        message: &[u8],
+        chain_id: u32,
+    ) -> Result<StreamEnvelope, CodecError> {
+        let decompressed;
+        let message = if self.compressed {
+            decompressed = zstd::decode_all(message)
+                .map_err(|e| CodecError::DecodeError(e.to_string()))?;
+            decompressed.as_slice()
+        } else {
+            message
+        };
+
+        let envelope: StreamEnvelope = match self.format {
+            Format::Json => return Ok(StreamEnvelope::decode(message, chain_id)?),
+            Format::MessagePack => {
+                if let Ok(version) = rmp_serde::from_slice::<SchemaVersion>(message) {
+                    version.check()?;
+                }
+                rmp_serde::from_slice(message)
+                    .map_err(|e| CodecError::DecodeError(e.to_string()))?
+            }
+            Format::Cbor => {
+                if let Ok(version) = ciborium::from_reader::<SchemaVersion, _>(message) {
+                    version.check()?;
+                }
+                ciborium::from_reader(message)
+                    .map_err(|e| CodecError::DecodeError(e.to_string()))?
+            }
+            Format::Protobuf => proto::Envelope::decode(message)
+                .map_err(|e| CodecError::DecodeError(e.to_string()))?
+                .try_into()?,
+        };
+
+        envelope.validate(chain_id)?;
+        Ok(envelope)
+    }
+}
+
+/// Read ahead of the full envelope, so a message from a newer producer reports
+/// its version instead of whichever field it fails to parse.
+#[derive(Deserialize)]
+struct SchemaVersion {
+    schema_version: u64,
+}
+
+impl SchemaVersion {
+    fn check(&self) -> Result<(), EnvelopeError> {
+        if self.schema_version > STREAM_SCHEMA_VERSION as u64 {
+            return Err(EnvelopeError::UnsupportedVersion(self.schema_version));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,660 @@
+//! Protobuf messages for [`StreamEnvelope`], kept in step by hand with
+//! `libs/common/proto/stream.proto`.
+
+use ethers::types::{Bytes, H160, H256, U256};
+
+use crate::types::{
+    ContractCreation, EnvelopeError, FinalityStatus, MessageKind, PendingResolution,
+    PendingStatus, PendingTransfer, StreamEnvelope, StreamPayload, SummaryHeader,
+    SummaryLog, SummaryTransaction, SyncLane, TransferMethod, STREAM_SCHEMA_VERSION,
+};
+
+use super::CodecError;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Envelope {
+    #[prost(uint32, tag = "1")]
+    pub schema_version: u32,
+    #[prost(enumeration = "Kind", tag = "2")]
+    pub kind: i32,
+    #[prost(uint32, tag = "3")]
+    pub chain_id: u32,
+    #[prost(uint64, tag = "4")]
+    pub block_number: u64,
+    #[prost(bytes = "vec", optional, tag = "5")]
+    pub block_hash: Option<Vec<u8>>,
+    #[prost(enumeration = "Finality", tag = "6")]
+    pub finality: i32,
+    #[prost(string, tag = "7")]
+    pub producer_id: String,
+    #[prost(oneof = "Payload", tags = "8, 9, 10, 11, 13, 14, 15")]
+    pub payload: Option<Payload>,
+    #[prost(enumeration = "Lane", tag = "12")]
+    pub lane: i32,
+    #[prost(string, optional, tag = "16")]
+    pub trace_context: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum Payload {
+    #[prost(message, tag = "8")]
+    Logs(Logs),
+    #[prost(message, tag = "9")]
+    Revert(Revert),
+    #[prost(message, tag = "10")]
+    Header(Header),
+    #[prost(message, tag = "11")]
+    Transaction(Transaction),
+    #[prost(message, tag = "13")]
+    PendingTransfer(Pending),
+    #[prost(message, tag = "14")]
+    PendingResolution(Resolution),
+    #[prost(message, tag = "15")]
+    ContractCreations(Creations),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub enum Kind {
+    Logs = 0,
+    Revert = 1,
+    Header = 2,
+    Transaction = 3,
+    PendingTransfer = 4,
+    PendingResolution = 5,
+    ContractCreations = 6,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub enum Finality {
+    Unfinalized = 0,
+    Finalized = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub enum Lane {
+    Live = 0,
+    Backfill = 1,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Logs {
+    #[prost(message, repeated, tag = "1")]
+    pub logs: Vec<Log>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Revert {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Log {
+    #[prost(bytes = "vec", tag = "1")]
+    pub address: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub data: Vec<u8>,
+    #[prost(uint64, optional, tag = "3")]
+    pub block_number: Option<u64>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub block_hash: Option<Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "5")]
+    pub transaction_hash: Option<Vec<u8>>,
+    #[prost(uint64, optional, tag = "6")]
+    pub transaction_index: Option<u64>,
+    #[prost(bytes = "vec", repeated, tag = "7")]
+    pub topics: Vec<Vec<u8>>,
+    #[prost(uint64, optional, tag = "8")]
+    pub log_index: Option<u64>,
+    #[prost(bool, optional, tag = "9")]
+    pub removed: Option<bool>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Header {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub hash: Option<Vec<u8>>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub parent_hash: Vec<u8>,
+    #[prost(uint64, optional, tag = "3")]
+    pub number: Option<u64>,
+    #[prost(uint64, tag = "4")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", optional, tag = "5")]
+    pub miner: Option<Vec<u8>>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub gas_used: Vec<u8>,
+    #[prost(bytes = "vec", tag = "7")]
+    pub gas_limit: Vec<u8>,
+    #[prost(bytes = "vec", optional, tag = "8")]
+    pub base_fee_per_gas: Option<Vec<u8>>,
+    #[prost(uint64, tag = "9")]
+    pub transaction_count: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Transaction {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: Vec<u8>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub block_hash: Option<Vec<u8>>,
+    #[prost(uint64, optional, tag = "3")]
+    pub block_number: Option<u64>,
+    #[prost(uint64, optional, tag = "4")]
+    pub chain_id: Option<u64>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub input: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub from: Vec<u8>,
+    #[prost(bytes = "vec", optional, tag = "7")]
+    pub to: Option<Vec<u8>>,
+    #[prost(bytes = "vec", tag = "8")]
+    pub nonce: Vec<u8>,
+    #[prost(uint64, optional, tag = "9")]
+    pub transaction_index: Option<u64>,
+    #[prost(bytes = "vec", tag = "10")]
+    pub value: Vec<u8>,
+    #[prost(uint64, optional, tag = "11")]
+    pub transaction_type: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Pending {
+    #[prost(bytes = "vec", tag = "1")]
+    pub transaction_hash: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub sender: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub contract: Vec<u8>,
+    #[prost(enumeration = "Method", tag = "4")]
+    pub method: i32,
+    #[prost(bytes = "vec", tag = "5")]
+    pub from: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub to: Vec<u8>,
+    #[prost(bytes = "vec", repeated, tag = "7")]
+    pub token_ids: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", repeated, tag = "8")]
+    pub amounts: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", tag = "9")]
+    pub nonce: Vec<u8>,
+    #[prost(bytes = "vec", optional, tag = "10")]
+    pub gas_price: Option<Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "11")]
+    pub max_priority_fee_per_gas: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub enum Method {
+    Plain = 0,
+    Safe = 1,
+    SafeBatch = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Resolution {
+    #[prost(bytes = "vec", tag = "1")]
+    pub transaction_hash: Vec<u8>,
+    #[prost(enumeration = "Status", tag = "2")]
+    pub status: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub enum Status {
+    Included = 0,
+    Dropped = 1,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Creations {
+    #[prost(message, repeated, tag = "1")]
+    pub creations: Vec<Creation>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Creation {
+    #[prost(bytes = "vec", tag = "1")]
+    pub address: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub creator: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub sender: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub transaction_hash: Vec<u8>,
+    #[prost(uint64, tag = "5")]
+    pub transaction_index: u64,
+    #[prost(bytes = "vec", optional, tag = "6")]
+    pub code_hash: Option<Vec<u8>>,
+}
+
+/// Big-endian, without leading zero bytes.
+fn u256_bytes(value: U256) -> Vec<u8> {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    let first = bytes.iter().position(|byte| *byte != 0).unwrap_or(32);
+    bytes[first..].to_vec()
+}
+
+fn to_u256(bytes: &[u8]) -> Result<U256, CodecError> {
+    if bytes.len() > 32 {
+        return Err(CodecError::DecodeError(format!(
+            "expected at most 32 bytes for a uint256, got {}",
+            bytes.len()
+        )));
+    }
+    Ok(U256::from_big_endian(bytes))
+}
+
+fn to_h256(bytes: &[u8]) -> Result<H256, CodecError> {
+    if bytes.len() != 32 {
+        return Err(CodecError::DecodeError(format!(
+            "expected 32 bytes for a hash, got {}",
+            bytes.len()
+        )));
+    }
+    Ok(H256::from_slice(bytes))
+}
+
+fn to_h160(bytes: &[u8]) -> Result<H160, CodecError> {
+    if bytes.len() != 20 {
+        return Err(CodecError::DecodeError(format!(
+            "expected 20 bytes for an address, got {}",
+            bytes.len()
+        )));
+    }
+    Ok(H160::from_slice(bytes))
+}
+
+impl From<&StreamEnvelope> for Envelope {
+    fn from(envelope: &StreamEnvelope) -> Self {
+        let kind = match envelope.kind {
+            MessageKind::Logs => Kind::Logs,
+            MessageKind::Revert => Kind::Revert,
+            MessageKind::Header => Kind::Header,
+            MessageKind::Transaction => Kind::Transaction,
+            MessageKind::PendingTransfer => Kind::PendingTransfer,
+            MessageKind::PendingResolution => Kind::PendingResolution,
+            MessageKind::ContractCreations => Kind::ContractCreations,
+        };
+        let finality = match envelope.finality {
+            FinalityStatus::Unfinalized => Finality::Unfinalized,
+            FinalityStatus::Finalized => Finality::Finalized,
+        };
+        let payload = match &envelope.payload {
+            StreamPayload::Logs(logs) => Payload::Logs(Logs {
+                logs: logs.iter().map(Log::from).collect(),
+            }),
+            StreamPayload::Revert => Payload::Revert(Revert {}),
+            StreamPayload::Header(header) => Payload::Header(header.into()),
+            StreamPayload::Transaction(transaction) => {
+                Payload::Transaction(transaction.into())
+            }
+            StreamPayload::PendingTransfer(transfer) => {
+                Payload::PendingTransfer(transfer.into())
+            }
+            StreamPayload::PendingResolution(resolution) => {
+                Payload::PendingResolution(resolution.into())
+            }
+            StreamPayload::ContractCreations(creations) => {
+                Payload::ContractCreations(Creations {
+                    creations: creations.iter().map(Creation::from).collect(),
+                })
+            }
+        };
+
+        Self {
+            schema_version: envelope.schema_version as u32,
+            kind: kind as i32,
+            chain_id: envelope.chain_id,
+            block_number: envelope.block_number,
+            block_hash: envelope.block_hash.map(|hash| hash.as_bytes().to_vec()),
+            finality: finality as i32,
+            producer_id: envelope.producer_id.clone(),
+            payload: Some(payload),
+            lane: match envelope.lane {
+                SyncLane::Live => Lane::Live,
+                SyncLane::Backfill => Lane::Backfill,
+            } as i32,
+            trace_context: envelope.trace_context.clone(),
+        }
+    }
+}
+
+impl TryFrom<Envelope> for StreamEnvelope {
+    type Error = CodecError;
+
+    fn try_from(envelope: Envelope) -> Result<Self, Self::Error> {
+        if envelope.schema_version > STREAM_SCHEMA_VERSION as u32 {
+            return Err(EnvelopeError::UnsupportedVersion(
+                envelope.schema_version as u64,
+            )
+            .into());
+        }
+
+        let kind = match Kind::try_from(envelope.kind) {
+            Ok(Kind::Logs) => MessageKind::Logs,
+            Ok(Kind::Revert) => MessageKind::Revert,
+            Ok(Kind::Header) => MessageKind::Header,
+            Ok(Kind::Transaction) => MessageKind::Transaction,
+            Ok(Kind::PendingTransfer) => MessageKind::PendingTransfer,
+            Ok(Kind::PendingResolution) => MessageKind::PendingResolution,
+            Ok(Kind::ContractCreations) => MessageKind::ContractCreations,
+            Err(_) => {
+                return Err(CodecError::DecodeError(format!(
+                    "unknown message kind {}",
+                    envelope.kind
+                )))
+            }
+        };
+        let finality = match Finality::try_from(envelope.finality) {
+            Ok(Finality::Unfinalized) => FinalityStatus::Unfinalized,
+            Ok(Finality::Finalized) => FinalityStatus::Finalized,
+            Err(_) => {
+                return Err(CodecError::DecodeError(format!(
+                    "unknown finality status {}",
+                    envelope.finality
+                )))
+            }
+        };
+        let lane = match Lane::try_from(envelope.lane) {
+            Ok(Lane::Live) => SyncLane::Live,
+            Ok(Lane::Backfill) => SyncLane::Backfill,
+            Err(_) => {
+                return Err(CodecError::DecodeError(format!(
+                    "unknown sync lane {}",
+                    envelope.lane
+                )))
+            }
+        };
+        let payload = match envelope.payload {
+            Some(Payload::Logs(logs)) => StreamPayload::Logs(
+                logs.logs
+                    .into_iter()
+                    .map(SummaryLog::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Some(Payload::Revert(_)) => StreamPayload::Revert,
+            Some(Payload::Header(header)) => StreamPayload::Header(header.try_into()?),
+            Some(Payload::Transaction(transaction)) => {
+                StreamPayload::Transaction(transaction.try_into()?)
+            }
+            Some(Payload::PendingTransfer(transfer)) => {
+                StreamPayload::PendingTransfer(transfer.try_into()?)
+            }
+            Some(Payload::PendingResolution(resolution)) => {
+                StreamPayload::PendingResolution(resolution.try_into()?)
+            }
+            Some(Payload::ContractCreations(creations)) => {
+                StreamPayload::ContractCreations(
+                    creations
+                        .creations
+                        .into_iter()
+                        .map(ContractCreation::try_from)
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            None => return Err(CodecError::DecodeError("missing payload".to_string())),
+        };
+
+        Ok(Self {
+            schema_version: envelope.schema_version as u16,
+            kind,
+            chain_id: envelope.chain_id,
+            block_number: envelope.block_number,
+            block_hash: envelope.block_hash.as_deref().map(to_h256).transpose()?,
+            finality,
+            producer_id: envelope.producer_id,
+            lane,
+            trace_context: envelope.trace_context,
+            payload,
+        })
+    }
+}
+
+impl From<&SummaryLog> for Log {
+    fn from(log: &SummaryLog) -> Self {
+        Self {
+            address: log.address.as_bytes().to_vec(),
+            data: log.data.to_vec(),
+            block_number: log.block_number,
+            block_hash: log.block_hash.map(|hash| hash.as_bytes().to_vec()),
+            transaction_hash: log.transaction_hash.map(|hash| hash.as_bytes().to_vec()),
+            transaction_index: log.transaction_index,
+            topics: log
+                .topics
+                .iter()
+                .map(|topic| topic.as_bytes().to_vec())
+                .collect(),
+            log_index: log.log_index,
+            removed: log.removed,
+        }
+    }
+}
+
+impl TryFrom<Log> for SummaryLog {
+    type Error = CodecError;
+
+    fn try_from(log: Log) -> Result<Self, Self::Error> {
+        Ok(Self {
+            address: to_h160(&log.address)?,
+            data: Bytes::from(log.data),
+            block_number: log.block_number,
+            block_hash: log.block_hash.as_deref().map(to_h256).transpose()?,
+            transaction_hash: log.transaction_hash.as_deref().map(to_h256).transpose()?,
+            transaction_index: log.transaction_index,
+            topics: log
+                .topics
+                .iter()
+                .map(|topic| to_h256(topic))
+                .collect::<Result<_, _>>()?,
+            log_index: log.log_index,
+            removed: log.removed,
+        })
+    }
+}
+
+impl From<&SummaryHeader> for Header {
+    fn from(header: &SummaryHeader) -> Self {
+        Self {
+            hash: header.hash.map(|hash| hash.as_bytes().to_vec()),
+            parent_hash: header.parent_hash.as_bytes().to_vec(),
+            number: header.number,
+            timestamp: header.timestamp,
+            miner: header.miner.map(|miner| miner.as_bytes().to_vec()),
+            gas_used: u256_bytes(header.gas_used),
+            gas_limit: u256_bytes(header.gas_limit),
+            base_fee_per_gas: header.base_fee_per_gas.map(u256_bytes),
+            transaction_count: header.transaction_count,
+        }
+    }
+}
+
+impl TryFrom<Header> for SummaryHeader {
+    type Error = CodecError;
+
+    fn try_from(header: Header) -> Result<Self, Self::Error> {
+        Ok(Self {
+            hash: header.hash.as_deref().map(to_h256).transpose()?,
+            parent_hash: to_h256(&header.parent_hash)?,
+            number: header.number,
+            timestamp: header.timestamp,
+            miner: header.miner.as_deref().map(to_h160).transpose()?,
+            gas_used: to_u256(&header.gas_used)?,
+            gas_limit: to_u256(&header.gas_limit)?,
+            base_fee_per_gas: header
+                .base_fee_per_gas
+                .as_deref()
+                .map(to_u256)
+                .transpose()?,
+            transaction_count: header.transaction_count,
+        })
+    }
+}
+
+impl From<&SummaryTransaction> for Transaction {
+    fn from(transaction: &SummaryTransaction) -> Self {
+        Self {
+            hash: transaction.hash.as_bytes().to_vec(),
+            block_hash: transaction.block_hash.map(|hash| hash.as_bytes().to_vec()),
+            block_number: transaction.block_number,
+            chain_id: transaction.chain_id,
+            input: transaction.input.to_vec(),
+            from: transaction.from.as_bytes().to_vec(),
+            to: transaction.to.map(|to| to.as_bytes().to_vec()),
+            nonce: u256_bytes(transaction.nonce),
+            transaction_index: transaction.transaction_index,
+            value: u256_bytes(transaction.value),
+            transaction_type: transaction.transaction_type,
+        }
+    }
+}
+
+impl TryFrom<Transaction> for SummaryTransaction {
+    type Error = CodecError;
+
+    fn try_from(transaction: Transaction) -> Result<Self, Self::Error> {
+        Ok(Self {
+            hash: to_h256(&transaction.hash)?,
+            block_hash: transaction.block_hash.as_deref().map(to_h256).transpose()?,
+            block_number: transaction.block_number,
+            chain_id: transaction.chain_id,
+            input: Bytes::from(transaction.input),
+            from: to_h160(&transaction.from)?,
+            to: transaction.to.as_deref().map(to_h160).transpose()?,
+            nonce: to_u256(&transaction.nonce)?,
+            transaction_index: transaction.transaction_index,
+            value: to_u256(&transaction.value)?,
+            transaction_type: transaction.transaction_type,
+        })
+    }
+}
+
+impl From<&PendingTransfer> for Pending {
+    fn from(transfer: &PendingTransfer) -> Self {
+        Self {
+            transaction_hash: transfer.transaction_hash.as_bytes().to_vec(),
+            sender: transfer.sender.as_bytes().to_vec(),
+            contract: transfer.contract.as_bytes().to_vec(),
+            method: match transfer.method {
+                TransferMethod::TransferFrom => Method::Plain,
+                TransferMethod::SafeTransferFrom => Method::Safe,
+                TransferMethod::SafeBatchTransferFrom => Method::SafeBatch,
+            } as i32,
+            from: transfer.from.as_bytes().to_vec(),
+            to: transfer.to.as_bytes().to_vec(),
+            token_ids: transfer.token_ids.iter().copied().map(u256_bytes).collect(),
+            amounts: transfer.amounts.iter().copied().map(u256_bytes).collect(),
+            nonce: u256_bytes(transfer.nonce),
+            gas_price: transfer.gas_price.map(u256_bytes),
+            max_priority_fee_per_gas: transfer.max_priority_fee_per_gas.map(u256_bytes),
+        }
+    }
+}
+
+impl TryFrom<Pending> for PendingTransfer {
+    type Error = CodecError;
+
+    fn try_from(transfer: Pending) -> Result<Self, Self::Error> {
+        let method = match Method::try_from(transfer.method) {
+            Ok(Method::Plain) => TransferMethod::TransferFrom,
+            Ok(Method::Safe) => TransferMethod::SafeTransferFrom,
+            Ok(Method::SafeBatch) => TransferMethod::SafeBatchTransferFrom,
+            Err(_) => {
+                return Err(CodecError::DecodeError(format!(
+                    "unknown transfer method {}",
+                    transfer.method
+                )))
+            }
+        };
+
+        Ok(Self {
+            transaction_hash: to_h256(&transfer.transaction_hash)?,
+            sender: to_h160(&transfer.sender)?,
+            contract: to_h160(&transfer.contract)?,
+            method,
+            from: to_h160(&transfer.from)?,
+            to: to_h160(&transfer.to)?,
+            token_ids: transfer
+                .token_ids
+                .iter()
+                .map(|id| to_u256(id))
+                .collect::<Result<_, _>>()?,
+            amounts: transfer
+                .amounts
+                .iter()
+                .map(|amount| to_u256(amount))
+                .collect::<Result<_, _>>()?,
+            nonce: to_u256(&transfer.nonce)?,
+            gas_price: transfer.gas_price.as_deref().map(to_u256).transpose()?,
+            max_priority_fee_per_gas: transfer
+                .max_priority_fee_per_gas
+                .as_deref()
+                .map(to_u256)
+                .transpose()?,
+        })
+    }
+}
+
+impl From<&PendingResolution> for Resolution {
+    fn from(resolution: &PendingResolution) -> Self {
+        Self {
+            transaction_hash: resolution.transaction_hash.as_bytes().to_vec(),
+            status: match resolution.status {
+                PendingStatus::Included => Status::Included,
+                PendingStatus::Dropped => Status::Dropped,
+            } as i32,
+        }
+    }
+}
+
+impl TryFrom<Resolution> for PendingResolution {
+    type Error = CodecError;
+
+    fn try_from(resolution: Resolution) -> Result<Self, Self::Error> {
+        let status = match Status::try_from(resolution.status) {
+            Ok(Status::Included) => PendingStatus::Included,
+            Ok(Status::Dropped) => PendingStatus::Dropped,
+            Err(_) => {
+                return Err(CodecError::DecodeError(format!(
+                    "unknown pending status {}",
+                    resolution.status
+                )))
+            }
+        };
+
+        Ok(Self {
+            transaction_hash: to_h256(&resolution.transaction_hash)?,
+            status,
+        })
+    }
+}
+
+impl From<&ContractCreation> for Creation {
+    fn from(creation: &ContractCreation) -> Self {
+        Self {
+            address: creation.address.as_bytes().to_vec(),
+            creator: creation.creator.as_bytes().to_vec(),
+            sender: creation.sender.as_bytes().to_vec(),
+            transaction_hash: creation.transaction_hash.as_bytes().to_vec(),
+            transaction_index: creation.transaction_index,
+            code_hash: creation.code_hash.map(|hash| hash.as_bytes().to_vec()),
+        }
+    }
+}
+
+impl TryFrom<Creation> for ContractCreation {
+    type Error = CodecError;
+
+    fn try_from(creation: Creation) -> Result<Self, Self::Error> {
+        Ok(Self {
+            address: to_h160(&creation.address)?,
+            creator: to_h160(&creation.creator)?,
+            sender: to_h160(&creation.sender)?,
+            transaction_hash: to_h256(&creation.transaction_hash)?,
+            transaction_index: creation.transaction_index,
+            code_hash: creation.code_hash.as_deref().map(to_h256).transpose()?,
+        })
+    }
+}
@@ -1,2 +1,7 @@
+pub mod chains;
+pub mod codec;
+pub mod logging;
 pub mod redis;
+pub mod telemetry;
+pub mod trie;
 pub mod types;
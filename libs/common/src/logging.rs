@@ -0,0 +1,213 @@
+use std::{fmt, path::PathBuf};
+
+use clap::{Args, ValueEnum};
+use opentelemetry::trace::TraceError;
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{InitError, RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    filter::ParseError,
+    fmt::MakeWriter,
+    layer::SubscriberExt,
+    util::{SubscriberInitExt, TryInitError},
+    EnvFilter, Layer, Registry,
+};
+
+use crate::telemetry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Pretty,
+    /// One JSON object per line.
+    Json,
+}
+
+/// How often the log file is rotated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(rotation: LogRotation) -> Self {
+        match rotation {
+            LogRotation::Minutely => Rotation::MINUTELY,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+/// Logging and tracing options shared by the services.
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct LoggingArgs {
+    #[arg(
+        long,
+        help = "Enables debug logging. Useful for troubleshooting and development. Ignored when a log filter is set. [optional]",
+        default_value_t = false
+    )]
+    pub debug: bool,
+    #[arg(
+        long,
+        env = "RUST_LOG",
+        help = "Log filter directives in RUST_LOG syntax, e.g. info,chain_watcher=debug. [optional]"
+    )]
+    pub log_filter: Option<String>,
+    #[arg(
+        long,
+        help = "Format of log lines: pretty or json. [optional]",
+        value_enum,
+        default_value_t = LogFormat::Pretty
+    )]
+    pub log_format: LogFormat,
+    #[arg(
+        long,
+        help = "Directory of the log files. [optional]",
+        default_value = "./logs"
+    )]
+    pub log_dir: PathBuf,
+    #[arg(
+        long,
+        help = "How often the log file is rotated: minutely, hourly, daily or never. [optional]",
+        value_enum,
+        default_value_t = LogRotation::Daily
+    )]
+    pub log_rotation: LogRotation,
+    #[arg(
+        long,
+        help = "Number of log files kept; older ones are deleted on rotation. All are kept by default. [optional]"
+    )]
+    pub log_max_files: Option<usize>,
+    #[arg(
+        long,
+        help = "Logs to stdout only, without a log file. [optional]",
+        default_value_t = false
+    )]
+    pub no_log_file: bool,
+    #[arg(
+        long,
+        help = "OTLP gRPC endpoint spans are exported to, e.g. http://localhost:4317. [optional]"
+    )]
+    pub otlp_endpoint: Option<String>,
+}
+
+impl LoggingArgs {
+    /// The filter directives in effect: `--log-filter` or `RUST_LOG`, else the
+    /// level chosen by `--debug`.
+    pub fn filter_directives(&self) -> &str {
+        match &self.log_filter {
+            Some(directives) => directives,
+            None if self.debug => "debug",
+            None => "info",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoggingError {
+    FilterError(ParseError),
+    FileError(InitError),
+    TraceError(TraceError),
+    InitError(TryInitError),
+}
+
+impl fmt::Display for LoggingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoggingError::FilterError(error) => {
+                write!(f, "Logging Error: invalid log filter: {}", error)
+            }
+            LoggingError::FileError(error) => {
+                write!(f, "Logging Error: cannot open the log file: {}", error)
+            }
+            LoggingError::TraceError(error) => {
+                write!(f, "Logging Error: cannot export spans: {}", error)
+            }
+            LoggingError::InitError(error) => write!(f, "Logging Error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for LoggingError {}
+
+/// Keeps logging running. Dropping it, before the process exits, flushes the
+/// log file and exports the spans still buffered.
+pub struct LoggingGuard {
+    _file: Option<WorkerGuard>,
+    otlp: bool,
+}
+
+impl Drop for LoggingGuard {
+    fn drop(&mut self) {
+        if self.otlp {
+            telemetry::shutdown();
+        }
+    }
+}
+
+/// Logs to stdout and, unless disabled, to a rotated file named after
+/// `service_name` in the log directory, and exports spans when an OTLP
+/// endpoint is set.
+pub fn init(
+    args: &LoggingArgs,
+    service_name: &'static str,
+) -> Result<LoggingGuard, LoggingError> {
+    let filter = EnvFilter::try_new(args.filter_directives())
+        .map_err(LoggingError::FilterError)?;
+
+    let mut layers = vec![fmt_layer(args.log_format, std::io::stdout, true)];
+    let mut file_guard = None;
+    if !args.no_log_file {
+        let mut builder = RollingFileAppender::builder()
+            .rotation(args.log_rotation.into())
+            .filename_prefix(format!("{}.log", service_name));
+        if let Some(max_files) = args.log_max_files {
+            builder = builder.max_log_files(max_files);
+        }
+        let appender = builder
+            .build(&args.log_dir)
+            .map_err(LoggingError::FileError)?;
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+        layers.push(fmt_layer(args.log_format, writer, false));
+        file_guard = Some(guard);
+    }
+    if let Some(endpoint) = &args.otlp_endpoint {
+        let layer = telemetry::otlp_layer(service_name, endpoint)
+            .map_err(LoggingError::TraceError)?;
+        layers.push(layer.boxed());
+    }
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(filter)
+        .try_init()
+        .map_err(LoggingError::InitError)?;
+
+    Ok(LoggingGuard {
+        _file: file_guard,
+        otlp: args.otlp_endpoint.is_some(),
+    })
+}
+
+fn fmt_layer<W>(
+    format: LogFormat,
+    writer: W,
+    ansi: bool,
+) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Pretty => layer.boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
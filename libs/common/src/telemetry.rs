@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use opentelemetry::{
+    propagation::{Extractor, Injector, TextMapPropagator},
+    trace::TraceError,
+    Context, KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace, Resource};
+use tracing::{Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// A layer that exports spans to the OTLP gRPC collector at `endpoint`, e.g.
+/// `http://localhost:4317`, in batches, under `service_name`.
+pub fn otlp_layer<S>(
+    service_name: &'static str,
+    endpoint: &str,
+) -> Result<OpenTelemetryLayer<S, trace::Tracer>, TraceError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let tracer =
+        opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(trace::config().with_resource(Resource::new([
+                KeyValue::new("service.name", service_name),
+            ])))
+            .install_batch(runtime::Tokio)?;
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Exports the spans still buffered. Called before the process exits.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// The W3C `traceparent` of the current span, if spans are exported.
+pub fn current_trace_context() -> Option<String> {
+    let mut carrier = Carrier::default();
+    TraceContextPropagator::new()
+        .inject_context(&Span::current().context(), &mut carrier);
+    carrier.0.remove(TRACEPARENT_HEADER)
+}
+
+/// Makes the span of `trace_context` the parent of `span`, or starts a new
+/// trace at `span` when there is none. Spans created under `span` before this
+/// call keep the previous trace.
+pub fn set_parent(span: &Span, trace_context: Option<&str>) {
+    let mut carrier = Carrier::default();
+    if let Some(trace_context) = trace_context {
+        carrier
+            .0
+            .insert(TRACEPARENT_HEADER.to_string(), trace_context.to_string());
+    }
+    let context =
+        TraceContextPropagator::new().extract_with_context(&Context::new(), &carrier);
+    span.set_parent(context);
+}
+
+#[derive(Default)]
+struct Carrier(HashMap<String, String>);
+
+impl Injector for Carrier {
+    fn set(&mut self, key: &str, value: String) {
+        self.0.insert(key.to_string(), value);
+    }
+}
+
+impl Extractor for Carrier {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(String::as_str).collect()
+    }
+}
@@ -0,0 +1,158 @@
+use ethers::{
+    abi::ethereum_types::BloomInput,
+    types::{Bloom, Log, TransactionReceipt, H256},
+    utils::{keccak256, rlp::RlpStream},
+};
+
+/// The root of the trie keyed by each receipt's RLP-encoded index.
+pub fn receipts_root(receipts: &[TransactionReceipt]) -> H256 {
+    ordered_trie_root(receipts.iter().map(encode_receipt).collect())
+}
+
+/// A receipt as hashed into the trie: its RLP list, prefixed with the
+/// transaction type for typed transactions (EIP-2718).
+pub fn encode_receipt(receipt: &TransactionReceipt) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(4);
+    // Receipts before Byzantium carry the post-transaction state root.
+    match (receipt.status, receipt.root) {
+        (None, Some(root)) => stream.append(&root),
+        (status, _) => stream.append(&status.unwrap_or_default()),
+    };
+    stream.append(&receipt.cumulative_gas_used);
+    stream.append(&receipt.logs_bloom);
+    stream.append_list(&receipt.logs);
+
+    let mut encoded = Vec::new();
+    match receipt.transaction_type.map(|kind| kind.as_u64()) {
+        Some(kind) if kind > 0 => encoded.push(kind as u8),
+        _ => {}
+    }
+    encoded.extend_from_slice(&stream.out());
+    encoded
+}
+
+/// The bloom filter of the logs' addresses and topics.
+pub fn logs_bloom(logs: &[Log]) -> Bloom {
+    let mut bloom = Bloom::zero();
+    for log in logs {
+        bloom.accrue(BloomInput::Raw(log.address.as_bytes()));
+        for topic in &log.topics {
+            bloom.accrue(BloomInput::Raw(topic.as_bytes()));
+        }
+    }
+    bloom
+}
+
+/// The root hash of a Merkle Patricia trie mapping the RLP encoding of each
+/// value's index to the value.
+pub fn ordered_trie_root(values: Vec<Vec<u8>>) -> H256 {
+    trie_root(
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let mut key = RlpStream::new();
+                key.append(&index);
+                (key.out().to_vec(), value)
+            })
+            .collect(),
+    )
+}
+
+/// The root hash of a Merkle Patricia trie holding `entries`, keyed by their
+/// first element.
+pub fn trie_root(entries: Vec<(Vec<u8>, Vec<u8>)>) -> H256 {
+    let mut entries: Vec<(Vec<u8>, Vec<u8>)> = entries
+        .into_iter()
+        .map(|(key, value)| (nibbles(&key), value))
+        .collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    if entries.is_empty() {
+        // The hash of an empty RLP string.
+        let mut empty = RlpStream::new();
+        empty.append_empty_data();
+        return H256(keccak256(empty.out()));
+    }
+    H256(keccak256(trie_node(&entries, 0)))
+}
+
+fn nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+/// The RLP encoding of the node holding `entries`, sorted by key, whose keys
+/// share their first `depth` nibbles.
+fn trie_node(entries: &[(Vec<u8>, Vec<u8>)], depth: usize) -> Vec<u8> {
+    if let [(key, value)] = entries {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&hex_prefix(&key[depth..], true));
+        stream.append(value);
+        return stream.out().to_vec();
+    }
+
+    let (first, last) = (&entries[0].0, &entries[entries.len() - 1].0);
+    let shared = first[depth..]
+        .iter()
+        .zip(&last[depth..])
+        .take_while(|(a, b)| a == b)
+        .count();
+    if shared > 0 {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&hex_prefix(&first[depth..depth + shared], false));
+        append_child(&mut stream, &trie_node(entries, depth + shared));
+        return stream.out().to_vec();
+    }
+
+    let mut stream = RlpStream::new_list(17);
+    // Keys that end here sort first.
+    let (value, mut rest) = match entries.split_first() {
+        Some(((key, value), rest)) if key.len() == depth => (Some(value), rest),
+        _ => (None, entries),
+    };
+    for nibble in 0..16 {
+        let count = rest
+            .iter()
+            .take_while(|(key, _)| key[depth] == nibble)
+            .count();
+        if count == 0 {
+            stream.append_empty_data();
+        } else {
+            append_child(&mut stream, &trie_node(&rest[..count], depth + 1));
+        }
+        rest = &rest[count..];
+    }
+    match value {
+        Some(value) => stream.append(value),
+        None => stream.append_empty_data(),
+    };
+    stream.out().to_vec()
+}
+
+/// Embeds a child node shorter than a hash, and refers to others by hash.
+fn append_child(stream: &mut RlpStream, node: &[u8]) {
+    if node.len() < 32 {
+        stream.append_raw(node, 1);
+    } else {
+        stream.append(&keccak256(node).to_vec());
+    }
+}
+
+/// Packs a path of nibbles into bytes, flagging whether it ends in a leaf and
+/// whether its length is odd.
+fn hex_prefix(path: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 } + (path.len() % 2) as u8;
+    let mut encoded = Vec::with_capacity(path.len() / 2 + 1);
+    let rest = if path.len() % 2 == 1 {
+        encoded.push(flag << 4 | path[0]);
+        &path[1..]
+    } else {
+        encoded.push(flag << 4);
+        path
+    };
+    encoded.extend(rest.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+    encoded
+}
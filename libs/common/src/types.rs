@@ -1,79 +1,475 @@
-use ethers::{
-    types::{Log, Transaction},
-    utils::hex,
-};
+use std::fmt;
+
+use ethers::types::{Block, Bytes, Log, Transaction, H160, H256, U256};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Serialize, Deserialize)]
+/// A log as published to the stream.
+///
+/// Hashes, addresses and byte strings keep their ethers types and serialize
+/// as full `0x`-prefixed hex. Values the node did not return stay `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SummaryLog {
-    pub address: String,
-    pub data: String,
-    pub block_number: u64,
-    pub transaction_hash: Option<String>,
+    pub address: H160,
+    pub data: Bytes,
+    pub block_number: Option<u64>,
+    pub block_hash: Option<H256>,
+    pub transaction_hash: Option<H256>,
     pub transaction_index: Option<u64>,
-    pub topics: Vec<String>,
-    pub log_index: Option<String>,
+    pub topics: Vec<H256>,
+    pub log_index: Option<u64>,
+    pub removed: Option<bool>,
 }
 
 impl From<Log> for SummaryLog {
     fn from(log: Log) -> Self {
         SummaryLog {
-            address: format!("0x{}", hex::encode(log.address)),
-            data: format!("0x{}", hex::encode(log.data)),
-            block_number: log
-                .block_number
-                .map_or_else(|| 0, |block_number| block_number.as_u64()),
-            transaction_hash: log
-                .transaction_hash
-                .map_or_else(|| None, |h| Some(format!("0x{}", hex::encode(h)))),
+            address: log.address,
+            data: log.data,
+            block_number: log.block_number.map(|block_number| block_number.as_u64()),
+            block_hash: log.block_hash,
+            transaction_hash: log.transaction_hash,
             transaction_index: log.transaction_index.map(|index| index.as_u64()),
-            topics: log
-                .topics
-                .into_iter()
-                .map(|topic| format!("0x{}", hex::encode(topic)))
-                .collect(),
-            log_index: log.log_index.map(|index| index.to_string()),
+            topics: log.topics,
+            log_index: log.log_index.map(|index| index.as_u64()),
+            removed: log.removed,
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// A transaction as published to the stream. See [`SummaryLog`] for the
+/// encoding rules.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SummaryTransaction {
-    pub hash: String,
-    pub block_hash: String,
-    pub block_number: u64,
-    pub chain_id: String,
-    pub input: String,
-    pub from: String,
-    pub to: String,
-    pub nonce: String,
-    pub transaction_index: u64,
-    pub value: String,
+    pub hash: H256,
+    pub block_hash: Option<H256>,
+    pub block_number: Option<u64>,
+    pub chain_id: Option<u64>,
+    pub input: Bytes,
+    pub from: H160,
+    pub to: Option<H160>,
+    pub nonce: U256,
+    pub transaction_index: Option<u64>,
+    pub value: U256,
+    pub transaction_type: Option<u64>,
 }
 
 impl From<Transaction> for SummaryTransaction {
     fn from(tx: Transaction) -> Self {
         SummaryTransaction {
-            hash: tx.hash.to_string(),
-            block_hash: tx
-                .block_hash
-                .map_or_else(|| "None".to_string(), |h| h.to_string()),
-            block_number: tx
-                .block_number
-                .map_or_else(|| 0, |block_number| block_number.as_u64()),
-            chain_id: tx
-                .chain_id
-                .map_or_else(|| 1.to_string(), |chain_id| chain_id.to_string()),
-            input: hex::encode(tx.input),
-            from: tx.from.to_string(),
-            to: tx
-                .to
-                .map_or_else(|| "None".to_string(), |to| to.to_string()),
-            nonce: tx.nonce.to_string(),
-            transaction_index: tx
-                .transaction_index
-                .map_or_else(|| 0, |index| index.as_u64()),
-            value: tx.value.to_string(),
+            hash: tx.hash,
+            block_hash: tx.block_hash,
+            block_number: tx.block_number.map(|block_number| block_number.as_u64()),
+            chain_id: tx.chain_id.map(|chain_id| chain_id.as_u64()),
+            input: tx.input,
+            from: tx.from,
+            to: tx.to,
+            nonce: tx.nonce,
+            transaction_index: tx.transaction_index.map(|index| index.as_u64()),
+            value: tx.value,
+            transaction_type: tx.transaction_type.map(|kind| kind.as_u64()),
+        }
+    }
+}
+
+/// The block header fields published with `header` messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SummaryHeader {
+    pub hash: Option<H256>,
+    pub parent_hash: H256,
+    pub number: Option<u64>,
+    pub timestamp: u64,
+    pub miner: Option<H160>,
+    pub gas_used: U256,
+    pub gas_limit: U256,
+    pub base_fee_per_gas: Option<U256>,
+    pub transaction_count: u64,
+}
+
+impl<TX> From<&Block<TX>> for SummaryHeader {
+    fn from(block: &Block<TX>) -> Self {
+        SummaryHeader {
+            hash: block.hash,
+            parent_hash: block.parent_hash,
+            number: block.number.map(|number| number.as_u64()),
+            timestamp: block.timestamp.low_u64(),
+            miner: block.author,
+            gas_used: block.gas_used,
+            gas_limit: block.gas_limit,
+            base_fee_per_gas: block.base_fee_per_gas,
+            transaction_count: block.transactions.len() as u64,
+        }
+    }
+}
+
+/// The token transfer function a pending transaction calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferMethod {
+    /// `transferFrom(address,address,uint256)`, shared by ERC-20 and ERC-721.
+    TransferFrom,
+    /// ERC-721 `safeTransferFrom`, with or without data, or ERC-1155
+    /// `safeTransferFrom`, which also carries an amount.
+    SafeTransferFrom,
+    /// ERC-1155 `safeBatchTransferFrom`.
+    SafeBatchTransferFrom,
+}
+
+/// A token transfer decoded from the calldata of a transaction that is not
+/// yet in a block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingTransfer {
+    pub transaction_hash: H256,
+    /// The account that sent the transaction.
+    pub sender: H160,
+    /// The token contract called.
+    pub contract: H160,
+    pub method: TransferMethod,
+    pub from: H160,
+    pub to: H160,
+    pub token_ids: Vec<U256>,
+    /// One per token id for ERC-1155 calls; empty for the others.
+    pub amounts: Vec<U256>,
+    pub nonce: U256,
+    pub gas_price: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingStatus {
+    /// Mined in the envelope's block.
+    Included,
+    /// Replaced by another transaction with the same nonce, or evicted from
+    /// the node's mempool.
+    Dropped,
+}
+
+/// What became of a transaction previously published as pending.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingResolution {
+    pub transaction_hash: H256,
+    pub status: PendingStatus,
+}
+
+/// A contract deployed in a block, either by a creation transaction or,
+/// found in the transaction's call trace, by another contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractCreation {
+    pub address: H160,
+    /// The account that sent the creation transaction, or the factory
+    /// contract that deployed this one.
+    pub creator: H160,
+    /// The account that sent the transaction.
+    pub sender: H160,
+    pub transaction_hash: H256,
+    pub transaction_index: u64,
+    /// keccak256 of the deployed bytecode, when requested.
+    pub code_hash: Option<H256>,
+}
+
+/// Version of the [`StreamEnvelope`] format written by this build. Consumers
+/// reject messages with a newer version instead of misreading them.
+pub const STREAM_SCHEMA_VERSION: u16 = 1;
+
+/// Version assigned to messages published before the envelope existed, which
+/// were a bare JSON array of logs.
+pub const LEGACY_SCHEMA_VERSION: u16 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    Logs,
+    Revert,
+    Header,
+    Transaction,
+    PendingTransfer,
+    PendingResolution,
+    ContractCreations,
+}
+
+impl fmt::Display for MessageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            MessageKind::Logs => "logs",
+            MessageKind::Revert => "revert",
+            MessageKind::Header => "header",
+            MessageKind::Transaction => "transaction",
+            MessageKind::PendingTransfer => "pending_transfer",
+            MessageKind::PendingResolution => "pending_resolution",
+            MessageKind::ContractCreations => "contract_creations",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// Whether the block was at least `finality_depth` blocks below the chain
+/// head when the message was published.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinalityStatus {
+    Unfinalized,
+    Finalized,
+}
+
+impl FinalityStatus {
+    pub fn of(block_number: u64, chain_head: u64, finality_depth: u64) -> Self {
+        if chain_head >= block_number.saturating_add(finality_depth) {
+            FinalityStatus::Finalized
+        } else {
+            FinalityStatus::Unfinalized
+        }
+    }
+}
+
+/// Which of the watcher's sync lanes published a message. Consumers can
+/// favour `live` messages over the `backfill` of historical blocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncLane {
+    #[default]
+    Live,
+    Backfill,
+}
+
+impl fmt::Display for SyncLane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncLane::Live => write!(f, "live"),
+            SyncLane::Backfill => write!(f, "backfill"),
+        }
+    }
+}
+
+/// The body of a stream message. A `revert` carries no body: the envelope's
+/// block number and hash identify the block that left the canonical chain.
+///
+/// Pending messages go to a separate stream. Their envelope carries the chain
+/// head when they were published, without a block hash, except for an
+/// `included` resolution, which carries the block the transaction was mined
+/// in.
+///
+/// `contract_creations` lists the contracts deployed in a block, in
+/// transaction order, after the block's `logs` messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamPayload {
+    Logs(Vec<SummaryLog>),
+    Revert,
+    Header(SummaryHeader),
+    Transaction(SummaryTransaction),
+    PendingTransfer(PendingTransfer),
+    PendingResolution(PendingResolution),
+    ContractCreations(Vec<ContractCreation>),
+}
+
+impl StreamPayload {
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            StreamPayload::Logs(_) => MessageKind::Logs,
+            StreamPayload::Revert => MessageKind::Revert,
+            StreamPayload::Header(_) => MessageKind::Header,
+            StreamPayload::Transaction(_) => MessageKind::Transaction,
+            StreamPayload::PendingTransfer(_) => MessageKind::PendingTransfer,
+            StreamPayload::PendingResolution(_) => MessageKind::PendingResolution,
+            StreamPayload::ContractCreations(_) => MessageKind::ContractCreations,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EnvelopeError {
+    ParseError(String),
+    UnsupportedVersion(u64),
+    ChainMismatch(u32, u32),
+    KindMismatch(MessageKind, MessageKind),
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvelopeError::ParseError(error) => write!(f, "Parse Error: {}", error),
+            EnvelopeError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported Version: schema version {} is newer than {}",
+                version, STREAM_SCHEMA_VERSION
+            ),
+            EnvelopeError::ChainMismatch(expected, actual) => write!(
+                f,
+                "Chain Mismatch: expected chain {}, message is for chain {}",
+                expected, actual
+            ),
+            EnvelopeError::KindMismatch(kind, payload) => write!(
+                f,
+                "Kind Mismatch: message kind {} carries a {} payload",
+                kind, payload
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+/// Every message published to a Redis stream, under the `message` field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamEnvelope {
+    pub schema_version: u16,
+    pub kind: MessageKind,
+    pub chain_id: u32,
+    pub block_number: u64,
+    pub block_hash: Option<H256>,
+    pub finality: FinalityStatus,
+    /// Identifies the chain-watcher instance that published the message.
+    pub producer_id: String,
+    /// Absent in messages published before lanes existed, which are `live`.
+    #[serde(default)]
+    pub lane: SyncLane,
+    /// W3C `traceparent` of the span that published the message, so a
+    /// consumer's spans join the producer's trace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_context: Option<String>,
+    pub payload: StreamPayload,
+}
+
+impl StreamEnvelope {
+    pub fn new(
+        chain_id: u32,
+        block_number: u64,
+        block_hash: Option<H256>,
+        finality: FinalityStatus,
+        producer_id: String,
+        payload: StreamPayload,
+    ) -> Self {
+        Self {
+            schema_version: STREAM_SCHEMA_VERSION,
+            kind: payload.kind(),
+            chain_id,
+            block_number,
+            block_hash,
+            finality,
+            producer_id,
+            lane: SyncLane::Live,
+            trace_context: None,
+            payload,
+        }
+    }
+
+    pub fn with_lane(mut self, lane: SyncLane) -> Self {
+        self.lane = lane;
+        self
+    }
+
+    pub fn with_trace_context(mut self, trace_context: Option<String>) -> Self {
+        self.trace_context = trace_context;
+        self
+    }
+
+    pub fn encode(&self) -> Result<String, EnvelopeError> {
+        serde_json::to_string(self).map_err(|e| EnvelopeError::ParseError(e.to_string()))
+    }
+
+    /// Decodes and validates a message read from a stream of `chain_id`.
+    ///
+    /// A bare JSON array is the format used before the envelope and is read
+    /// as a `logs` message for `chain_id` with schema version 0.
+    pub fn decode(message: &[u8], chain_id: u32) -> Result<Self, EnvelopeError> {
+        let value: Value = serde_json::from_slice(message)
+            .map_err(|e| EnvelopeError::ParseError(e.to_string()))?;
+
+        let envelope = match value {
+            Value::Array(_) => Self::from_legacy(value, chain_id)?,
+            _ => {
+                // Check the version first so a newer format reports as such
+                // rather than as whatever field it happens to break.
+                if let Some(version) = value.get("schema_version").and_then(Value::as_u64)
+                {
+                    if version > STREAM_SCHEMA_VERSION as u64 {
+                        return Err(EnvelopeError::UnsupportedVersion(version));
+                    }
+                }
+                serde_json::from_value(value)
+                    .map_err(|e| EnvelopeError::ParseError(e.to_string()))?
+            }
+        };
+
+        envelope.validate(chain_id)?;
+        Ok(envelope)
+    }
+
+    pub fn validate(&self, chain_id: u32) -> Result<(), EnvelopeError> {
+        if self.schema_version > STREAM_SCHEMA_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(
+                self.schema_version as u64,
+            ));
+        }
+        if self.chain_id != chain_id {
+            return Err(EnvelopeError::ChainMismatch(chain_id, self.chain_id));
+        }
+        if self.kind != self.payload.kind() {
+            return Err(EnvelopeError::KindMismatch(self.kind, self.payload.kind()));
+        }
+        Ok(())
+    }
+
+    fn from_legacy(value: Value, chain_id: u32) -> Result<Self, EnvelopeError> {
+        let logs: Vec<LegacySummaryLog> = serde_json::from_value(value)
+            .map_err(|e| EnvelopeError::ParseError(e.to_string()))?;
+        let logs: Vec<SummaryLog> = logs.into_iter().map(SummaryLog::from).collect();
+
+        Ok(Self {
+            schema_version: LEGACY_SCHEMA_VERSION,
+            kind: MessageKind::Logs,
+            chain_id,
+            block_number: logs
+                .first()
+                .and_then(|log| log.block_number)
+                .unwrap_or_default(),
+            block_hash: logs.first().and_then(|log| log.block_hash),
+            finality: FinalityStatus::Unfinalized,
+            producer_id: String::new(),
+            lane: SyncLane::Live,
+            trace_context: None,
+            payload: StreamPayload::Logs(logs),
+        })
+    }
+}
+
+/// A log in the pre-envelope format, where `log_index` was a decimal string.
+#[derive(Deserialize)]
+struct LegacySummaryLog {
+    address: H160,
+    data: Bytes,
+    block_number: Option<u64>,
+    block_hash: Option<H256>,
+    transaction_hash: Option<H256>,
+    transaction_index: Option<u64>,
+    topics: Vec<H256>,
+    log_index: Option<LegacyLogIndex>,
+    removed: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegacyLogIndex {
+    Number(u64),
+    Text(String),
+}
+
+impl From<LegacySummaryLog> for SummaryLog {
+    fn from(log: LegacySummaryLog) -> Self {
+        SummaryLog {
+            address: log.address,
+            data: log.data,
+            block_number: log.block_number,
+            block_hash: log.block_hash,
+            transaction_hash: log.transaction_hash,
+            transaction_index: log.transaction_index,
+            topics: log.topics,
+            log_index: log.log_index.and_then(|index| match index {
+                LegacyLogIndex::Number(index) => Some(index),
+                LegacyLogIndex::Text(index) => index.parse().ok(),
+            }),
+            removed: log.removed,
         }
     }
 }
@@ -85,8 +481,24 @@ pub struct RedisConfig {
     pub group_name: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NativeCurrency {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChainConfig {
     pub id: u32,
     pub name: String,
+    pub native_currency: NativeCurrency,
+    /// Average time between blocks, in milliseconds.
+    pub average_block_time_ms: u64,
+    /// Number of confirmations after which a block is considered final.
+    pub finality_depth: u64,
+    /// Whether the chain's nodes implement `eth_getBlockReceipts`.
+    pub supports_block_receipts: bool,
+    /// Largest block range accepted by a single `eth_getLogs` call.
+    pub max_logs_range: u64,
 }
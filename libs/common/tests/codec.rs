@@ -0,0 +1,205 @@
+use common::{
+    codec::{CodecError, Encoding, Format},
+    types::{
+        ContractCreation, EnvelopeError, FinalityStatus, PendingResolution,
+        PendingStatus, PendingTransfer, StreamEnvelope, StreamPayload, SummaryHeader,
+        SummaryLog, SummaryTransaction, SyncLane, TransferMethod, STREAM_SCHEMA_VERSION,
+    },
+};
+use ethers::types::{Bytes, H160, H256, U256};
+
+const ENCODINGS: [&str; 8] = [
+    "json",
+    "json+zstd",
+    "msgpack",
+    "msgpack+zstd",
+    "cbor",
+    "cbor+zstd",
+    "protobuf",
+    "protobuf+zstd",
+];
+
+fn envelope(payload: StreamPayload) -> StreamEnvelope {
+    StreamEnvelope::new(
+        137,
+        19_000_000,
+        Some(H256::repeat_byte(0xbb)),
+        FinalityStatus::Finalized,
+        "watcher-1".to_string(),
+        payload,
+    )
+}
+
+fn payloads() -> Vec<StreamPayload> {
+    vec![
+        StreamPayload::Logs(vec![SummaryLog {
+            address: H160::repeat_byte(0x11),
+            data: Bytes::from(vec![0x00; 64]),
+            block_number: Some(19_000_000),
+            block_hash: Some(H256::repeat_byte(0xbb)),
+            transaction_hash: Some(H256::repeat_byte(0xaa)),
+            transaction_index: Some(3),
+            topics: vec![H256::repeat_byte(0xdd), H256::zero()],
+            log_index: Some(12),
+            removed: None,
+        }]),
+        StreamPayload::Revert,
+        StreamPayload::Header(SummaryHeader {
+            hash: Some(H256::repeat_byte(0xbb)),
+            parent_hash: H256::repeat_byte(0xba),
+            number: Some(19_000_000),
+            timestamp: 1_700_000_000,
+            miner: Some(H160::repeat_byte(0x33)),
+            gas_used: U256::from(15_000_000u64),
+            gas_limit: U256::from(30_000_000u64),
+            base_fee_per_gas: None,
+            transaction_count: 150,
+        }),
+        StreamPayload::Transaction(SummaryTransaction {
+            hash: H256::repeat_byte(0xaa),
+            block_hash: Some(H256::repeat_byte(0xbb)),
+            block_number: Some(19_000_000),
+            chain_id: Some(137),
+            input: Bytes::from(vec![0x42, 0x84, 0x2e, 0x0e]),
+            from: H160::repeat_byte(0x11),
+            to: None,
+            nonce: U256::zero(),
+            transaction_index: Some(3),
+            value: U256::MAX,
+            transaction_type: Some(2),
+        }),
+        StreamPayload::PendingTransfer(PendingTransfer {
+            transaction_hash: H256::repeat_byte(0xaa),
+            sender: H160::repeat_byte(0x11),
+            contract: H160::repeat_byte(0x22),
+            method: TransferMethod::SafeBatchTransferFrom,
+            from: H160::repeat_byte(0x11),
+            to: H160::repeat_byte(0x33),
+            token_ids: vec![U256::from(7), U256::MAX],
+            amounts: vec![U256::one(), U256::zero()],
+            nonce: U256::from(42),
+            gas_price: Some(U256::from(30_000_000_000u64)),
+            max_priority_fee_per_gas: None,
+        }),
+        StreamPayload::PendingResolution(PendingResolution {
+            transaction_hash: H256::repeat_byte(0xaa),
+            status: PendingStatus::Dropped,
+        }),
+        StreamPayload::ContractCreations(vec![
+            ContractCreation {
+                address: H160::repeat_byte(0x44),
+                creator: H160::repeat_byte(0x11),
+                sender: H160::repeat_byte(0x11),
+                transaction_hash: H256::repeat_byte(0xaa),
+                transaction_index: 0,
+                code_hash: Some(H256::repeat_byte(0xcc)),
+            },
+            ContractCreation {
+                address: H160::repeat_byte(0x55),
+                creator: H160::repeat_byte(0x44),
+                sender: H160::repeat_byte(0x11),
+                transaction_hash: H256::repeat_byte(0xab),
+                transaction_index: 4,
+                code_hash: None,
+            },
+        ]),
+    ]
+}
+
+#[test]
+fn every_encoding_round_trips() {
+    for name in ENCODINGS {
+        let encoding: Encoding = name.parse().unwrap();
+        assert_eq!(encoding.to_string(), name);
+
+        for payload in payloads() {
+            let envelope = envelope(payload);
+            let encoded = encoding.encode(&envelope).unwrap();
+            assert_eq!(
+                encoding.decode(&encoded, 137).unwrap(),
+                envelope,
+                "{}",
+                name
+            );
+        }
+    }
+}
+
+#[test]
+fn lane_survives_every_encoding() {
+    let envelope = envelope(StreamPayload::Revert).with_lane(SyncLane::Backfill);
+    for name in ENCODINGS {
+        let encoding: Encoding = name.parse().unwrap();
+        let encoded = encoding.encode(&envelope).unwrap();
+        assert_eq!(
+            encoding.decode(&encoded, 137).unwrap().lane,
+            SyncLane::Backfill,
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn trace_context_survives_every_encoding() {
+    let trace_context = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let envelope = envelope(StreamPayload::Revert)
+        .with_trace_context(Some(trace_context.to_string()));
+    for name in ENCODINGS {
+        let encoding: Encoding = name.parse().unwrap();
+        let encoded = encoding.encode(&envelope).unwrap();
+        assert_eq!(
+            encoding
+                .decode(&encoded, 137)
+                .unwrap()
+                .trace_context
+                .as_deref(),
+            Some(trace_context),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn protobuf_is_smaller_than_json() {
+    let envelope = envelope(payloads().remove(0));
+    let json = Encoding::default().encode(&envelope).unwrap();
+    let protobuf = Encoding {
+        format: Format::Protobuf,
+        compressed: false,
+    }
+    .encode(&envelope)
+    .unwrap();
+
+    assert!(protobuf.len() * 2 < json.len());
+}
+
+#[test]
+fn unknown_encoding_is_rejected() {
+    assert!(matches!(
+        "yaml+zstd".parse::<Encoding>(),
+        Err(CodecError::UnknownEncoding(_))
+    ));
+}
+
+#[test]
+fn binary_formats_check_schema_version() {
+    let mut envelope = envelope(StreamPayload::Revert);
+    envelope.schema_version = STREAM_SCHEMA_VERSION + 1;
+
+    for name in ["msgpack", "cbor+zstd", "protobuf"] {
+        let encoding: Encoding = name.parse().unwrap();
+        let encoded = encoding.encode(&envelope).unwrap();
+        assert!(
+            matches!(
+                encoding.decode(&encoded, 137),
+                Err(CodecError::EnvelopeError(
+                    EnvelopeError::UnsupportedVersion(_)
+                ))
+            ),
+            "{}",
+            name
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use common::types::{
+    EnvelopeError, FinalityStatus, MessageKind, StreamEnvelope, StreamPayload,
+    SummaryLog, SyncLane, LEGACY_SCHEMA_VERSION, STREAM_SCHEMA_VERSION,
+};
+use ethers::types::{Bytes, H160, H256};
+
+fn sample_envelope() -> StreamEnvelope {
+    StreamEnvelope::new(
+        137,
+        19_000_000,
+        Some(H256::repeat_byte(0xbb)),
+        FinalityStatus::Unfinalized,
+        "watcher-1".to_string(),
+        StreamPayload::Logs(vec![SummaryLog {
+            address: H160::repeat_byte(0x11),
+            data: Bytes::from(vec![0x01, 0x02]),
+            block_number: Some(19_000_000),
+            block_hash: Some(H256::repeat_byte(0xbb)),
+            transaction_hash: Some(H256::repeat_byte(0xaa)),
+            transaction_index: Some(3),
+            topics: vec![H256::repeat_byte(0xdd)],
+            log_index: Some(12),
+            removed: Some(false),
+        }]),
+    )
+}
+
+#[test]
+fn envelope_round_trips() {
+    let envelope = sample_envelope();
+    let encoded = envelope.encode().unwrap();
+
+    assert_eq!(envelope.schema_version, STREAM_SCHEMA_VERSION);
+    assert_eq!(envelope.kind, MessageKind::Logs);
+    assert_eq!(
+        StreamEnvelope::decode(encoded.as_bytes(), 137).unwrap(),
+        envelope
+    );
+}
+
+#[test]
+fn legacy_array_decodes_as_logs() {
+    let legacy = r#"[{
+        "address": "0x1111111111111111111111111111111111111111",
+        "data": "0x0102",
+        "block_number": 19000000,
+        "transaction_hash": "0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        "transaction_index": 3,
+        "topics": ["0xdddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddd"],
+        "log_index": "12"
+    }]"#;
+
+    let envelope = StreamEnvelope::decode(legacy.as_bytes(), 137).unwrap();
+
+    assert_eq!(envelope.schema_version, LEGACY_SCHEMA_VERSION);
+    assert_eq!(envelope.chain_id, 137);
+    assert_eq!(envelope.block_number, 19_000_000);
+    match envelope.payload {
+        StreamPayload::Logs(logs) => {
+            assert_eq!(logs.len(), 1);
+            assert_eq!(logs[0].log_index, Some(12));
+            assert_eq!(logs[0].block_hash, None);
+        }
+        payload => panic!("unexpected {} payload", payload.kind()),
+    }
+}
+
+#[test]
+fn missing_lane_decodes_as_live() {
+    let mut value =
+        serde_json::to_value(sample_envelope().with_lane(SyncLane::Backfill)).unwrap();
+    value.as_object_mut().unwrap().remove("lane");
+
+    let envelope = StreamEnvelope::decode(value.to_string().as_bytes(), 137).unwrap();
+    assert_eq!(envelope.lane, SyncLane::Live);
+}
+
+#[test]
+fn trace_context_is_omitted_when_absent() {
+    let value = serde_json::to_value(sample_envelope()).unwrap();
+    assert!(value.get("trace_context").is_none());
+    assert_eq!(
+        StreamEnvelope::decode(value.to_string().as_bytes(), 137)
+            .unwrap()
+            .trace_context,
+        None
+    );
+}
+
+#[test]
+fn newer_schema_version_is_rejected() {
+    let mut value = serde_json::to_value(sample_envelope()).unwrap();
+    value["schema_version"] = (STREAM_SCHEMA_VERSION + 1).into();
+    value["payload"] = serde_json::json!({ "unknown": {} });
+
+    let error = StreamEnvelope::decode(value.to_string().as_bytes(), 137).unwrap_err();
+    assert!(matches!(error, EnvelopeError::UnsupportedVersion(_)));
+}
+
+#[test]
+fn other_chain_is_rejected() {
+    let encoded = sample_envelope().encode().unwrap();
+
+    let error = StreamEnvelope::decode(encoded.as_bytes(), 1).unwrap_err();
+    assert!(matches!(error, EnvelopeError::ChainMismatch(1, 137)));
+}
+
+#[test]
+fn finality_follows_depth() {
+    assert_eq!(
+        FinalityStatus::of(100, 163, 64),
+        FinalityStatus::Unfinalized
+    );
+    assert_eq!(FinalityStatus::of(100, 164, 64), FinalityStatus::Finalized);
+}
@@ -0,0 +1,98 @@
+use std::{fs, path::PathBuf};
+
+use clap::Parser;
+use common::logging::{self, LogFormat, LogRotation, LoggingArgs, LoggingError};
+use serde_json::Value;
+
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    logging: LoggingArgs,
+}
+
+fn parse(args: &[&str]) -> LoggingArgs {
+    Cli::parse_from(["service"].iter().chain(args)).logging
+}
+
+#[test]
+fn defaults_to_pretty_daily_files() {
+    let args = parse(&[]);
+    assert_eq!(args.log_format, LogFormat::Pretty);
+    assert_eq!(args.log_dir, PathBuf::from("./logs"));
+    assert_eq!(args.log_rotation, LogRotation::Daily);
+    assert_eq!(args.log_max_files, None);
+    assert!(!args.no_log_file);
+    assert_eq!(args.otlp_endpoint, None);
+}
+
+#[test]
+fn filter_directives_take_precedence_over_debug() {
+    // As if RUST_LOG were unset.
+    let mut args = parse(&["--debug"]);
+    args.log_filter = None;
+    assert_eq!(args.filter_directives(), "debug");
+    args.debug = false;
+    assert_eq!(args.filter_directives(), "info");
+
+    let args = parse(&["--debug", "--log-filter", "warn,chain_watcher=trace"]);
+    assert_eq!(args.filter_directives(), "warn,chain_watcher=trace");
+}
+
+#[test]
+fn parses_container_options() {
+    let args = parse(&[
+        "--log-format",
+        "json",
+        "--no-log-file",
+        "--log-rotation",
+        "hourly",
+        "--log-max-files",
+        "24",
+    ]);
+    assert_eq!(args.log_format, LogFormat::Json);
+    assert!(args.no_log_file);
+    assert_eq!(args.log_rotation, LogRotation::Hourly);
+    assert_eq!(args.log_max_files, Some(24));
+}
+
+// Installs the global subscriber, so it is the only test that logs.
+#[test]
+fn writes_json_lines_to_the_log_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let log_dir = dir.path().to_str().unwrap();
+
+    let invalid = parse(&["--log-filter", "common=loudest", "--log-dir", log_dir]);
+    assert!(matches!(
+        logging::init(&invalid, "service"),
+        Err(LoggingError::FilterError(_))
+    ));
+
+    let args = parse(&[
+        "--log-filter",
+        "info",
+        "--log-format",
+        "json",
+        "--log-rotation",
+        "never",
+        "--log-dir",
+        log_dir,
+    ]);
+    let guard = logging::init(&args, "service").unwrap();
+    tracing::info!(block_number = 5, "Block processed");
+    tracing::debug!("Filtered out");
+    assert!(matches!(
+        logging::init(&args, "service"),
+        Err(LoggingError::InitError(_))
+    ));
+    drop(guard);
+
+    let content = fs::read_to_string(dir.path().join("service.log")).unwrap();
+    let lines: Vec<Value> = content
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["level"], "INFO");
+    assert_eq!(lines[0]["fields"]["message"], "Block processed");
+    assert_eq!(lines[0]["fields"]["block_number"], 5);
+}
@@ -0,0 +1,80 @@
+use common::trie::{logs_bloom, ordered_trie_root, trie_root};
+use ethers::{
+    abi::ethereum_types::BloomInput,
+    types::{Bloom, Log, H160, H256},
+};
+
+fn hash(value: &str) -> H256 {
+    value.parse().unwrap()
+}
+
+fn entries(pairs: &[(&str, &str)]) -> Vec<(Vec<u8>, Vec<u8>)> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.as_bytes().to_vec(), value.as_bytes().to_vec()))
+        .collect()
+}
+
+#[test]
+fn hashes_the_reference_tries() {
+    assert_eq!(
+        trie_root(Vec::new()),
+        hash("0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421")
+    );
+    assert_eq!(
+        trie_root(entries(&[
+            ("doe", "reindeer"),
+            ("dog", "puppy"),
+            ("dogglesworth", "cat"),
+        ])),
+        hash("0x8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3")
+    );
+    // "do" ends in a branch node that also leads to "dog" and "doge".
+    assert_eq!(
+        trie_root(entries(&[
+            ("do", "verb"),
+            ("dog", "puppy"),
+            ("doge", "coin"),
+            ("horse", "stallion"),
+        ])),
+        hash("0x5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84")
+    );
+}
+
+#[test]
+fn keys_ordered_tries_by_rlp_encoded_index() {
+    // Index 0 is encoded as 0x80, so it sorts after indexes 1 to 127.
+    let values: Vec<Vec<u8>> = (0..200u32)
+        .map(|index| index.to_be_bytes().to_vec())
+        .collect();
+    let keyed = values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let key = match index {
+                0 => vec![0x80],
+                1..=127 => vec![index as u8],
+                _ => vec![0x81, index as u8],
+            };
+            (key, value.clone())
+        })
+        .collect();
+
+    assert_eq!(ordered_trie_root(values), trie_root(keyed));
+}
+
+#[test]
+fn blooms_log_addresses_and_topics() {
+    let log = Log {
+        address: H160::repeat_byte(1),
+        topics: vec![H256::repeat_byte(2), H256::repeat_byte(3)],
+        ..Default::default()
+    };
+    let mut expected = Bloom::zero();
+    expected.accrue(BloomInput::Raw(H160::repeat_byte(1).as_bytes()));
+    expected.accrue(BloomInput::Raw(H256::repeat_byte(2).as_bytes()));
+    expected.accrue(BloomInput::Raw(H256::repeat_byte(3).as_bytes()));
+
+    assert_eq!(logs_bloom(&[log]), expected);
+    assert_eq!(logs_bloom(&[]), Bloom::zero());
+}
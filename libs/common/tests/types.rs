@@ -0,0 +1,114 @@
+use common::types::{SummaryLog, SummaryTransaction};
+use ethers::types::{Bytes, Log, Transaction, H160, H256, U256, U64};
+use serde_json::Value;
+
+fn sample_log() -> Log {
+    Log {
+        address: H160::from_low_u64_be(0x1234_5678_9abc_def0),
+        topics: vec![H256::repeat_byte(0xdd), H256::repeat_byte(0x01)],
+        data: Bytes::from(vec![0x00, 0x01, 0xfe, 0xff]),
+        block_hash: Some(H256::repeat_byte(0xbb)),
+        block_number: Some(U64::from(19_000_000u64)),
+        transaction_hash: Some(H256::repeat_byte(0xaa)),
+        transaction_index: Some(U64::from(7u64)),
+        log_index: Some(U256::from(1_000_000_000_000u64)),
+        removed: Some(false),
+        ..Default::default()
+    }
+}
+
+fn sample_transaction() -> Transaction {
+    Transaction {
+        hash: H256::repeat_byte(0xaa),
+        nonce: U256::from(42u64),
+        block_hash: Some(H256::repeat_byte(0xbb)),
+        block_number: Some(U64::from(19_000_000u64)),
+        transaction_index: Some(U64::from(7u64)),
+        from: H160::repeat_byte(0x11),
+        to: Some(H160::repeat_byte(0x22)),
+        value: U256::MAX,
+        input: Bytes::from(vec![0x42, 0x84, 0x2e, 0x0e, 0x00]),
+        chain_id: Some(U256::from(137u64)),
+        transaction_type: Some(U64::from(2u64)),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn summary_log_round_trips_without_loss() {
+    let summary = SummaryLog::from(sample_log());
+
+    let json = serde_json::to_string(&summary).unwrap();
+    let decoded: SummaryLog = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(decoded, summary);
+    assert_eq!(decoded.log_index, Some(1_000_000_000_000));
+    assert_eq!(decoded.block_number, Some(19_000_000));
+}
+
+#[test]
+fn summary_log_serializes_full_hex() {
+    let value = serde_json::to_value(SummaryLog::from(sample_log())).unwrap();
+
+    assert_eq!(
+        value["address"],
+        "0x000000000000000000000000123456789abcdef0"
+    );
+    assert_eq!(value["transaction_hash"], format!("0x{}", "aa".repeat(32)));
+    assert_eq!(value["topics"][0], format!("0x{}", "dd".repeat(32)));
+    assert_eq!(value["data"], "0x0001feff");
+}
+
+#[test]
+fn summary_log_keeps_missing_values_empty() {
+    let summary = SummaryLog::from(Log::default());
+    let value = serde_json::to_value(&summary).unwrap();
+
+    assert_eq!(summary.block_number, None);
+    assert_eq!(summary.log_index, None);
+    assert_eq!(value["block_number"], Value::Null);
+    assert_eq!(value["transaction_hash"], Value::Null);
+}
+
+#[test]
+fn summary_transaction_round_trips_without_loss() {
+    let summary = SummaryTransaction::from(sample_transaction());
+
+    let json = serde_json::to_string(&summary).unwrap();
+    let decoded: SummaryTransaction = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(decoded, summary);
+    assert_eq!(decoded.value, U256::MAX);
+    assert_eq!(decoded.chain_id, Some(137));
+}
+
+#[test]
+fn summary_transaction_serializes_full_hex() {
+    let value =
+        serde_json::to_value(SummaryTransaction::from(sample_transaction())).unwrap();
+
+    assert_eq!(value["hash"], format!("0x{}", "aa".repeat(32)));
+    assert_eq!(value["block_hash"], format!("0x{}", "bb".repeat(32)));
+    assert_eq!(value["from"], format!("0x{}", "11".repeat(20)));
+    assert_eq!(value["to"], format!("0x{}", "22".repeat(20)));
+    assert_eq!(value["input"], "0x42842e0e00");
+}
+
+#[test]
+fn summary_transaction_keeps_missing_values_empty() {
+    let transaction = Transaction {
+        to: None,
+        chain_id: None,
+        block_hash: None,
+        block_number: None,
+        ..sample_transaction()
+    };
+    let summary = SummaryTransaction::from(transaction);
+    let value = serde_json::to_value(&summary).unwrap();
+
+    assert_eq!(summary.to, None);
+    assert_eq!(summary.chain_id, None);
+    assert_eq!(value["to"], Value::Null);
+    assert_eq!(value["block_hash"], Value::Null);
+    assert_eq!(value["block_number"], Value::Null);
+}
@@ -0,0 +1,316 @@
+use common::trie::receipts_root;
+use ethers::{
+    abi::ethereum_types::BloomInput,
+    types::{
+        Address, Block, BlockNumber, Bloom, Bytes, CallFrame, Log, NameOrAddress,
+        Transaction, TransactionReceipt, H256, U256, U64,
+    },
+    utils::{get_contract_address, keccak256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::events::Event;
+
+/// Timestamp of block 0.
+pub const GENESIS_TIMESTAMP: u64 = 1_700_000_000;
+
+/// Seconds between two blocks' timestamps.
+pub const BLOCK_INTERVAL: u64 = 12;
+
+/// A transaction to include in the next mined block. Without a `to`, it
+/// deploys a contract at the address derived from `from` and its nonce.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MockTransaction {
+    #[serde(default)]
+    pub from: Address,
+    #[serde(default)]
+    pub to: Option<Address>,
+    #[serde(default)]
+    pub events: Vec<Event>,
+    /// Contracts deployed by `to`, or by the deployed contract, during the
+    /// transaction.
+    #[serde(default)]
+    pub creates: Vec<Address>,
+}
+
+impl MockTransaction {
+    pub fn new(from: Address, to: Address) -> Self {
+        Self {
+            from,
+            to: Some(to),
+            ..Default::default()
+        }
+    }
+
+    pub fn deploy(from: Address) -> Self {
+        Self {
+            from,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_event(mut self, event: Event) -> Self {
+        self.events.push(event);
+        self
+    }
+
+    pub fn with_created(mut self, address: Address) -> Self {
+        self.creates.push(address);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockBlock {
+    pub block: Block<Transaction>,
+    pub receipts: Vec<TransactionReceipt>,
+    /// The call tree of each transaction, as geth's `callTracer` reports it.
+    pub traces: Vec<CallFrame>,
+}
+
+impl MockBlock {
+    pub fn hash(&self) -> H256 {
+        self.block.hash.unwrap_or_default()
+    }
+
+    pub fn logs(&self) -> impl Iterator<Item = &Log> {
+        self.receipts.iter().flat_map(|receipt| receipt.logs.iter())
+    }
+
+    /// Contracts deployed in the block, by transactions or by factories.
+    pub fn created(&self) -> impl Iterator<Item = Address> + '_ {
+        self.traces.iter().flat_map(|trace| {
+            std::iter::once(trace)
+                .chain(trace.calls.iter().flatten())
+                .filter(|frame| frame.typ == "CREATE")
+                .filter_map(|frame| frame.to.as_ref()?.as_address().copied())
+        })
+    }
+}
+
+/// A scripted EVM chain. Hashes derive from the chain id, the block number and
+/// a fork counter, so the same script yields the same chain, and blocks mined
+/// after a reorg get new hashes.
+#[derive(Debug, Clone)]
+pub struct MockChain {
+    chain_id: u64,
+    blocks: Vec<MockBlock>,
+    forks: u64,
+}
+
+impl MockChain {
+    /// A chain holding only its genesis block.
+    pub fn new(chain_id: u64) -> Self {
+        let mut chain = Self {
+            chain_id,
+            blocks: Vec::new(),
+            forks: 0,
+        };
+        chain.mine(Vec::new());
+        chain
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    pub fn head(&self) -> u64 {
+        self.blocks.len() as u64 - 1
+    }
+
+    pub fn block(&self, number: u64) -> Option<&MockBlock> {
+        self.blocks.get(number as usize)
+    }
+
+    pub fn block_by_hash(&self, hash: H256) -> Option<&MockBlock> {
+        self.blocks.iter().find(|block| block.hash() == hash)
+    }
+
+    pub fn receipt(&self, hash: H256) -> Option<&TransactionReceipt> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.receipts.iter())
+            .find(|receipt| receipt.transaction_hash == hash)
+    }
+
+    /// The runtime code of `address` at block `number`: a few bytes derived
+    /// from the address once the contract is deployed, empty before.
+    pub fn code(&self, address: Address, number: u64) -> Bytes {
+        let deployed = self
+            .blocks
+            .iter()
+            .take(number as usize + 1)
+            .any(|block| block.created().any(|created| created == address));
+        if !deployed {
+            return Bytes::new();
+        }
+        let mut code = vec![0x60, 0x80, 0x60, 0x40, 0x52];
+        code.extend_from_slice(address.as_bytes());
+        code.into()
+    }
+
+    /// Resolves a block tag against the current head. Every block counts as
+    /// safe and finalized, and there are no pending blocks.
+    pub fn resolve(&self, number: BlockNumber) -> u64 {
+        match number {
+            BlockNumber::Number(number) => number.as_u64(),
+            BlockNumber::Earliest => 0,
+            _ => self.head(),
+        }
+    }
+
+    /// Mines a block with `transactions` and returns its number.
+    pub fn mine(&mut self, transactions: Vec<MockTransaction>) -> u64 {
+        let number = self.blocks.len() as u64;
+        let parent_hash = self.blocks.last().map(MockBlock::hash).unwrap_or_default();
+        let hash = self.hash(&[b"block", &number.to_be_bytes()]);
+
+        let mut block_bloom = Bloom::default();
+        let mut log_index = 0u64;
+        let mut block_transactions = Vec::with_capacity(transactions.len());
+        let mut receipts = Vec::with_capacity(transactions.len());
+        let mut traces = Vec::with_capacity(transactions.len());
+        for (index, transaction) in transactions.into_iter().enumerate() {
+            let index = index as u64;
+            let tx_hash = self.hash(&[b"tx", hash.as_bytes(), &index.to_be_bytes()]);
+
+            let mut receipt_bloom = Bloom::default();
+            let logs: Vec<Log> = transaction
+                .events
+                .iter()
+                .enumerate()
+                .map(|(transaction_log_index, event)| {
+                    let (address, topics, data) = event.to_log();
+                    receipt_bloom.accrue(BloomInput::Raw(address.as_bytes()));
+                    for topic in &topics {
+                        receipt_bloom.accrue(BloomInput::Raw(topic.as_bytes()));
+                    }
+                    log_index += 1;
+                    Log {
+                        address,
+                        topics,
+                        data,
+                        block_hash: Some(hash),
+                        block_number: Some(U64::from(number)),
+                        transaction_hash: Some(tx_hash),
+                        transaction_index: Some(U64::from(index)),
+                        log_index: Some(U256::from(log_index - 1)),
+                        transaction_log_index: Some(U256::from(transaction_log_index)),
+                        removed: Some(false),
+                        ..Default::default()
+                    }
+                })
+                .collect();
+            block_bloom.accrue_bloom(&receipt_bloom);
+
+            let nonce = U256::from(number);
+            let contract_address = match transaction.to {
+                Some(_) => None,
+                None => Some(get_contract_address(transaction.from, nonce)),
+            };
+            let callee = transaction.to.or(contract_address).unwrap_or_default();
+            traces.push(CallFrame {
+                typ: if contract_address.is_some() {
+                    "CREATE"
+                } else {
+                    "CALL"
+                }
+                .to_string(),
+                from: transaction.from,
+                to: Some(NameOrAddress::Address(callee)),
+                gas: U256::from(21_000),
+                gas_used: U256::from(21_000),
+                calls: (!transaction.creates.is_empty()).then(|| {
+                    transaction
+                        .creates
+                        .iter()
+                        .map(|created| CallFrame {
+                            typ: "CREATE".to_string(),
+                            from: callee,
+                            to: Some(NameOrAddress::Address(*created)),
+                            ..Default::default()
+                        })
+                        .collect()
+                }),
+                ..Default::default()
+            });
+
+            block_transactions.push(Transaction {
+                hash: tx_hash,
+                nonce,
+                block_hash: Some(hash),
+                block_number: Some(U64::from(number)),
+                transaction_index: Some(U64::from(index)),
+                from: transaction.from,
+                to: transaction.to,
+                gas: U256::from(21_000),
+                gas_price: Some(U256::from(1_000_000_000u64)),
+                chain_id: Some(U256::from(self.chain_id)),
+                ..Default::default()
+            });
+            receipts.push(TransactionReceipt {
+                transaction_hash: tx_hash,
+                transaction_index: U64::from(index),
+                block_hash: Some(hash),
+                block_number: Some(U64::from(number)),
+                from: transaction.from,
+                to: transaction.to,
+                cumulative_gas_used: U256::from(21_000 * (index + 1)),
+                gas_used: Some(U256::from(21_000)),
+                logs,
+                status: Some(U64::one()),
+                logs_bloom: receipt_bloom,
+                contract_address,
+                ..Default::default()
+            });
+        }
+
+        self.blocks.push(MockBlock {
+            block: Block {
+                hash: Some(hash),
+                parent_hash,
+                number: Some(U64::from(number)),
+                timestamp: U256::from(GENESIS_TIMESTAMP + number * BLOCK_INTERVAL),
+                gas_limit: U256::from(30_000_000),
+                gas_used: U256::from(21_000 * block_transactions.len() as u64),
+                logs_bloom: Some(block_bloom),
+                receipts_root: receipts_root(&receipts),
+                transactions: block_transactions,
+                ..Default::default()
+            },
+            receipts,
+            traces,
+        });
+        number
+    }
+
+    /// Mines `count` blocks without transactions and returns the new head.
+    pub fn mine_empty(&mut self, count: u64) -> u64 {
+        for _ in 0..count {
+            self.mine(Vec::new());
+        }
+        self.head()
+    }
+
+    /// Drops the last `depth` blocks, then mines `blocks` in their place and
+    /// returns the new head. The genesis block is never dropped.
+    pub fn reorg(&mut self, depth: u64, blocks: Vec<Vec<MockTransaction>>) -> u64 {
+        let depth = depth.min(self.head()) as usize;
+        self.blocks.truncate(self.blocks.len() - depth);
+        self.forks += 1;
+        for transactions in blocks {
+            self.mine(transactions);
+        }
+        self.head()
+    }
+
+    fn hash(&self, parts: &[&[u8]]) -> H256 {
+        let mut preimage = Vec::with_capacity(64);
+        preimage.extend_from_slice(&self.chain_id.to_be_bytes());
+        preimage.extend_from_slice(&self.forks.to_be_bytes());
+        for part in parts {
+            preimage.extend_from_slice(part);
+        }
+        H256(keccak256(preimage))
+    }
+}
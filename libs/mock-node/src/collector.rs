@@ -0,0 +1,105 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use ethers::utils::hex;
+use opentelemetry_proto::tonic::{
+    collector::trace::v1::{
+        trace_service_server::{TraceService, TraceServiceServer},
+        ExportTraceServiceRequest, ExportTraceServiceResponse,
+    },
+    common::v1::any_value::Value,
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::server::ServerHandle;
+
+/// A span received by a [`MockCollector`], with ids as hex strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectedSpan {
+    pub service_name: String,
+    pub name: String,
+    pub trace_id: String,
+    pub span_id: String,
+    /// Empty for the root span of a trace.
+    pub parent_span_id: String,
+}
+
+/// An OTLP gRPC trace collector that keeps every span it receives, standing in
+/// for an OpenTelemetry collector in tests.
+#[derive(Clone, Default)]
+pub struct MockCollector {
+    spans: Arc<Mutex<Vec<CollectedSpan>>>,
+}
+
+impl MockCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Listens on `addr`; port 0 picks a free port, see
+    /// [`ServerHandle::http_url`].
+    pub async fn serve(&self, addr: SocketAddr) -> std::io::Result<ServerHandle> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let service = TraceServiceServer::new(self.clone());
+        let task = tokio::spawn(async move {
+            if let Err(error) = Server::builder()
+                .add_service(service)
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+            {
+                tracing::error!("Mock collector stopped: {}", error);
+            }
+        });
+
+        Ok(ServerHandle { local_addr, task })
+    }
+
+    pub fn spans(&self) -> Vec<CollectedSpan> {
+        self.spans.lock().unwrap().clone()
+    }
+}
+
+#[tonic::async_trait]
+impl TraceService for MockCollector {
+    async fn export(
+        &self,
+        request: Request<ExportTraceServiceRequest>,
+    ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+        let mut spans = self.spans.lock().unwrap();
+        for resource_spans in request.into_inner().resource_spans {
+            let service_name = resource_spans
+                .resource
+                .iter()
+                .flat_map(|resource| &resource.attributes)
+                .find(|attribute| attribute.key == "service.name")
+                .and_then(
+                    |attribute| match attribute.value.as_ref()?.value.as_ref()? {
+                        Value::StringValue(name) => Some(name.clone()),
+                        _ => None,
+                    },
+                )
+                .unwrap_or_default();
+            for span in resource_spans
+                .scope_spans
+                .into_iter()
+                .flat_map(|scope_spans| scope_spans.spans)
+            {
+                spans.push(CollectedSpan {
+                    service_name: service_name.clone(),
+                    name: span.name,
+                    trace_id: hex::encode(span.trace_id),
+                    span_id: hex::encode(span.span_id),
+                    parent_span_id: hex::encode(span.parent_span_id),
+                });
+            }
+        }
+        Ok(Response::new(ExportTraceServiceResponse {
+            partial_success: None,
+        }))
+    }
+}
@@ -0,0 +1,132 @@
+use ethers::{
+    abi::{self, Token},
+    types::{Address, Bytes, H256, U256},
+    utils::keccak256,
+};
+use serde::{Deserialize, Serialize};
+
+/// `Transfer(address,address,uint256)`, shared by ERC-20 and ERC-721.
+pub const TRANSFER_TOPIC: &str =
+    "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+/// `TransferSingle(address,address,address,uint256,uint256)`.
+pub const TRANSFER_SINGLE_TOPIC: &str =
+    "0xc3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62";
+/// `TransferBatch(address,address,address,uint256[],uint256[])`.
+pub const TRANSFER_BATCH_TOPIC: &str =
+    "0x4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb";
+
+/// An event emitted by a mock transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Erc721Transfer {
+        contract: Address,
+        from: Address,
+        to: Address,
+        token_id: U256,
+    },
+    Erc1155TransferSingle {
+        contract: Address,
+        operator: Address,
+        from: Address,
+        to: Address,
+        id: U256,
+        value: U256,
+    },
+    Erc1155TransferBatch {
+        contract: Address,
+        operator: Address,
+        from: Address,
+        to: Address,
+        ids: Vec<U256>,
+        values: Vec<U256>,
+    },
+    /// Any other log, given as is.
+    Log {
+        address: Address,
+        topics: Vec<H256>,
+        #[serde(default)]
+        data: Bytes,
+    },
+}
+
+impl Event {
+    /// Address, topics and data of the log the event emits.
+    pub fn to_log(&self) -> (Address, Vec<H256>, Bytes) {
+        match self {
+            Event::Erc721Transfer {
+                contract,
+                from,
+                to,
+                token_id,
+            } => (
+                *contract,
+                vec![
+                    topic(TRANSFER_TOPIC),
+                    (*from).into(),
+                    (*to).into(),
+                    word(*token_id),
+                ],
+                Bytes::new(),
+            ),
+            Event::Erc1155TransferSingle {
+                contract,
+                operator,
+                from,
+                to,
+                id,
+                value,
+            } => (
+                *contract,
+                vec![
+                    topic(TRANSFER_SINGLE_TOPIC),
+                    (*operator).into(),
+                    (*from).into(),
+                    (*to).into(),
+                ],
+                abi::encode(&[Token::Uint(*id), Token::Uint(*value)]).into(),
+            ),
+            Event::Erc1155TransferBatch {
+                contract,
+                operator,
+                from,
+                to,
+                ids,
+                values,
+            } => (
+                *contract,
+                vec![
+                    topic(TRANSFER_BATCH_TOPIC),
+                    (*operator).into(),
+                    (*from).into(),
+                    (*to).into(),
+                ],
+                abi::encode(&[uints(ids), uints(values)]).into(),
+            ),
+            Event::Log {
+                address,
+                topics,
+                data,
+            } => (*address, topics.clone(), data.clone()),
+        }
+    }
+}
+
+/// A deterministic address for tests, e.g. `address(1)`.
+pub fn address(seed: u64) -> Address {
+    Address::from_slice(&keccak256(seed.to_be_bytes())[12..])
+}
+
+fn topic(signature: &str) -> H256 {
+    signature.parse().expect("valid topic constant")
+}
+
+fn word(value: U256) -> H256 {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    H256(bytes)
+}
+
+fn uints(values: &[U256]) -> Token {
+    Token::Array(values.iter().copied().map(Token::Uint).collect())
+}
@@ -0,0 +1,6 @@
+pub mod chain;
+pub mod collector;
+pub mod events;
+pub mod rpc;
+pub mod script;
+pub mod server;
@@ -0,0 +1,82 @@
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+use clap::Parser;
+use mock_node::{chain::MockChain, script, server::MockNode};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Serves a scripted EVM chain over JSON-RPC")]
+struct Args {
+    #[arg(long, default_value = "127.0.0.1:8545", help = "Address to listen on")]
+    listen: SocketAddr,
+
+    #[arg(long, default_value_t = 1, help = "Chain id to report")]
+    chain_id: u64,
+
+    #[arg(long, help = "JSON script of steps to run on startup [optional]")]
+    script: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Mine an empty block every this many milliseconds after the script [optional]"
+    )]
+    block_time_ms: Option<u64>,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "Delay added to every response, in milliseconds"
+    )]
+    latency_ms: u64,
+
+    #[arg(long, default_value_t = 0, help = "Fail every nth call, 0 never fails")]
+    fail_every: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new("info"))
+        .init();
+
+    let steps = match &args.script {
+        Some(path) => script::load(path)?,
+        None => Vec::new(),
+    };
+
+    let node = MockNode::new(MockChain::new(args.chain_id));
+    node.set_latency(Duration::from_millis(args.latency_ms));
+    node.fail_every(args.fail_every);
+
+    let server = node.serve(args.listen).await?;
+    tracing::info!(
+        "Serving chain {} at {} and {}",
+        args.chain_id,
+        server.http_url(),
+        server.ws_url()
+    );
+
+    script::run(&node, steps).await;
+
+    match args.block_time_ms {
+        Some(block_time_ms) => {
+            let mut interval =
+                tokio::time::interval(Duration::from_millis(block_time_ms));
+            interval.tick().await;
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let head = node.update(|chain| chain.mine_empty(1));
+                        tracing::debug!("Mined block {}", head);
+                    }
+                    _ = tokio::signal::ctrl_c() => break,
+                }
+            }
+        }
+        None => tokio::signal::ctrl_c().await?,
+    }
+
+    Ok(())
+}
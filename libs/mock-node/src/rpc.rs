@@ -0,0 +1,170 @@
+use std::fmt;
+
+use ethers::types::{
+    Address, BlockNumber, Filter, FilterBlockOption, Log, Topic, ValueOrArray, H256, U64,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::chain::MockChain;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Used for faults injected by the node.
+pub const SERVER_ERROR: i64 = -32000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({ "code": self.code, "message": self.message })
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RPC Error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// Answers a single call against `chain`.
+pub fn call(chain: &MockChain, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "eth_blockNumber" => Ok(json!(U64::from(chain.head()))),
+        "eth_chainId" => Ok(json!(U64::from(chain.chain_id()))),
+        "eth_getBlockByNumber" => {
+            let number: BlockNumber = param(params, 0)?;
+            let full: bool = param(params, 1)?;
+            let Some(block) = chain.block(chain.resolve(number)) else {
+                return Ok(Value::Null);
+            };
+            if full {
+                return to_json(&block.block);
+            }
+            let hashes: Vec<H256> =
+                block.block.transactions.iter().map(|tx| tx.hash).collect();
+            let mut block = to_json(&block.block)?;
+            block["transactions"] = json!(hashes);
+            Ok(block)
+        }
+        "eth_getTransactionReceipt" => {
+            let hash: H256 = param(params, 0)?;
+            chain.receipt(hash).map_or(Ok(Value::Null), to_json)
+        }
+        "eth_getCode" => {
+            let address: Address = param(params, 0)?;
+            let number: BlockNumber = param(params, 1)?;
+            Ok(json!(chain.code(address, chain.resolve(number))))
+        }
+        "debug_traceBlockByNumber" => {
+            let number: BlockNumber = param(params, 0)?;
+            let Some(block) = chain.block(chain.resolve(number)) else {
+                return Err(RpcError::new(SERVER_ERROR, "block not found"));
+            };
+            let traces: Vec<Value> = block
+                .block
+                .transactions
+                .iter()
+                .zip(&block.traces)
+                .map(|(transaction, trace)| json!({ "txHash": transaction.hash, "result": trace }))
+                .collect();
+            Ok(json!(traces))
+        }
+        "eth_getLogs" => {
+            let filter: Filter = param(params, 0)?;
+            to_json(&logs(chain, &filter)?)
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("the method {} does not exist/is not available", method),
+        )),
+    }
+}
+
+fn logs(chain: &MockChain, filter: &Filter) -> Result<Vec<Log>, RpcError> {
+    let blocks = match filter.block_option {
+        FilterBlockOption::AtBlockHash(hash) => match chain.block_by_hash(hash) {
+            Some(block) => vec![block],
+            None => return Err(RpcError::new(INVALID_PARAMS, "unknown block")),
+        },
+        FilterBlockOption::Range {
+            from_block,
+            to_block,
+        } => {
+            let from = chain.resolve(from_block.unwrap_or(BlockNumber::Latest));
+            let to = chain.resolve(to_block.unwrap_or(BlockNumber::Latest));
+            (from..=to.min(chain.head()))
+                .filter_map(|number| chain.block(number))
+                .collect()
+        }
+    };
+
+    Ok(blocks
+        .into_iter()
+        .flat_map(|block| block.logs())
+        .filter(|log| {
+            matches_address(filter.address.as_ref(), log)
+                && filter
+                    .topics
+                    .iter()
+                    .enumerate()
+                    .all(|(position, topic)| matches_topic(topic.as_ref(), log, position))
+        })
+        .cloned()
+        .collect())
+}
+
+fn matches_address(
+    address: Option<&ValueOrArray<ethers::types::Address>>,
+    log: &Log,
+) -> bool {
+    match address {
+        None => true,
+        Some(ValueOrArray::Value(address)) => log.address == *address,
+        Some(ValueOrArray::Array(addresses)) => {
+            addresses.is_empty() || addresses.contains(&log.address)
+        }
+    }
+}
+
+fn matches_topic(topic: Option<&Topic>, log: &Log, position: usize) -> bool {
+    let actual = log.topics.get(position);
+    match topic {
+        None | Some(ValueOrArray::Value(None)) => true,
+        Some(ValueOrArray::Value(Some(expected))) => actual == Some(expected),
+        Some(ValueOrArray::Array(options)) => {
+            options.is_empty()
+                || options.contains(&None)
+                || options
+                    .iter()
+                    .flatten()
+                    .any(|expected| actual == Some(expected))
+        }
+    }
+}
+
+fn param<T: DeserializeOwned>(params: &Value, index: usize) -> Result<T, RpcError> {
+    let value = params.get(index).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|e| {
+        RpcError::new(INVALID_PARAMS, format!("invalid argument {}: {}", index, e))
+    })
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))
+}
@@ -0,0 +1,88 @@
+use std::{fmt, path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{chain::MockTransaction, server::MockNode};
+
+/// One step of a chain script. Scripts are JSON arrays of steps, e.g.
+/// `[{"step": "mine_empty", "count": 10}, {"step": "reorg", "depth": 2}]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Step {
+    /// Mines one block with the given transactions.
+    Mine {
+        #[serde(default)]
+        transactions: Vec<MockTransaction>,
+    },
+    MineEmpty {
+        count: u64,
+    },
+    /// Replaces the last `depth` blocks with `blocks`.
+    Reorg {
+        depth: u64,
+        #[serde(default)]
+        blocks: Vec<Vec<MockTransaction>>,
+    },
+    Sleep {
+        ms: u64,
+    },
+    Latency {
+        ms: u64,
+    },
+    FailNext {
+        count: u64,
+    },
+    FailEvery {
+        every: u64,
+    },
+    StripReceiptLogs {
+        enabled: bool,
+    },
+}
+
+#[derive(Debug)]
+pub enum ScriptError {
+    IoError(std::io::Error),
+    ParseError(serde_json::Error),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::IoError(e) => write!(f, "Script IO Error: {}", e),
+            ScriptError::ParseError(e) => write!(f, "Script Parse Error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+pub fn load(path: &Path) -> Result<Vec<Step>, ScriptError> {
+    let content = std::fs::read(path).map_err(ScriptError::IoError)?;
+    serde_json::from_slice(&content).map_err(ScriptError::ParseError)
+}
+
+/// Runs `steps` against `node` in order.
+pub async fn run(node: &MockNode, steps: Vec<Step>) {
+    for step in steps {
+        match step {
+            Step::Mine { transactions } => {
+                let number = node.update(|chain| chain.mine(transactions));
+                tracing::info!("Mined block {}", number);
+            }
+            Step::MineEmpty { count } => {
+                let head = node.update(|chain| chain.mine_empty(count));
+                tracing::info!("Mined {} empty blocks, head is {}", count, head);
+            }
+            Step::Reorg { depth, blocks } => {
+                let head = node.update(|chain| chain.reorg(depth, blocks));
+                tracing::info!("Reorged {} blocks, head is {}", depth, head);
+            }
+            Step::Sleep { ms } => tokio::time::sleep(Duration::from_millis(ms)).await,
+            Step::Latency { ms } => node.set_latency(Duration::from_millis(ms)),
+            Step::FailNext { count } => node.fail_next(count),
+            Step::FailEvery { every } => node.fail_every(every),
+            Step::StripReceiptLogs { enabled } => node.strip_receipt_logs(enabled),
+        }
+    }
+}
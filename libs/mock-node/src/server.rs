@@ -0,0 +1,245 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    body::Bytes,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    routing::post,
+    Json, Router,
+};
+use serde_json::{json, Value};
+use tokio::{net::TcpListener, task::JoinHandle};
+
+use crate::{
+    chain::MockChain,
+    rpc::{self, RpcError, INVALID_REQUEST, PARSE_ERROR, SERVER_ERROR},
+};
+
+#[derive(Debug, Default)]
+struct Faults {
+    latency: Duration,
+    fail_next: u64,
+    fail_every: u64,
+    calls: u64,
+    strip_receipt_logs: bool,
+}
+
+impl Faults {
+    /// Counts a call and tells whether it must fail.
+    fn inject(&mut self) -> bool {
+        self.calls += 1;
+        if self.fail_next > 0 {
+            self.fail_next -= 1;
+            return true;
+        }
+        self.fail_every > 0 && self.calls.is_multiple_of(self.fail_every)
+    }
+}
+
+struct NodeState {
+    chain: MockChain,
+    faults: Faults,
+    requests: Vec<String>,
+}
+
+/// Serves a [`MockChain`] over HTTP and WebSocket JSON-RPC on the same port.
+///
+/// Clones share the chain, so tests can mine blocks or inject faults while a
+/// client is connected.
+#[derive(Clone)]
+pub struct MockNode {
+    state: Arc<Mutex<NodeState>>,
+}
+
+impl MockNode {
+    pub fn new(chain: MockChain) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(NodeState {
+                chain,
+                faults: Faults::default(),
+                requests: Vec::new(),
+            })),
+        }
+    }
+
+    /// Runs `f` on the chain, e.g. `node.update(|chain| chain.mine_empty(10))`.
+    pub fn update<T>(&self, f: impl FnOnce(&mut MockChain) -> T) -> T {
+        f(&mut self.state.lock().unwrap().chain)
+    }
+
+    /// Delays every HTTP request and WebSocket message by `latency`.
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().faults.latency = latency;
+    }
+
+    /// Fails the next `count` calls with a server error.
+    pub fn fail_next(&self, count: u64) {
+        self.state.lock().unwrap().faults.fail_next = count;
+    }
+
+    /// Serves receipts without their logs while `enabled`, as an overloaded
+    /// provider might.
+    pub fn strip_receipt_logs(&self, enabled: bool) {
+        self.state.lock().unwrap().faults.strip_receipt_logs = enabled;
+    }
+
+    /// Fails every `every`th call from now on with a server error. 0 disables
+    /// it.
+    pub fn fail_every(&self, every: u64) {
+        let faults = &mut self.state.lock().unwrap().faults;
+        faults.fail_every = every;
+        faults.calls = 0;
+    }
+
+    /// Methods called so far, in order, including failed calls.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/", post(http_handler).get(ws_handler))
+            .with_state(self.clone())
+    }
+
+    /// Binds `addr` and serves in the background until the handle is dropped.
+    /// Port 0 picks a free port.
+    pub async fn serve(&self, addr: SocketAddr) -> std::io::Result<ServerHandle> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let router = self.router();
+        let task = tokio::spawn(async move {
+            if let Err(error) = axum::serve(listener, router).await {
+                tracing::error!("Mock node stopped: {}", error);
+            }
+        });
+
+        Ok(ServerHandle { local_addr, task })
+    }
+
+    /// Answers a JSON-RPC request or batch, after the configured latency.
+    pub async fn handle(&self, body: &[u8]) -> Value {
+        let (response, latency) = {
+            let mut state = self.state.lock().unwrap();
+            let response = match serde_json::from_slice::<Value>(body) {
+                Ok(Value::Array(batch)) if !batch.is_empty() => Value::Array(
+                    batch.iter().map(|request| state.respond(request)).collect(),
+                ),
+                Ok(request) => state.respond(&request),
+                Err(e) => error_response(
+                    Value::Null,
+                    RpcError::new(PARSE_ERROR, format!("parse error: {}", e)),
+                ),
+            };
+            (response, state.faults.latency)
+        };
+
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        response
+    }
+}
+
+impl NodeState {
+    fn respond(&mut self, request: &Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return error_response(id, RpcError::new(INVALID_REQUEST, "invalid request"));
+        };
+        self.requests.push(method.to_string());
+
+        if self.faults.inject() {
+            return error_response(id, RpcError::new(SERVER_ERROR, "injected fault"));
+        }
+        let params = request.get("params").cloned().unwrap_or(json!([]));
+        match rpc::call(&self.chain, method, &params) {
+            Ok(mut result) => {
+                if self.faults.strip_receipt_logs {
+                    strip_logs(method, &mut result);
+                }
+                json!({ "jsonrpc": "2.0", "id": id, "result": result })
+            }
+            Err(error) => error_response(id, error),
+        }
+    }
+}
+
+fn strip_logs(method: &str, result: &mut Value) {
+    let receipts = match (method, result) {
+        ("eth_getTransactionReceipt", receipt @ Value::Object(_)) => {
+            vec![receipt]
+        }
+        ("eth_getBlockReceipts", Value::Array(receipts)) => receipts.iter_mut().collect(),
+        _ => return,
+    };
+    for receipt in receipts {
+        receipt["logs"] = json!([]);
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": error.to_json() })
+}
+
+async fn http_handler(State(node): State<MockNode>, body: Bytes) -> Json<Value> {
+    Json(node.handle(&body).await)
+}
+
+async fn ws_handler(
+    State(node): State<MockNode>,
+    ws: WebSocketUpgrade,
+) -> axum::response::Response {
+    ws.on_upgrade(move |socket| serve_socket(socket, node))
+}
+
+async fn serve_socket(mut socket: WebSocket, node: MockNode) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let body = match message {
+            Message::Text(text) => text.into_bytes(),
+            Message::Binary(bytes) => bytes,
+            Message::Close(_) => return,
+            _ => continue,
+        };
+        let response = node.handle(&body).await;
+        if socket
+            .send(Message::Text(response.to_string()))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// A running mock node or collector. Dropping it stops the server.
+pub struct ServerHandle {
+    pub(crate) local_addr: SocketAddr,
+    pub(crate) task: JoinHandle<()>,
+}
+
+impl ServerHandle {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn http_url(&self) -> String {
+        format!("http://{}", self.local_addr)
+    }
+
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}", self.local_addr)
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,268 @@
+use std::time::{Duration, Instant};
+
+use ethers::{
+    providers::{Http, Middleware, Provider, Ws},
+    types::{BlockNumber, Filter, U256},
+};
+use mock_node::{
+    chain::{MockChain, MockTransaction},
+    events::{address, Event, TRANSFER_BATCH_TOPIC, TRANSFER_TOPIC},
+    script::{self, Step},
+    server::{MockNode, ServerHandle},
+};
+use serde_json::{json, Value};
+
+async fn start(chain: MockChain) -> (MockNode, ServerHandle) {
+    let node = MockNode::new(chain);
+    let server = node.serve(([127, 0, 0, 1], 0).into()).await.unwrap();
+    (node, server)
+}
+
+fn nft_transfers() -> Vec<MockTransaction> {
+    vec![
+        MockTransaction::new(address(1), address(10)).with_event(Event::Erc721Transfer {
+            contract: address(10),
+            from: address(1),
+            to: address(2),
+            token_id: U256::from(7),
+        }),
+        MockTransaction::new(address(3), address(11)).with_event(
+            Event::Erc1155TransferBatch {
+                contract: address(11),
+                operator: address(3),
+                from: address(3),
+                to: address(4),
+                ids: vec![U256::from(1), U256::from(2)],
+                values: vec![U256::from(5), U256::from(6)],
+            },
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn serves_blocks_receipts_and_logs_over_http() {
+    let mut chain = MockChain::new(5);
+    chain.mine_empty(2);
+    chain.mine(nft_transfers());
+    let (_node, server) = start(chain).await;
+    let provider = Provider::<Http>::try_from(server.http_url()).unwrap();
+
+    assert_eq!(provider.get_chainid().await.unwrap(), U256::from(5));
+    assert_eq!(provider.get_block_number().await.unwrap().as_u64(), 3);
+
+    let block = provider.get_block_with_txs(3).await.unwrap().unwrap();
+    let parent = provider.get_block(2).await.unwrap().unwrap();
+    assert_eq!(block.parent_hash, parent.hash.unwrap());
+    assert_eq!(block.transactions.len(), 2);
+
+    let receipt = provider
+        .get_transaction_receipt(block.transactions[1].hash)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(receipt.logs.len(), 1);
+    assert_eq!(receipt.logs[0].log_index, Some(U256::from(1)));
+    assert_eq!(
+        receipt.logs[0].topics[0],
+        TRANSFER_BATCH_TOPIC.parse().unwrap()
+    );
+
+    let transfers = provider
+        .get_logs(
+            &Filter::new()
+                .from_block(0)
+                .to_block(BlockNumber::Latest)
+                .topic0(TRANSFER_TOPIC.parse::<ethers::types::H256>().unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(transfers.len(), 1);
+    assert_eq!(transfers[0].address, address(10));
+
+    assert!(provider.get_block(4).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn serves_the_same_chain_over_websocket() {
+    let mut chain = MockChain::new(1);
+    chain.mine(nft_transfers());
+    let (node, server) = start(chain).await;
+    let provider = Provider::<Ws>::connect(server.ws_url()).await.unwrap();
+
+    assert_eq!(provider.get_block_number().await.unwrap().as_u64(), 1);
+    node.update(|chain| chain.mine_empty(3));
+    assert_eq!(provider.get_block_number().await.unwrap().as_u64(), 4);
+
+    let block = provider.get_block(1).await.unwrap().unwrap();
+    assert_eq!(block.transactions.len(), 2);
+}
+
+#[tokio::test]
+async fn reorgs_replace_blocks_with_new_hashes() {
+    let mut chain = MockChain::new(1);
+    chain.mine_empty(5);
+    let (node, server) = start(chain).await;
+    let provider = Provider::<Http>::try_from(server.http_url()).unwrap();
+
+    let before = provider.get_block(4).await.unwrap().unwrap();
+    let kept = provider.get_block(3).await.unwrap().unwrap();
+    let head = node
+        .update(|chain| chain.reorg(2, vec![nft_transfers(), Vec::new(), Vec::new()]));
+    assert_eq!(head, 6);
+
+    let after = provider.get_block(4).await.unwrap().unwrap();
+    assert_ne!(after.hash, before.hash);
+    assert_eq!(after.parent_hash, kept.hash.unwrap());
+    assert_eq!(after.transactions.len(), 2);
+    assert_eq!(
+        provider.get_block(3).await.unwrap().unwrap().hash,
+        kept.hash
+    );
+}
+
+#[tokio::test]
+async fn injects_faults_and_latency() {
+    let (node, server) = start(MockChain::new(1)).await;
+    let provider = Provider::<Http>::try_from(server.http_url()).unwrap();
+
+    node.fail_next(1);
+    assert!(provider.get_block_number().await.is_err());
+    assert!(provider.get_block_number().await.is_ok());
+
+    node.fail_every(2);
+    assert!(provider.get_block_number().await.is_ok());
+    assert!(provider.get_block_number().await.is_err());
+    node.fail_every(0);
+
+    node.set_latency(Duration::from_millis(200));
+    let started = Instant::now();
+    provider.get_block_number().await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(200));
+
+    assert_eq!(node.requests().len(), 5);
+}
+
+#[tokio::test]
+async fn traces_deployments_and_serves_their_code() {
+    let mut chain = MockChain::new(1);
+    chain.mine_empty(1);
+    chain.mine(vec![
+        MockTransaction::deploy(address(1)),
+        MockTransaction::new(address(2), address(20)).with_created(address(30)),
+    ]);
+    let (node, _server) = start(chain).await;
+
+    let deployed = node
+        .update(|chain| chain.block(2).unwrap().receipts[0].contract_address)
+        .unwrap();
+    let code = |address: ethers::types::Address, block: u64| {
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_getCode",
+            "params": [address, format!("{:#x}", block)] })
+        .to_string()
+    };
+    assert_eq!(
+        node.handle(code(address(30), 1).as_bytes()).await["result"],
+        json!("0x")
+    );
+    let result = node.handle(code(address(30), 2).as_bytes()).await["result"].clone();
+    assert!(result
+        .as_str()
+        .unwrap()
+        .ends_with(&format!("{:x}", address(30))));
+    assert_ne!(
+        node.handle(code(deployed, 2).as_bytes()).await["result"],
+        json!("0x")
+    );
+
+    let traces = node
+        .handle(
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "debug_traceBlockByNumber",
+                "params": ["0x2", { "tracer": "callTracer" }] })
+            .to_string()
+            .as_bytes(),
+        )
+        .await;
+    let traces = traces["result"].as_array().unwrap();
+    assert_eq!(traces.len(), 2);
+    assert_eq!(traces[0]["result"]["type"], json!("CREATE"));
+    assert_eq!(traces[1]["result"]["type"], json!("CALL"));
+    assert_eq!(traces[1]["result"]["calls"][0]["type"], json!("CREATE"));
+    assert_eq!(
+        traces[1]["result"]["calls"][0]["to"],
+        json!(format!("{:?}", address(30)))
+    );
+}
+
+#[tokio::test]
+async fn answers_batches_and_rejects_unknown_methods() {
+    let node = MockNode::new(MockChain::new(1));
+    let response = node
+        .handle(
+            json!([
+                { "jsonrpc": "2.0", "id": 1, "method": "eth_chainId", "params": [] },
+                { "jsonrpc": "2.0", "id": 2, "method": "eth_sendRawTransaction", "params": [] },
+            ])
+            .to_string()
+            .as_bytes(),
+        )
+        .await;
+
+    assert_eq!(response[0]["result"], json!("0x1"));
+    assert_eq!(response[1]["error"]["code"], json!(-32601));
+    assert_eq!(node.handle(b"{").await["error"]["code"], json!(-32700));
+    assert_eq!(
+        node.handle(json!({ "id": 3 }).to_string().as_bytes()).await["id"],
+        Value::from(3)
+    );
+}
+
+#[tokio::test]
+async fn runs_scripts() {
+    let steps: Vec<Step> = serde_json::from_value(json!([
+        { "step": "mine_empty", "count": 3 },
+        { "step": "mine", "transactions": [{
+            "from": format!("{:?}", address(1)),
+            "to": format!("{:?}", address(10)),
+            "events": [{
+                "event": "erc1155_transfer_single",
+                "contract": format!("{:?}", address(10)),
+                "operator": format!("{:?}", address(1)),
+                "from": format!("{:?}", address(1)),
+                "to": format!("{:?}", address(2)),
+                "id": "0x1",
+                "value": "0x2",
+            }],
+        }] },
+        { "step": "reorg", "depth": 1, "blocks": [[], []] },
+    ]))
+    .unwrap();
+    let node = MockNode::new(MockChain::new(1));
+
+    script::run(&node, steps).await;
+
+    assert_eq!(node.update(|chain| chain.head()), 5);
+    let logs = node.update(|chain| chain.block(4).unwrap().logs().count());
+    assert_eq!(logs, 0);
+}
+
+#[tokio::test]
+async fn loads_the_example_script() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("scripts/nft-transfers.json");
+    let steps = script::load(&path).unwrap();
+
+    assert_eq!(steps.len(), 7);
+    let Step::Mine { transactions } = &steps[1] else {
+        panic!("expected a mine step, got {:?}", steps[1]);
+    };
+    let logs: Vec<_> = transactions[1].events.iter().map(Event::to_log).collect();
+    assert_eq!(logs[1].1[0], TRANSFER_BATCH_TOPIC.parse().unwrap());
+    assert_eq!(
+        logs[1].1[3],
+        address_topic("0x3333333333333333333333333333333333333333")
+    );
+}
+
+fn address_topic(address: &str) -> ethers::types::H256 {
+    address.parse::<ethers::types::Address>().unwrap().into()
+}
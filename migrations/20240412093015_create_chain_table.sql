@@ -0,0 +1,6 @@
+-- Chains known to the watcher, with the genesis hash seen on first sync.
+CREATE TABLE chain (
+    chain_id INTEGER PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    genesis_hash VARCHAR(66) NOT NULL
+);
@@ -0,0 +1,18 @@
+-- Drop the duplicate rows left by re-runs before enforcing uniqueness.
+DELETE FROM block a
+USING block b
+WHERE a.chain_id = b.chain_id
+    AND a.block_number = b.block_number
+    AND a.id > b.id;
+
+ALTER TABLE block
+    ADD COLUMN hash VARCHAR(66),
+    ADD COLUMN parent_hash VARCHAR(66),
+    ADD COLUMN "timestamp" TIMESTAMPTZ,
+    ADD COLUMN miner VARCHAR(42),
+    ADD COLUMN gas_used NUMERIC(78, 0),
+    ADD COLUMN gas_limit NUMERIC(78, 0),
+    ADD COLUMN base_fee_per_gas NUMERIC(78, 0),
+    ADD COLUMN transaction_count INTEGER,
+    ADD COLUMN log_count INTEGER,
+    ADD CONSTRAINT block_chain_id_block_number_key UNIQUE (chain_id, block_number);
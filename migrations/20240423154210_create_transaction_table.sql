@@ -0,0 +1,23 @@
+CREATE TABLE transaction (
+    id BIGSERIAL PRIMARY KEY,
+    chain_id INTEGER NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash VARCHAR(66),
+    hash VARCHAR(66) NOT NULL,
+    tx_index BIGINT NOT NULL,
+    "from" VARCHAR(42) NOT NULL,
+    "to" VARCHAR(42),
+    value NUMERIC(78, 0) NOT NULL,
+    nonce NUMERIC(78, 0) NOT NULL,
+    input_selector VARCHAR(10),
+    gas_used NUMERIC(78, 0),
+    effective_gas_price NUMERIC(78, 0),
+    status SMALLINT,
+    contract_address VARCHAR(42),
+    "type" SMALLINT,
+    CONSTRAINT transaction_chain_id_hash_key UNIQUE (chain_id, hash)
+);
+
+CREATE INDEX transaction_chain_id_block_number_idx ON transaction (chain_id, block_number);
+CREATE INDEX transaction_from_idx ON transaction ("from");
+CREATE INDEX transaction_to_idx ON transaction ("to");
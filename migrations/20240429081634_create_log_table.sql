@@ -0,0 +1,18 @@
+-- Raw archive of every published log. Partitions cover fixed block ranges and
+-- are created by chain-watcher the first time a range is written.
+CREATE TABLE log (
+    chain_id INTEGER NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash VARCHAR(66),
+    tx_hash VARCHAR(66),
+    tx_index BIGINT,
+    log_index BIGINT NOT NULL,
+    address VARCHAR(42) NOT NULL,
+    topics VARCHAR(66)[] NOT NULL,
+    data TEXT NOT NULL,
+    removed BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (chain_id, block_number, log_index)
+) PARTITION BY RANGE (block_number);
+
+CREATE INDEX log_address_idx ON log (chain_id, address, block_number);
+CREATE INDEX log_topic0_idx ON log (chain_id, (topics[1]), block_number);
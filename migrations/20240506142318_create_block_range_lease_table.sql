@@ -0,0 +1,13 @@
+-- Block ranges of a sharded backfill. Watcher instances claim a pending range,
+-- or one whose lease expired, and mark it done once every block is indexed.
+CREATE TABLE block_range_lease (
+    chain_id INTEGER NOT NULL,
+    start_block BIGINT NOT NULL,
+    end_block BIGINT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    owner VARCHAR(255),
+    expires_at TIMESTAMPTZ,
+    PRIMARY KEY (chain_id, start_block)
+);
+
+CREATE INDEX block_range_lease_status_idx ON block_range_lease (chain_id, status, start_block);
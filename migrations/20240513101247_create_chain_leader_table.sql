@@ -0,0 +1,8 @@
+-- Leader lease of each chain's live sync. The fencing token grows with every
+-- change of leader and is checked by Redis, so a deposed leader cannot publish.
+CREATE TABLE chain_leader (
+    chain_id INTEGER PRIMARY KEY,
+    owner VARCHAR(255) NOT NULL,
+    fencing_token BIGINT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
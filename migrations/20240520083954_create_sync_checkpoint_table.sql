@@ -0,0 +1,10 @@
+-- Progress of each sync lane. The live lane starts at the head seen on the
+-- first run; the backfill lane works up from the configured start block to it.
+CREATE TABLE sync_checkpoint (
+    chain_id INTEGER NOT NULL,
+    lane VARCHAR(16) NOT NULL,
+    start_block BIGINT NOT NULL,
+    last_block BIGINT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (chain_id, lane)
+);
@@ -0,0 +1,8 @@
+-- Cache of `--start-time` and `--end-time` lookups: the first block of a chain
+-- mined at or after a Unix timestamp. Only final blocks are cached.
+CREATE TABLE block_time (
+    chain_id INTEGER NOT NULL,
+    timestamp BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    PRIMARY KEY (chain_id, timestamp)
+);